@@ -1,27 +1,35 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_binary, Binary, Coin, CosmosMsg, Deps, DepsMut, Env, MessageInfo, Response, StdResult,
+    coin, to_json_binary, wasm_execute, BankMsg, Binary, Coin, CosmosMsg, Deps, DepsMut, Env,
+    MessageInfo, Reply, Response, StdError, StdResult, SubMsg, SubMsgResult, Uint128,
 };
 
 use cw2::set_contract_version;
-use cw_flash_loan_gateway::helpers::{Contract as FlashLoanGateway, RequestFlashLoanProps};
+use cw_flash_loan_gateway::helpers::{
+    parse_flash_loan_reply, Contract as FlashLoanGateway, RequestFlashLoanProps,
+};
+use cw_utils::parse_execute_response_data;
 
 use crate::{
     error::ContractError,
-    msg::{ExecuteMsg, InstantiateMsg, QueryMsg},
-    state::{Config, CONFIG},
+    msg::{ExecuteMsg, FlashLoanProfit, InstantiateMsg, MigrateMsg, QueryMsg, StateResponse},
+    state::{Config, FlashLoan, CONFIG, FLASH_LOAN},
+    swap::{swap_route_msgs, SwapStep},
 };
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:cw-flash-loans-borrower";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+const REPLY_ON_FLASH_LOAN: u64 = 1;
+const REPLY_ON_FLASH_LOAN_CLOSED: u64 = 2;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
     _env: Env,
-    _info: MessageInfo,
+    info: MessageInfo,
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
@@ -31,6 +39,7 @@ pub fn instantiate(
     CONFIG.save(
         deps.storage,
         &Config {
+            owner: info.sender,
             cw_gateway_contract_addr,
         },
     )?;
@@ -46,19 +55,30 @@ pub fn execute(
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
     match msg {
-        ExecuteMsg::OpenFlashLoan { asset_to_borrow } => {
-            exectute_open_flash_loan(deps, env, info, asset_to_borrow)
-        }
+        ExecuteMsg::OpenFlashLoan {
+            asset,
+            min_profit,
+            msgs,
+            swap_route,
+        } => exectute_open_flash_loan(deps, env, info, asset, min_profit, msgs, swap_route),
         ExecuteMsg::OnFlashLoanProvided {} => execute_on_flash_loan_provided(deps, env, info),
+        ExecuteMsg::SwapRoute {} => execute_swap_route(deps, env, info),
+        ExecuteMsg::CloseFlashLoan {} => execute_close_flash_loan(deps, env, info),
+        ExecuteMsg::Withdraw { amount, recipient } => {
+            execute_withdraw(deps, env, info, amount, recipient)
+        }
     }
 }
 
 /// Handler initiating a flash loan
 fn exectute_open_flash_loan(
     deps: DepsMut,
-    _env: Env,
-    _info: MessageInfo,
+    env: Env,
+    info: MessageInfo,
     asset_to_borrow: Coin,
+    min_profit: Option<Uint128>,
+    msgs: Vec<CosmosMsg>,
+    swap_route: Vec<SwapStep>,
 ) -> Result<Response, ContractError> {
     println!(
         "[Borrower: exectute_open_flash_loan]: can I borrow this asset? {:?}",
@@ -67,23 +87,47 @@ fn exectute_open_flash_loan(
 
     let config = CONFIG.load(deps.storage)?;
 
+    // Each loan burns fees, so only the owner gets to decide when one is opened
+    if info.sender != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // Remember where we started from, so the profit can be measured once the loan is repaid
+    let initial_balance = deps
+        .querier
+        .query_balance(env.contract.address.clone(), asset_to_borrow.denom.as_str())?
+        .amount;
+
+    FLASH_LOAN.save(
+        deps.storage,
+        &FlashLoan {
+            asset: asset_to_borrow.clone(),
+            min_profit,
+            initial_balance,
+            msgs,
+            swap_route,
+        },
+    )?;
+
     // This one tells the gateway about which message to execute while calling
     // the borrower contract back with the flash-loaned funds.
     let on_flash_loan_provided_hook = &ExecuteMsg::OnFlashLoanProvided {};
 
-    let msgs = vec![
-        FlashLoanGateway(config.cw_gateway_contract_addr).request_flash_loan(
-            RequestFlashLoanProps {
-                asset: asset_to_borrow,
-                on_flash_loan_provided_hook,
-            },
-        )?,
-    ];
+    let msg = FlashLoanGateway(config.cw_gateway_contract_addr).request_flash_loan(
+        RequestFlashLoanProps {
+            asset: asset_to_borrow,
+            on_flash_loan_provided_hook,
+            referrer: None,
+        },
+    )?;
 
-    Ok(Response::new().add_messages(msgs).add_attributes(vec![
-        ("module", "borrower"),
-        ("action", "execute_open_flash_loan"),
-    ]))
+    // The reply passes the loan's profit on to the owner
+    Ok(Response::new()
+        .add_submessage(SubMsg::reply_on_success(msg, REPLY_ON_FLASH_LOAN))
+        .add_attributes(vec![
+            ("module", "borrower"),
+            ("action", "execute_open_flash_loan"),
+        ]))
 }
 
 /// Handler utilising the flash loan.
@@ -91,40 +135,247 @@ fn exectute_open_flash_loan(
 fn execute_on_flash_loan_provided(
     deps: DepsMut,
     env: Env,
-    _info: MessageInfo,
+    info: MessageInfo,
 ) -> Result<Response, ContractError> {
     let config = CONFIG.load(deps.storage)?;
 
+    // Only the gateway is allowed to call the hook, it's the one holding the loan state
+    if info.sender != config.cw_gateway_contract_addr {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let flash_loan = FLASH_LOAN.load(deps.storage)?;
+
     // Money's in — time for swaps.
 
-    let available_luna = deps
-        .querier
-        .query_balance(env.contract.address.clone(), "uluna")?;
+    let available_funds = deps.querier.query_balance(
+        env.contract.address.clone(),
+        flash_loan.asset.denom.as_str(),
+    )?;
     println!(
-        "[Borrower: execute_on_flash_loan_provided]: available uluna = {:?}",
-        available_luna
+        "[Borrower: execute_on_flash_loan_provided]: available funds = {:?}",
+        available_funds
     );
 
-    let mut msgs: Vec<CosmosMsg> = vec![
-        // TODO: add any arbitrary messages to perform required transactions
-    ];
+    // Messages the owner asked for when opening the loan, e.g. arbitrage swaps
+    let mut msgs = flash_loan.msgs;
+
+    // The route can only be priced once those messages moved the pools
+    if !flash_loan.swap_route.is_empty() {
+        msgs.push(
+            wasm_execute(
+                env.contract.address.to_string(),
+                &ExecuteMsg::SwapRoute {},
+                vec![],
+            )?
+            .into(),
+        );
+    }
 
     // Repay the flash loan
     let flash_loan_gateway = FlashLoanGateway(config.cw_gateway_contract_addr);
 
     let total_repayment =
-        flash_loan_gateway.get_debt_remaining(&deps.querier, env.contract.address)?;
+        flash_loan_gateway.get_debt_remaining(&deps.querier, env.contract.address.clone())?;
 
     msgs.push(flash_loan_gateway.repay_flash_loan(total_repayment)?);
 
+    // Once everything else is done, check whether the loan was worth it.
+    // Its reply makes the profit the hook's data, which the gateway hands back.
+    let close_msg = wasm_execute(
+        env.contract.address.to_string(),
+        &ExecuteMsg::CloseFlashLoan {},
+        vec![],
+    )?;
+
+    Ok(Response::new()
+        .add_messages(msgs)
+        .add_submessage(SubMsg::reply_on_success(
+            close_msg,
+            REPLY_ON_FLASH_LOAN_CLOSED,
+        ))
+        .add_attributes(vec![
+            ("module", "borrower"),
+            ("action", "execute_on_flash_loan_provided"),
+        ]))
+}
+
+/// Handler swapping the flash-loaned funds along the loan's route,
+/// right after the owner's messages and before the loan gets repaid.
+fn execute_swap_route(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    // This step is only ever scheduled by the contract itself
+    if info.sender != env.contract.address {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let flash_loan = FLASH_LOAN.load(deps.storage)?;
+
+    let (msgs, swap_return) = swap_route_msgs(
+        deps.api,
+        &deps.querier,
+        &env.contract.address,
+        flash_loan.asset,
+        &flash_loan.swap_route,
+    )?;
+    println!(
+        "[Borrower: execute_swap_route]: swap route returns {:?}",
+        swap_return
+    );
+
     Ok(Response::new().add_messages(msgs).add_attributes(vec![
         ("module", "borrower"),
-        ("action", "execute_on_flash_loan_provided"),
+        ("action", "execute_swap_route"),
+        ("swap_return", &swap_return.to_string()),
     ]))
 }
 
+/// Handler finalising the flash loan after it has been repaid.
+/// Fails the whole transaction when the loan didn't bring the expected profit.
+fn execute_close_flash_loan(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    // This step is only ever scheduled by the contract itself
+    if info.sender != env.contract.address {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let FlashLoan {
+        asset,
+        min_profit,
+        initial_balance,
+        ..
+    } = FLASH_LOAN.load(deps.storage)?;
+
+    let final_balance = deps
+        .querier
+        .query_balance(env.contract.address.clone(), asset.denom.as_str())?
+        .amount;
+
+    println!(
+        "[Borrower: execute_close_flash_loan]: {} balance went from {} to {}",
+        &asset.denom, initial_balance, final_balance
+    );
+
+    if let Some(min_profit) = min_profit {
+        if final_balance
+            < initial_balance
+                .checked_add(min_profit)
+                .map_err(StdError::from)?
+        {
+            return Err(ContractError::ProfitBelowMinimum {
+                min_profit,
+                initial_balance,
+                final_balance,
+            });
+        }
+    }
+
+    FLASH_LOAN.remove(deps.storage);
+
+    let profit = FlashLoanProfit {
+        initial_balance: coin(initial_balance.u128(), asset.denom.as_str()),
+        final_balance: coin(final_balance.u128(), asset.denom.as_str()),
+        profit: coin(
+            final_balance.saturating_sub(initial_balance).u128(),
+            asset.denom.as_str(),
+        ),
+    };
+
+    Ok(Response::new()
+        .set_data(to_json_binary(&profit)?)
+        .add_attributes(vec![
+            ("module", "borrower"),
+            ("action", "execute_close_flash_loan"),
+            ("profit", &profit.profit.to_string()),
+        ]))
+}
+
+/// Handler sweeping accumulated profits out of the borrower contract.
+fn execute_withdraw(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    amount: Coin,
+    recipient: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    if info.sender != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let recipient = deps.api.addr_validate(&recipient)?;
+
+    let msg = BankMsg::Send {
+        to_address: recipient.to_string(),
+        amount: vec![amount],
+    };
+
+    Ok(Response::new().add_message(msg).add_attributes(vec![
+        ("module", "borrower"),
+        ("action", "execute_withdraw"),
+        ("recipient", recipient.as_str()),
+    ]))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(_deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
+    match msg.id {
+        REPLY_ON_FLASH_LOAN => reply_on_flash_loan(msg),
+        REPLY_ON_FLASH_LOAN_CLOSED => reply_on_flash_loan_closed(msg.result),
+        id => Err(StdError::generic_err(format!("reply id `{:?}` is invalid", id)).into()),
+    }
+}
+
+/// Responds to the owner with the profit the hook reported to the gateway
+fn reply_on_flash_loan(msg: Reply) -> Result<Response, ContractError> {
+    let loan = parse_flash_loan_reply(msg)?;
+
+    let mut response = Response::new().add_attributes(vec![
+        ("module", "borrower"),
+        ("action", "reply_on_flash_loan"),
+        ("loan_id", &loan.loan_id.to_string()),
+    ]);
+    if let Some(data) = loan.hook_data {
+        response = response.set_data(data);
+    }
+
+    Ok(response)
+}
+
+/// Passes the profit `CloseFlashLoan` responded with on, as the hook's data
+fn reply_on_flash_loan_closed(result: SubMsgResult) -> Result<Response, ContractError> {
+    let data = result
+        .into_result()
+        .map_err(StdError::generic_err)?
+        .data
+        .ok_or_else(|| StdError::generic_err("Closing the flash loan responded without data"))?;
+    let profit = parse_execute_response_data(&data)
+        .map_err(|err| StdError::generic_err(err.to_string()))?
+        .data
+        .ok_or_else(|| StdError::generic_err("Closing the flash loan responded without data"))?;
+
+    Ok(Response::new().set_data(profit))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::new().add_attributes(vec![("module", "borrower"), ("action", "migrate")]))
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(_deps: Deps, _env: Env, _msg: QueryMsg) -> StdResult<Binary> {
-    let stub: [u8; 0] = [];
-    to_binary(&stub)
+pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::State => to_json_binary(&StateResponse {
+            state: CONFIG.load(deps.storage)?,
+        }),
+    }
 }
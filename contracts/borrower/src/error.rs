@@ -1,4 +1,4 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{StdError, Uint128};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -10,8 +10,14 @@ pub enum ContractError {
     Unauthorized {},
 
     #[error("Custom Error val: {val:?}")]
-    CustomError{val: String},
+    CustomError { val: String },
 
+    #[error("Profit below minimum of {min_profit}: balance went from {initial_balance} to {final_balance}")]
+    ProfitBelowMinimum {
+        min_profit: Uint128,
+        initial_balance: Uint128,
+        final_balance: Uint128,
+    },
     // Add any other custom errors you like here.
     // Look at https://docs.rs/thiserror/1.0.21/thiserror/ for details.
 }
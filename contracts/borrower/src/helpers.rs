@@ -1,11 +1,9 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{
-    to_binary, Addr, Coin, CosmosMsg, StdResult, WasmMsg,
-};
+use cosmwasm_std::{to_json_binary, Addr, Coin, CosmosMsg, StdResult, WasmMsg};
 
-use crate::msg::{ExecuteMsg};
+use crate::msg::ExecuteMsg;
 
 /// CwBridgeContract is a wrapper around Addr that provides a lot of helpers
 /// for working with this.
@@ -17,8 +15,12 @@ impl Contract {
         self.0.clone()
     }
 
-    pub fn call<T: Into<ExecuteMsg>>(&self, msg: T, funds: Option<Vec<Coin>>) -> StdResult<CosmosMsg> {
-        let msg = to_binary(&msg.into())?;
+    pub fn call<T: Into<ExecuteMsg>>(
+        &self,
+        msg: T,
+        funds: Option<Vec<Coin>>,
+    ) -> StdResult<CosmosMsg> {
+        let msg = to_json_binary(&msg.into())?;
         Ok(WasmMsg::Execute {
             contract_addr: self.addr().into(),
             msg,
@@ -2,133 +2,126 @@
 mod tests {
     use crate::helpers::Contract as CwBorrowerContract;
     use crate::msg::InstantiateMsg;
-    use cosmwasm_std::{coin, Addr, Coin, Empty};
+    use cosmwasm_std::{coin, Addr, Coin, Decimal, Empty, Uint128};
     use cw_flash_loan_gateway::{self, helpers::Contract as CwGatewayContract};
-    use cw_flash_loan_vault::{self, helpers::Contract as CwVaultContract};
-    use cw_multi_test::{App, AppBuilder, Contract, ContractWrapper, Executor};
+    use cw_flash_loan_mock_amm::msg::{
+        Asset, AssetInfo, InstantiateMsg as PoolInstantiateMsg, QueryMsg as PoolQueryMsg,
+        SimulationResponse,
+    };
+    use cw_flash_loan_testing::deployment::DeploymentApp;
+    use cw_flash_loan_testing::{Deployment, DeploymentBuilder, ADMIN};
+    use cw_flash_loan_vault;
+    use cw_multi_test::{Contract, ContractWrapper, Executor};
+
+    use self::osmosis::MockPoolManager;
+
+    type TestApp = DeploymentApp<MockPoolManager>;
 
     pub fn cw_borrower_contract() -> Box<dyn Contract<Empty>> {
         let contract = ContractWrapper::new(
             crate::contract::execute,
             crate::contract::instantiate,
             crate::contract::query,
-        );
-
-        Box::new(contract)
-    }
-
-    pub fn cw_gateway_contract() -> Box<dyn Contract<Empty>> {
-        let contract = ContractWrapper::new(
-            cw_flash_loan_gateway::contract::execute,
-            cw_flash_loan_gateway::contract::instantiate,
-            cw_flash_loan_gateway::contract::query,
         )
-        .with_reply(cw_flash_loan_gateway::contract::reply);
+        .with_reply(crate::contract::reply);
 
         Box::new(contract)
     }
 
-    pub fn cw_vault_contract() -> Box<dyn Contract<Empty>> {
+    pub fn cw_mock_amm_contract() -> Box<dyn Contract<Empty>> {
         let contract = ContractWrapper::new(
-            cw_flash_loan_vault::contract::execute,
-            cw_flash_loan_vault::contract::instantiate,
-            cw_flash_loan_vault::contract::query,
-        )
-        .with_reply(cw_flash_loan_vault::contract::reply);
+            cw_flash_loan_mock_amm::contract::execute,
+            cw_flash_loan_mock_amm::contract::instantiate,
+            cw_flash_loan_mock_amm::contract::query,
+        );
 
         Box::new(contract)
     }
 
     const USER: &str = "USER";
-    const ADMIN: &str = "ADMIN";
-
-    fn mock_app(initial_user_balance: Coin) -> App {
-        AppBuilder::new().build(|router, _, storage| {
-            router
-                .bank
-                .init_balance(storage, &Addr::unchecked(USER), vec![initial_user_balance])
-                .unwrap();
-
-            router
-                .bank
-                .init_balance(
-                    storage,
-                    &Addr::unchecked(ADMIN),
-                    vec![coin(1_000_000_000_000_000_000, "uluna")],
-                )
-                .unwrap();
-        })
-    }
 
     struct ProperInstantiateProps {
         initial_user_coins: Coin,
         initial_vault_coins: Coin,
     }
 
+    /// Deploys a vault lending `initial_vault_coins` and the borrower, funded with
+    /// `initial_user_coins`, in an app with Osmosis' pool manager
     fn proper_instantiate(
         props: ProperInstantiateProps,
-    ) -> (App, CwBorrowerContract, CwVaultContract, CwGatewayContract) {
+    ) -> (Deployment<MockPoolManager>, CwBorrowerContract) {
         let ProperInstantiateProps {
             initial_user_coins,
             initial_vault_coins,
         } = props;
-        let mut app = mock_app(initial_user_coins.clone());
 
-        let cw_vault_id = app.store_code(cw_vault_contract());
-        let msg = cw_flash_loan_vault::msg::InstantiateMsg {};
-        let cw_vault_contract_addr = app
-            .instantiate_contract(
-                cw_vault_id,
-                Addr::unchecked(ADMIN),
-                &msg,
-                &[initial_vault_coins],
-                "test",
-                None,
-            )
-            .unwrap();
+        // the admin seeds pools on both sides of the vault's denom
+        let pool_denom = if initial_vault_coins.denom == "uusd" {
+            "uluna"
+        } else {
+            "uusd"
+        };
+        let mut deployment = DeploymentBuilder::new()
+            .with_stargate(MockPoolManager::default())
+            .with_denom(&initial_vault_coins.denom)
+            .with_liquidity(initial_vault_coins.amount.u128())
+            .with_balance(USER, vec![initial_user_coins.clone()])
+            .with_balance(ADMIN, vec![coin(1_000_000_000_000_000_000, pool_denom)])
+            .build();
+
+        let msg = InstantiateMsg {
+            cw_gateway_contract_addr: deployment.gateway.addr().to_string(),
+        };
+        let cw_borrower_contract_addr = deployment.register_borrower(
+            cw_borrower_contract(),
+            &msg,
+            &[initial_user_coins],
+            "borrower",
+        );
 
-        let cw_vault_contract = CwVaultContract(cw_vault_contract_addr.into());
+        (deployment, CwBorrowerContract(cw_borrower_contract_addr))
+    }
 
-        let cw_gateway_id = app.store_code(cw_gateway_contract());
-        let msg = cw_flash_loan_gateway::msg::InstantiateMsg {
-            cw_vault_contract_addr: cw_vault_contract.addr().to_string(),
+    fn instantiate_pool(app: &mut TestApp, reserves: Vec<Coin>) -> Addr {
+        let cw_mock_amm_id = app.store_code(cw_mock_amm_contract());
+        let msg = PoolInstantiateMsg {
+            denoms: [reserves[0].denom.clone(), reserves[1].denom.clone()],
+            commission_rate: Decimal::permille(3),
         };
-        let cw_gateway_contract_addr = app.instantiate_contract(
-            cw_gateway_id,
+
+        app.instantiate_contract(
+            cw_mock_amm_id,
             Addr::unchecked(ADMIN),
             &msg,
-            &[],
-            "test",
+            &reserves,
+            "pool",
             None,
-        );
-
-        let cw_gateway_contract_addr = cw_gateway_contract_addr.unwrap();
+        )
+        .unwrap()
+    }
 
-        let cw_gateway_contract = CwGatewayContract(cw_gateway_contract_addr);
+    fn native(asset: &Coin) -> Asset {
+        Asset {
+            info: AssetInfo::NativeToken {
+                denom: asset.denom.clone(),
+            },
+            amount: asset.amount,
+        }
+    }
 
-        let cw_borrower_id = app.store_code(cw_borrower_contract());
-        let msg = InstantiateMsg {
-            cw_gateway_contract_addr: cw_gateway_contract.addr().to_string(),
-        };
-        let cw_borrower_contract_addr = app
-            .instantiate_contract(
-                cw_borrower_id,
-                Addr::unchecked(ADMIN),
-                &msg,
-                &[initial_user_coins],
-                "test",
-                None,
+    fn simulate_swap(app: &TestApp, pool: &Addr, offer: &Coin) -> Uint128 {
+        let simulation: SimulationResponse = app
+            .wrap()
+            .query_wasm_smart(
+                pool,
+                &PoolQueryMsg::Simulation {
+                    offer_asset: native(offer),
+                    ask_asset_info: None,
+                },
             )
             .unwrap();
 
-        let cw_borrower_contract = CwBorrowerContract(cw_borrower_contract_addr);
-
-        (
-            app,
-            cw_borrower_contract,
-            cw_vault_contract,
-            cw_gateway_contract,
-        )
+        simulation.return_amount
     }
 
     mod transfers {
@@ -140,73 +133,2285 @@ mod tests {
             let initial_user_coins = coin(50, "uluna");
             let expected_coin_to_borrow = coin(1_000, "uluna");
 
-            let (mut app, cw_borrower_contract, cw_vault_contract, cw_gateway_contract) =
+            let (mut deployment, cw_borrower_contract) =
                 proper_instantiate(ProperInstantiateProps {
                     initial_vault_coins: initial_vault_coins.clone(),
                     initial_user_coins: initial_user_coins.clone(),
                 });
 
-            print_balances(
-                "Initial balances",
-                &app,
-                &cw_borrower_contract,
-                &cw_gateway_contract,
-                &cw_vault_contract,
-            );
+            deployment.print_balances("Initial balances", &[("Borrower", &cw_borrower_contract.0)]);
 
             let cosmos_msg = cw_borrower_contract
                 .call(
                     crate::msg::ExecuteMsg::OpenFlashLoan {
-                        asset_to_borrow: expected_coin_to_borrow.clone(),
+                        asset: expected_coin_to_borrow.clone(),
+                        min_profit: None,
+                        msgs: vec![],
+                        swap_route: vec![],
                     },
                     None,
                 )
                 .unwrap();
 
-            let flash_loan_result = app.execute(cw_gateway_contract.addr(), cosmos_msg);
+            let flash_loan_result = deployment.app.execute(Addr::unchecked(ADMIN), cosmos_msg);
 
-            print_balances(
-                "End balances",
-                &app,
-                &cw_borrower_contract,
-                &cw_gateway_contract,
-                &cw_vault_contract,
-            );
+            deployment.print_balances("End balances", &[("Borrower", &cw_borrower_contract.0)]);
 
             if flash_loan_result.is_err() {
                 println!("Response: {:?}", &flash_loan_result);
             }
 
             assert!(flash_loan_result.is_ok());
+
+            deployment.assert_treasury_balanced();
         }
+    }
 
-        fn print_balances(
-            label: &str,
-            app: &App,
-            cw_borrower_contract: &CwBorrowerContract,
-            cw_gateway_contract: &CwGatewayContract,
-            cw_vault_contract: &CwVaultContract,
-        ) {
-            let borrower_coins = app
+    mod access_control {
+        use super::*;
+        use crate::msg::ExecuteMsg;
+        use crate::ContractError;
+
+        #[test]
+        fn only_owner_can_open_flash_loan() {
+            let (mut deployment, cw_borrower_contract) =
+                proper_instantiate(ProperInstantiateProps {
+                    initial_vault_coins: coin(200_000, "uluna"),
+                    initial_user_coins: coin(50, "uluna"),
+                });
+
+            let cosmos_msg = cw_borrower_contract
+                .call(
+                    ExecuteMsg::OpenFlashLoan {
+                        asset: coin(1_000, "uluna"),
+                        min_profit: None,
+                        msgs: vec![],
+                        swap_route: vec![],
+                    },
+                    None,
+                )
+                .unwrap();
+
+            let err = deployment
+                .app
+                .execute(Addr::unchecked(USER), cosmos_msg)
+                .unwrap_err();
+
+            assert!(matches!(
+                err.downcast_ref::<ContractError>(),
+                Some(ContractError::Unauthorized {})
+            ));
+
+            deployment.assert_treasury_balanced();
+        }
+
+        #[test]
+        fn only_gateway_can_call_the_hook() {
+            let (mut deployment, cw_borrower_contract) =
+                proper_instantiate(ProperInstantiateProps {
+                    initial_vault_coins: coin(200_000, "uluna"),
+                    initial_user_coins: coin(50, "uluna"),
+                });
+
+            let cosmos_msg = cw_borrower_contract
+                .call(ExecuteMsg::OnFlashLoanProvided {}, None)
+                .unwrap();
+
+            let err = deployment
+                .app
+                .execute(Addr::unchecked(ADMIN), cosmos_msg)
+                .unwrap_err();
+
+            assert!(matches!(
+                err.downcast_ref::<ContractError>(),
+                Some(ContractError::Unauthorized {})
+            ));
+
+            deployment.assert_treasury_balanced();
+        }
+
+        #[test]
+        fn owner_withdraws_profits() {
+            let (mut deployment, cw_borrower_contract) =
+                proper_instantiate(ProperInstantiateProps {
+                    initial_vault_coins: coin(200_000, "uluna"),
+                    initial_user_coins: coin(50, "uluna"),
+                });
+
+            let withdraw_msg = ExecuteMsg::Withdraw {
+                amount: coin(20, "uluna"),
+                recipient: "recipient".to_string(),
+            };
+
+            let err = deployment
+                .app
+                .execute(
+                    Addr::unchecked(USER),
+                    cw_borrower_contract
+                        .call(withdraw_msg.clone(), None)
+                        .unwrap(),
+                )
+                .unwrap_err();
+
+            assert!(matches!(
+                err.downcast_ref::<ContractError>(),
+                Some(ContractError::Unauthorized {})
+            ));
+
+            deployment
+                .app
+                .execute(
+                    Addr::unchecked(ADMIN),
+                    cw_borrower_contract.call(withdraw_msg, None).unwrap(),
+                )
+                .unwrap();
+
+            let borrower_balance = deployment
+                .app
+                .wrap()
+                .query_balance(cw_borrower_contract.addr(), "uluna")
+                .unwrap();
+            let recipient_balance = deployment
+                .app
+                .wrap()
+                .query_balance("recipient", "uluna")
+                .unwrap();
+
+            assert_eq!(borrower_balance, coin(30, "uluna"));
+            assert_eq!(recipient_balance, coin(20, "uluna"));
+
+            deployment.assert_treasury_balanced();
+        }
+    }
+
+    mod profit_guard {
+        use super::*;
+        use crate::msg::ExecuteMsg;
+        use crate::ContractError;
+        use cosmwasm_std::Uint128;
+        use cw_flash_loan_gateway::ContractError as GatewayError;
+
+        #[test]
+        fn unprofitable_loan_is_reverted() {
+            let (mut deployment, cw_borrower_contract) =
+                proper_instantiate(ProperInstantiateProps {
+                    initial_vault_coins: coin(200_000, "uluna"),
+                    initial_user_coins: coin(50, "uluna"),
+                });
+
+            // Nothing is traded in between, so the loan fees are a pure loss
+            let cosmos_msg = cw_borrower_contract
+                .call(
+                    ExecuteMsg::OpenFlashLoan {
+                        asset: coin(1_000, "uluna"),
+                        min_profit: Some(Uint128::zero()),
+                        msgs: vec![],
+                        swap_route: vec![],
+                    },
+                    None,
+                )
+                .unwrap();
+
+            let err = deployment
+                .app
+                .execute(Addr::unchecked(ADMIN), cosmos_msg)
+                .unwrap_err();
+
+            // The gateway reports the failing hook along with the borrower's own error
+            let expected_reason = ContractError::ProfitBelowMinimum {
+                min_profit: Uint128::zero(),
+                initial_balance: Uint128::new(50),
+                final_balance: Uint128::new(20),
+            }
+            .to_string();
+
+            assert!(matches!(
+                err.downcast_ref::<GatewayError>(),
+                Some(GatewayError::BorrowerHookFailed { loan_id: 1, reason })
+                    if reason.contains(&expected_reason)
+            ));
+
+            let borrower_balance = deployment
+                .app
+                .wrap()
+                .query_balance(cw_borrower_contract.addr(), "uluna")
+                .unwrap();
+            let vault_balance = deployment
+                .app
+                .wrap()
+                .query_balance(deployment.vault.addr(), "uluna")
+                .unwrap();
+
+            assert_eq!(borrower_balance, coin(50, "uluna"));
+            assert_eq!(vault_balance, coin(200_000, "uluna"));
+
+            deployment.assert_treasury_balanced();
+        }
+
+        #[test]
+        fn only_borrower_can_close_flash_loan() {
+            let (mut deployment, cw_borrower_contract) =
+                proper_instantiate(ProperInstantiateProps {
+                    initial_vault_coins: coin(200_000, "uluna"),
+                    initial_user_coins: coin(50, "uluna"),
+                });
+
+            let cosmos_msg = cw_borrower_contract
+                .call(ExecuteMsg::CloseFlashLoan {}, None)
+                .unwrap();
+
+            let err = deployment
+                .app
+                .execute(Addr::unchecked(ADMIN), cosmos_msg)
+                .unwrap_err();
+
+            assert!(matches!(
+                err.downcast_ref::<ContractError>(),
+                Some(ContractError::Unauthorized {})
+            ));
+
+            deployment.assert_treasury_balanced();
+        }
+    }
+
+    mod arbitrage {
+        use super::*;
+        use crate::msg::{ExecuteMsg, FlashLoanProfit};
+        use cosmwasm_std::{from_json, to_json_binary, CosmosMsg, Uint128, WasmMsg};
+        use cw_flash_loan_gateway::ContractError as GatewayError;
+        use cw_flash_loan_mock_amm::msg::ExecuteMsg as PoolExecuteMsg;
+
+        fn swap_msg(pool: &Addr, offer: Coin) -> CosmosMsg {
+            WasmMsg::Execute {
+                contract_addr: pool.to_string(),
+                msg: to_json_binary(&PoolExecuteMsg::Swap {
+                    offer_asset: native(&offer),
+                    ask_asset_info: None,
+                    belief_price: None,
+                    max_spread: None,
+                    to: None,
+                })
+                .unwrap(),
+                funds: vec![offer],
+            }
+            .into()
+        }
+
+        /// Sells the borrowed uluna where it's expensive and buys it back where it's cheap.
+        fn arbitrage_msgs(
+            app: &TestApp,
+            expensive_pool: &Addr,
+            cheap_pool: &Addr,
+            borrowed: &Coin,
+        ) -> (Vec<CosmosMsg>, Uint128) {
+            let uusd_amount = simulate_swap(app, expensive_pool, borrowed);
+            let uusd = coin(uusd_amount.u128(), "uusd");
+            let uluna_amount = simulate_swap(app, cheap_pool, &uusd);
+
+            (
+                vec![
+                    swap_msg(expensive_pool, borrowed.clone()),
+                    swap_msg(cheap_pool, uusd),
+                ],
+                uluna_amount,
+            )
+        }
+
+        #[test]
+        fn borrower_arbitrages_mispriced_pools() {
+            let borrowed = coin(1_000, "uluna");
+
+            let (mut deployment, cw_borrower_contract) =
+                proper_instantiate(ProperInstantiateProps {
+                    initial_vault_coins: coin(200_000, "uluna"),
+                    initial_user_coins: coin(50, "uluna"),
+                });
+
+            // 1 uluna = 2 uusd
+            let cheap_pool = instantiate_pool(
+                &mut deployment.app,
+                vec![coin(100_000, "uluna"), coin(200_000, "uusd")],
+            );
+            // 1 uluna = 3 uusd
+            let expensive_pool = instantiate_pool(
+                &mut deployment.app,
+                vec![coin(100_000, "uluna"), coin(300_000, "uusd")],
+            );
+
+            let (msgs, uluna_bought_back) =
+                arbitrage_msgs(&deployment.app, &expensive_pool, &cheap_pool, &borrowed);
+
+            let response = deployment
+                .app
+                .execute_contract(
+                    Addr::unchecked(ADMIN),
+                    cw_borrower_contract.addr(),
+                    &ExecuteMsg::OpenFlashLoan {
+                        asset: borrowed.clone(),
+                        min_profit: Some(Uint128::new(100)),
+                        msgs,
+                        swap_route: vec![],
+                    },
+                    &[],
+                )
+                .unwrap();
+
+            let borrower_balance = deployment
+                .app
+                .wrap()
+                .query_balance(cw_borrower_contract.addr(), "uluna")
+                .unwrap();
+            let vault_balance = deployment
+                .app
+                .wrap()
+                .query_balance(deployment.vault.addr(), "uluna")
+                .unwrap();
+            let gateway_balance = deployment
+                .app
                 .wrap()
-                .query_all_balances(cw_borrower_contract.addr())
+                .query_balance(deployment.gateway.addr(), "uluna")
                 .unwrap();
 
-            println!("[{}]: Borrower = {:?}", label, &borrower_coins);
+            // 1_000 borrowed, 20 of vault fees and 10 of gateway fees
+            let expected_profit = uluna_bought_back.u128() - 1_030;
+            assert!(expected_profit >= 100);
+            assert_eq!(borrower_balance, coin(50 + expected_profit, "uluna"));
+            assert_eq!(vault_balance, coin(200_020, "uluna"));
+            assert_eq!(gateway_balance, coin(10, "uluna"));
 
-            let gateway_coins = app
+            // The hook reported the profit to the gateway, which handed it back
+            let profit: FlashLoanProfit = from_json(response.data.unwrap()).unwrap();
+            assert_eq!(
+                profit,
+                FlashLoanProfit {
+                    initial_balance: coin(50, "uluna"),
+                    final_balance: borrower_balance,
+                    profit: coin(expected_profit, "uluna"),
+                }
+            );
+
+            deployment.assert_treasury_balanced();
+        }
+
+        #[test]
+        fn arbitrage_without_price_gap_is_reverted() {
+            let borrowed = coin(1_000, "uluna");
+
+            let (mut deployment, cw_borrower_contract) =
+                proper_instantiate(ProperInstantiateProps {
+                    initial_vault_coins: coin(200_000, "uluna"),
+                    initial_user_coins: coin(100, "uluna"),
+                });
+
+            let first_pool = instantiate_pool(
+                &mut deployment.app,
+                vec![coin(100_000, "uluna"), coin(200_000, "uusd")],
+            );
+            let second_pool = instantiate_pool(
+                &mut deployment.app,
+                vec![coin(100_000, "uluna"), coin(200_000, "uusd")],
+            );
+
+            // Round-tripping only pays the pools' commissions
+            let (msgs, _) = arbitrage_msgs(&deployment.app, &second_pool, &first_pool, &borrowed);
+
+            let cosmos_msg = cw_borrower_contract
+                .call(
+                    ExecuteMsg::OpenFlashLoan {
+                        asset: borrowed,
+                        min_profit: Some(Uint128::zero()),
+                        msgs,
+                        swap_route: vec![],
+                    },
+                    None,
+                )
+                .unwrap();
+
+            let err = deployment
+                .app
+                .execute(Addr::unchecked(ADMIN), cosmos_msg)
+                .unwrap_err();
+
+            assert!(matches!(
+                err.downcast_ref::<GatewayError>(),
+                Some(GatewayError::BorrowerHookFailed { reason, .. })
+                    if reason.contains("Profit below minimum")
+            ));
+
+            let borrower_balance = deployment
+                .app
+                .wrap()
+                .query_balance(cw_borrower_contract.addr(), "uluna")
+                .unwrap();
+            let vault_balance = deployment
+                .app
                 .wrap()
-                .query_all_balances(cw_gateway_contract.addr())
+                .query_balance(deployment.vault.addr(), "uluna")
+                .unwrap();
+
+            assert_eq!(borrower_balance, coin(100, "uluna"));
+            assert_eq!(vault_balance, coin(200_000, "uluna"));
+
+            deployment.assert_treasury_balanced();
+        }
+    }
+
+    mod failures {
+        use super::*;
+        use crate::msg::ExecuteMsg;
+        use cw_flash_loan_gateway::ContractError as GatewayError;
+        use cw_flash_loan_testing::test_borrower::{self, Hook, Repay};
+
+        fn open_flash_loan(
+            app: &mut TestApp,
+            cw_borrower_contract: &CwBorrowerContract,
+            asset: Coin,
+        ) -> anyhow::Result<()> {
+            let cosmos_msg = cw_borrower_contract
+                .call(
+                    ExecuteMsg::OpenFlashLoan {
+                        asset,
+                        min_profit: None,
+                        msgs: vec![],
+                        swap_route: vec![],
+                    },
+                    None,
+                )
+                .unwrap();
+
+            app.execute(Addr::unchecked(ADMIN), cosmos_msg)?;
+
+            Ok(())
+        }
+
+        #[test]
+        fn vault_refusal_is_reported() {
+            let (mut deployment, cw_borrower_contract) =
+                proper_instantiate(ProperInstantiateProps {
+                    initial_vault_coins: coin(200_000, "uluna"),
+                    initial_user_coins: coin(50, "uluna"),
+                });
+
+            let err = open_flash_loan(
+                &mut deployment.app,
+                &cw_borrower_contract,
+                coin(200_001, "uluna"),
+            )
+            .unwrap_err();
+
+            assert!(matches!(
+                err.downcast_ref::<GatewayError>(),
+                Some(GatewayError::VaultRefused { loan_id: 1, .. })
+            ));
+
+            deployment.assert_treasury_balanced();
+        }
+
+        #[test]
+        fn repayment_shortfall_is_reported() {
+            let (mut deployment, cw_borrower_contract) =
+                proper_instantiate(ProperInstantiateProps {
+                    initial_vault_coins: coin(200_000, "uluna"),
+                    initial_user_coins: coin(50, "uluna"),
+                });
+
+            // A first loan goes through, the next one gets the following id
+            open_flash_loan(
+                &mut deployment.app,
+                &cw_borrower_contract,
+                coin(1_000, "uluna"),
+            )
+            .unwrap();
+
+            // This borrower pays back what it borrowed but never the fees
+            let lazy_borrower_id = deployment.app.store_code(test_borrower::contract());
+            let lazy_borrower = deployment
+                .app
+                .instantiate_contract(
+                    lazy_borrower_id,
+                    Addr::unchecked(ADMIN),
+                    &Empty {},
+                    &[],
+                    "lazy borrower",
+                    None,
+                )
+                .unwrap();
+
+            let err = deployment
+                .app
+                .execute_contract(
+                    Addr::unchecked(ADMIN),
+                    lazy_borrower,
+                    &test_borrower::ExecuteMsg::Borrow {
+                        gateway: deployment.gateway.addr().to_string(),
+                        asset: coin(1_000, "uluna"),
+                        referrer: None,
+                        hook: Hook {
+                            repay: Repay::Principal,
+                            ..Hook::default()
+                        },
+                    },
+                    &[],
+                )
+                .unwrap_err();
+
+            match err.downcast_ref::<GatewayError>() {
+                Some(GatewayError::RepaymentShortfall {
+                    loan_id,
+                    required,
+                    available,
+                }) => {
+                    assert_eq!(*loan_id, 2);
+                    assert_eq!(required, &coin(1_030, "uluna"));
+                    // Only the principal was paid back, the gateway fee kept
+                    // from the first loan doesn't make up for the missing fees
+                    assert_eq!(available, &coin(1_000, "uluna"));
+                }
+                other => panic!("unexpected error: {:?}", other),
+            }
+
+            deployment.assert_treasury_balanced();
+        }
+    }
+
+    mod simulation {
+        use super::*;
+        use crate::msg::ExecuteMsg;
+        use cw_flash_loan_gateway::msg::{LoanTerms, Rejection, SimulateFlashLoanResponse};
+        use cw_flash_loan_gateway::ContractError as GatewayError;
+
+        #[test]
+        fn simulation_matches_execution() {
+            let (mut deployment, cw_borrower_contract) =
+                proper_instantiate(ProperInstantiateProps {
+                    initial_vault_coins: coin(200_000, "uluna"),
+                    initial_user_coins: coin(50, "uluna"),
+                });
+
+            let simulation = deployment
+                .gateway
+                .simulate_flash_loan(
+                    &deployment.app.wrap(),
+                    vec![coin(1_000, "uluna")],
+                    &cw_borrower_contract.addr(),
+                    None,
+                )
                 .unwrap();
 
-            println!("[{}]: Gateway = {:?}", label, &gateway_coins);
+            assert_eq!(
+                simulation,
+                SimulateFlashLoanResponse {
+                    loans: vec![LoanTerms {
+                        asset: coin(1_000, "uluna"),
+                        vault: deployment.vault.addr(),
+                        gateway_fee: coin(10, "uluna"),
+                        vault_fee: coin(20, "uluna"),
+                        total_repayment: coin(1_030, "uluna"),
+                    }],
+                    total_repayment: vec![coin(1_030, "uluna")],
+                    rejection: None,
+                }
+            );
+
+            deployment
+                .app
+                .execute(
+                    Addr::unchecked(ADMIN),
+                    cw_borrower_contract
+                        .call(
+                            ExecuteMsg::OpenFlashLoan {
+                                asset: coin(1_000, "uluna"),
+                                min_profit: None,
+                                msgs: vec![],
+                                swap_route: vec![],
+                            },
+                            None,
+                        )
+                        .unwrap(),
+                )
+                .unwrap();
 
-            let vault_coins = app
+            // The borrower paid exactly the simulated fees
+            let borrower_balance = deployment
+                .app
+                .wrap()
+                .query_balance(cw_borrower_contract.addr(), "uluna")
+                .unwrap();
+            let vault_balance = deployment
+                .app
+                .wrap()
+                .query_balance(deployment.vault.addr(), "uluna")
+                .unwrap();
+            let gateway_balance = deployment
+                .app
                 .wrap()
-                .query_all_balances(cw_vault_contract.addr())
+                .query_balance(deployment.gateway.addr(), "uluna")
                 .unwrap();
 
-            println!("[{}]: Vault = {:?}", label, &vault_coins);
+            assert_eq!(borrower_balance, coin(20, "uluna"));
+            assert_eq!(vault_balance, coin(200_020, "uluna"));
+            assert_eq!(gateway_balance, coin(10, "uluna"));
+
+            deployment.assert_treasury_balanced();
+        }
+
+        #[test]
+        fn simulation_reports_rejection() {
+            let (mut deployment, cw_borrower_contract) =
+                proper_instantiate(ProperInstantiateProps {
+                    initial_vault_coins: coin(200_000, "uluna"),
+                    initial_user_coins: coin(50, "uluna"),
+                });
+
+            let simulation = deployment
+                .gateway
+                .simulate_flash_loan(
+                    &deployment.app.wrap(),
+                    vec![coin(200_001, "uluna")],
+                    &cw_borrower_contract.addr(),
+                    None,
+                )
+                .unwrap();
+
+            assert!(simulation.loans.is_empty());
+
+            let err = deployment
+                .app
+                .execute(
+                    Addr::unchecked(ADMIN),
+                    cw_borrower_contract
+                        .call(
+                            ExecuteMsg::OpenFlashLoan {
+                                asset: coin(200_001, "uluna"),
+                                min_profit: None,
+                                msgs: vec![],
+                                swap_route: vec![],
+                            },
+                            None,
+                        )
+                        .unwrap(),
+                )
+                .unwrap_err();
+
+            assert_eq!(
+                simulation.rejection,
+                err.downcast::<GatewayError>().ok().map(Rejection::from)
+            );
+
+            // Loans cover a single asset
+            let simulation = deployment
+                .gateway
+                .simulate_flash_loan(
+                    &deployment.app.wrap(),
+                    vec![coin(1_000, "uluna"), coin(1_000, "uusd")],
+                    &cw_borrower_contract.addr(),
+                    None,
+                )
+                .unwrap();
+
+            assert_eq!(simulation.rejection, Some(Rejection::SingleAssetOnly {}));
+
+            deployment.assert_treasury_balanced();
+        }
+    }
+
+    mod fees {
+        use super::*;
+        use crate::msg::ExecuteMsg;
+        use cw_flash_loan_vault::msg::{ExecuteMsg as VaultExecuteMsg, FeeQuoteResponse};
+        use cw_flash_loan_vault::state::FeeCurve;
+
+        #[test]
+        fn vault_fee_follows_utilization() {
+            let (mut deployment, cw_borrower_contract) =
+                proper_instantiate(ProperInstantiateProps {
+                    initial_vault_coins: coin(200_000, "uluna"),
+                    initial_user_coins: coin(2_000, "uluna"),
+                });
+
+            deployment
+                .app
+                .execute_contract(
+                    Addr::unchecked(ADMIN),
+                    deployment.vault.addr(),
+                    &VaultExecuteMsg::UpdateFeeCurve {
+                        fee_curve: FeeCurve {
+                            base_rate: Decimal::percent(1),
+                            kink: Decimal::percent(50),
+                            slope_below_kink: Decimal::percent(4),
+                            slope_above_kink: Decimal::percent(50),
+                        },
+                    },
+                    &[],
+                )
+                .unwrap();
+
+            // A quarter of the liquidity costs 1% + 4% * 25% / 50%
+            assert_eq!(
+                deployment
+                    .vault
+                    .fee_quote(&deployment.app.wrap(), coin(50_000, "uluna"))
+                    .unwrap(),
+                FeeQuoteResponse {
+                    fee: coin(1_500, "uluna"),
+                    rate: Decimal::percent(3),
+                    utilization: Decimal::percent(25),
+                }
+            );
+
+            // Past the kink the rate climbs steeply: 1% + 4% + 50% * 25% / 50%
+            let simulation = deployment
+                .gateway
+                .simulate_flash_loan(
+                    &deployment.app.wrap(),
+                    vec![coin(150_000, "uluna")],
+                    &cw_borrower_contract.addr(),
+                    None,
+                )
+                .unwrap();
+            assert_eq!(simulation.loans[0].vault_fee, coin(45_000, "uluna"));
+
+            deployment
+                .app
+                .execute(
+                    Addr::unchecked(ADMIN),
+                    cw_borrower_contract
+                        .call(
+                            ExecuteMsg::OpenFlashLoan {
+                                asset: coin(50_000, "uluna"),
+                                min_profit: None,
+                                msgs: vec![],
+                                swap_route: vec![],
+                            },
+                            None,
+                        )
+                        .unwrap(),
+                )
+                .unwrap();
+
+            // The quoted fee was charged even though the loan drained the vault meanwhile
+            let vault_balance = deployment
+                .app
+                .wrap()
+                .query_balance(deployment.vault.addr(), "uluna")
+                .unwrap();
+            let gateway_balance = deployment
+                .app
+                .wrap()
+                .query_balance(deployment.gateway.addr(), "uluna")
+                .unwrap();
+
+            assert_eq!(vault_balance, coin(201_500, "uluna"));
+            assert_eq!(gateway_balance, coin(500, "uluna"));
+
+            deployment.assert_treasury_balanced();
+        }
+    }
+
+    mod tiers {
+        use super::*;
+        use crate::msg::ExecuteMsg;
+        use cw_flash_loan_gateway::msg::{BorrowerTierResponse, ExecuteMsg as GatewayExecuteMsg};
+        use cw_flash_loan_gateway::state::{FeeConfig, FeeTier};
+
+        fn fee_config() -> FeeConfig {
+            FeeConfig {
+                rate: Decimal::percent(1),
+                tiers: vec![
+                    FeeTier {
+                        denom: "uluna".to_string(),
+                        min_volume: Uint128::new(2_000),
+                        discount: Decimal::percent(50),
+                    },
+                    FeeTier {
+                        denom: "uluna".to_string(),
+                        min_volume: Uint128::new(5_000),
+                        discount: Decimal::one(),
+                    },
+                ],
+                volume_window: 3_600,
+                referral_share: Decimal::zero(),
+            }
+        }
+
+        fn open_flash_loan(app: &mut TestApp, borrower: &CwBorrowerContract, asset: Coin) {
+            app.execute(
+                Addr::unchecked(ADMIN),
+                borrower
+                    .call(
+                        ExecuteMsg::OpenFlashLoan {
+                            asset,
+                            min_profit: None,
+                            msgs: vec![],
+                            swap_route: vec![],
+                        },
+                        None,
+                    )
+                    .unwrap(),
+            )
+            .unwrap();
+        }
+
+        #[test]
+        fn only_owner_updates_fees() {
+            let (mut deployment, _) = proper_instantiate(ProperInstantiateProps {
+                initial_vault_coins: coin(100_000, "uluna"),
+                initial_user_coins: coin(1_000, "uluna"),
+            });
+
+            let err = deployment
+                .app
+                .execute_contract(
+                    Addr::unchecked(USER),
+                    deployment.gateway.addr(),
+                    &GatewayExecuteMsg::UpdateFees { fees: fee_config() },
+                    &[],
+                )
+                .unwrap_err();
+
+            assert!(matches!(
+                err.downcast_ref::<cw_flash_loan_gateway::ContractError>(),
+                Some(cw_flash_loan_gateway::ContractError::Unauthorized {})
+            ));
+
+            deployment.assert_treasury_balanced();
+        }
+
+        #[test]
+        fn volume_discounts_gateway_fee() {
+            let (mut deployment, cw_borrower_contract) =
+                proper_instantiate(ProperInstantiateProps {
+                    initial_vault_coins: coin(100_000, "uluna"),
+                    initial_user_coins: coin(1_000, "uluna"),
+                });
+
+            deployment
+                .app
+                .execute_contract(
+                    Addr::unchecked(ADMIN),
+                    deployment.gateway.addr(),
+                    &GatewayExecuteMsg::UpdateFees { fees: fee_config() },
+                    &[],
+                )
+                .unwrap();
+
+            let borrower = cw_borrower_contract.addr();
+            open_flash_loan(
+                &mut deployment.app,
+                &cw_borrower_contract,
+                coin(1_000, "uluna"),
+            );
+            open_flash_loan(
+                &mut deployment.app,
+                &cw_borrower_contract,
+                coin(1_000, "uluna"),
+            );
+
+            let tier = deployment
+                .gateway
+                .borrower_tier(&deployment.app.wrap(), &borrower, "uluna")
+                .unwrap();
+            assert_eq!(tier.volume, Uint128::new(2_000));
+            assert_eq!(tier.tier, Some(fee_config().tiers[0].clone()));
+            assert_eq!(tier.fee_rate, Decimal::permille(5));
+
+            // Volume in one denom doesn't discount loans of another
+            let tier = deployment
+                .gateway
+                .borrower_tier(&deployment.app.wrap(), &borrower, "uusd")
+                .unwrap();
+            assert_eq!(tier.volume, Uint128::zero());
+            assert_eq!(tier.fee_rate, Decimal::percent(1));
+
+            // Without a denom, every denom with tiers or volume is listed
+            let tiers = deployment
+                .gateway
+                .borrower_tiers(&deployment.app.wrap(), &borrower)
+                .unwrap();
+            assert_eq!(
+                tiers,
+                vec![BorrowerTierResponse {
+                    denom: "uluna".to_string(),
+                    volume: Uint128::new(2_000),
+                    tier: Some(fee_config().tiers[0].clone()),
+                    fee_rate: Decimal::permille(5),
+                }]
+            );
+
+            let simulation = deployment
+                .gateway
+                .simulate_flash_loan(
+                    &deployment.app.wrap(),
+                    vec![coin(1_000, "uluna")],
+                    &borrower,
+                    None,
+                )
+                .unwrap();
+            assert_eq!(simulation.loans[0].gateway_fee, coin(5, "uluna"));
+
+            // Volume falls out of the window after an hour
+            deployment
+                .app
+                .update_block(|block| block.time = block.time.plus_seconds(3_601));
+
+            let tier = deployment
+                .gateway
+                .borrower_tier(&deployment.app.wrap(), &borrower, "uluna")
+                .unwrap();
+            assert_eq!(tier.volume, Uint128::zero());
+            assert_eq!(tier.tier, None);
+            assert_eq!(tier.fee_rate, Decimal::percent(1));
+
+            deployment.assert_treasury_balanced();
+        }
+    }
+
+    mod min_loan_sizes {
+        use super::*;
+        use crate::msg::ExecuteMsg;
+        use cw_flash_loan_gateway::msg::{ExecuteMsg as GatewayExecuteMsg, Rejection};
+        use cw_flash_loan_gateway::ContractError as GatewayError;
+
+        fn open_flash_loan(
+            app: &mut TestApp,
+            borrower: &CwBorrowerContract,
+            asset: Coin,
+        ) -> anyhow::Result<()> {
+            let cosmos_msg = borrower
+                .call(
+                    ExecuteMsg::OpenFlashLoan {
+                        asset,
+                        min_profit: None,
+                        msgs: vec![],
+                        swap_route: vec![],
+                    },
+                    None,
+                )
+                .unwrap();
+
+            app.execute(Addr::unchecked(ADMIN), cosmos_msg)?;
+
+            Ok(())
+        }
+
+        #[test]
+        fn only_owner_updates_min_loan_sizes() {
+            let (mut deployment, _) = proper_instantiate(ProperInstantiateProps {
+                initial_vault_coins: coin(100_000, "uluna"),
+                initial_user_coins: coin(1_000, "uluna"),
+            });
+
+            let err = deployment
+                .app
+                .execute_contract(
+                    Addr::unchecked(USER),
+                    deployment.gateway.addr(),
+                    &GatewayExecuteMsg::UpdateMinLoanSizes {
+                        min_loan_sizes: vec![coin(1_000, "uluna")],
+                    },
+                    &[],
+                )
+                .unwrap_err();
+
+            assert!(matches!(
+                err.downcast_ref::<GatewayError>(),
+                Some(GatewayError::Unauthorized {})
+            ));
+        }
+
+        #[test]
+        fn loans_below_the_minimum_are_rejected() {
+            let (mut deployment, cw_borrower_contract) =
+                proper_instantiate(ProperInstantiateProps {
+                    initial_vault_coins: coin(100_000, "uluna"),
+                    initial_user_coins: coin(1_000, "uluna"),
+                });
+
+            // Without a minimum, only empty loans are too small
+            let err = open_flash_loan(&mut deployment.app, &cw_borrower_contract, coin(0, "uluna"))
+                .unwrap_err();
+            match err.downcast_ref::<GatewayError>() {
+                Some(GatewayError::LoanTooSmall { min }) => assert_eq!(min, &coin(1, "uluna")),
+                other => panic!("unexpected error: {:?}", other),
+            }
+
+            deployment
+                .app
+                .execute_contract(
+                    Addr::unchecked(ADMIN),
+                    deployment.gateway.addr(),
+                    &GatewayExecuteMsg::UpdateMinLoanSizes {
+                        min_loan_sizes: vec![coin(1_000, "uluna")],
+                    },
+                    &[],
+                )
+                .unwrap();
+            assert_eq!(
+                deployment
+                    .gateway
+                    .min_loan_size(&deployment.app.wrap(), "uluna")
+                    .unwrap(),
+                coin(1_000, "uluna")
+            );
+
+            let err = open_flash_loan(
+                &mut deployment.app,
+                &cw_borrower_contract,
+                coin(999, "uluna"),
+            )
+            .unwrap_err();
+            match err.downcast_ref::<GatewayError>() {
+                Some(GatewayError::LoanTooSmall { min }) => assert_eq!(min, &coin(1_000, "uluna")),
+                other => panic!("unexpected error: {:?}", other),
+            }
+
+            let simulation = deployment
+                .gateway
+                .simulate_flash_loan(
+                    &deployment.app.wrap(),
+                    vec![coin(999, "uluna")],
+                    &cw_borrower_contract.addr(),
+                    None,
+                )
+                .unwrap();
+            assert_eq!(
+                simulation.rejection,
+                Some(Rejection::LoanTooSmall {
+                    min: coin(1_000, "uluna")
+                })
+            );
+
+            open_flash_loan(
+                &mut deployment.app,
+                &cw_borrower_contract,
+                coin(1_000, "uluna"),
+            )
+            .unwrap();
+
+            deployment.assert_treasury_balanced();
+        }
+    }
+
+    mod stale_loans {
+        use super::*;
+        use crate::msg::ExecuteMsg;
+        use cosmwasm_std::Binary;
+        use cw_flash_loan_gateway::msg::{ExecuteMsg as GatewayExecuteMsg, Rejection};
+        use cw_flash_loan_gateway::state::{FlashLoanState, FLASH_LOAN_STATE};
+        use cw_flash_loan_gateway::ContractError as GatewayError;
+        use cw_flash_loan_testing::assert_event;
+
+        /// Leaves the state of a loan behind in the gateway, as if it never got cleaned up
+        fn leave_loan_behind(app: &mut TestApp, gateway: &CwGatewayContract, borrower: &Addr) {
+            let state = FlashLoanState {
+                loan_id: 42,
+                borrower_contract_addr: borrower.clone(),
+                borrower_requested_asset: coin(1_000, "uluna"),
+                vault_fee: coin(20, "uluna"),
+                gateway_fee: coin(10, "uluna"),
+                referrer: None,
+                on_funded_msg: Binary::default(),
+                opened_at: app.block_info().height,
+            };
+
+            let mut storage = app.contract_storage_mut(&gateway.addr());
+            FLASH_LOAN_STATE.save(storage.as_mut(), &state).unwrap();
+        }
+
+        fn open_flash_loan(
+            app: &mut TestApp,
+            borrower: &CwBorrowerContract,
+            asset: Coin,
+        ) -> anyhow::Result<()> {
+            let cosmos_msg = borrower
+                .call(
+                    ExecuteMsg::OpenFlashLoan {
+                        asset,
+                        min_profit: None,
+                        msgs: vec![],
+                        swap_route: vec![],
+                    },
+                    None,
+                )
+                .unwrap();
+
+            app.execute(Addr::unchecked(ADMIN), cosmos_msg)?;
+
+            Ok(())
+        }
+
+        #[test]
+        fn stale_loan_does_not_block_borrowers() {
+            let (mut deployment, cw_borrower_contract) =
+                proper_instantiate(ProperInstantiateProps {
+                    initial_vault_coins: coin(100_000, "uluna"),
+                    initial_user_coins: coin(1_000, "uluna"),
+                });
+            let borrower = cw_borrower_contract.addr();
+
+            leave_loan_behind(&mut deployment.app, &deployment.gateway, &borrower);
+
+            // Within its block the loan is still in progress
+            let err = open_flash_loan(
+                &mut deployment.app,
+                &cw_borrower_contract,
+                coin(1_000, "uluna"),
+            )
+            .unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<GatewayError>(),
+                Some(GatewayError::LoanInProgress {})
+            ));
+            let simulate = |deployment: &Deployment<MockPoolManager>| {
+                deployment
+                    .gateway
+                    .simulate_flash_loan(
+                        &deployment.app.wrap(),
+                        vec![coin(1_000, "uluna")],
+                        &borrower,
+                        None,
+                    )
+                    .unwrap()
+                    .rejection
+            };
+            assert_eq!(simulate(&deployment), Some(Rejection::LoanInProgress {}));
+
+            deployment.app.update_block(|block| block.height += 1);
+            assert_eq!(simulate(&deployment), None);
+
+            assert!(deployment
+                .gateway
+                .get_debt_remaining(&deployment.app.wrap(), borrower)
+                .is_err());
+            open_flash_loan(
+                &mut deployment.app,
+                &cw_borrower_contract,
+                coin(1_000, "uluna"),
+            )
+            .unwrap();
+
+            deployment.assert_treasury_balanced();
+        }
+
+        #[test]
+        fn owner_clears_stale_loans() {
+            let (mut deployment, cw_borrower_contract) =
+                proper_instantiate(ProperInstantiateProps {
+                    initial_vault_coins: coin(100_000, "uluna"),
+                    initial_user_coins: coin(1_000, "uluna"),
+                });
+
+            leave_loan_behind(
+                &mut deployment.app,
+                &deployment.gateway,
+                &cw_borrower_contract.addr(),
+            );
+
+            // Not stale yet
+            let response = deployment
+                .app
+                .execute_contract(
+                    Addr::unchecked(ADMIN),
+                    deployment.gateway.addr(),
+                    &GatewayExecuteMsg::ClearStaleLoans {},
+                    &[],
+                )
+                .unwrap();
+            assert!(!response.events.iter().any(|event| event
+                .attributes
+                .iter()
+                .any(|attr| attr.key == "cleared_loan_id")));
+
+            deployment.app.update_block(|block| block.height += 1);
+
+            let err = deployment
+                .app
+                .execute_contract(
+                    Addr::unchecked(USER),
+                    deployment.gateway.addr(),
+                    &GatewayExecuteMsg::ClearStaleLoans {},
+                    &[],
+                )
+                .unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<GatewayError>(),
+                Some(GatewayError::Unauthorized {})
+            ));
+
+            let response = deployment
+                .app
+                .execute_contract(
+                    Addr::unchecked(ADMIN),
+                    deployment.gateway.addr(),
+                    &GatewayExecuteMsg::ClearStaleLoans {},
+                    &[],
+                )
+                .unwrap();
+            assert_event(&response, "wasm", &[("cleared_loan_id", "42")]);
+
+            let storage = deployment.app.contract_storage(&deployment.gateway.addr());
+            assert_eq!(FLASH_LOAN_STATE.may_load(storage.as_ref()).unwrap(), None);
+        }
+    }
+
+    mod governance {
+        use super::*;
+        use crate::msg::ExecuteMsg;
+        use cw_flash_loan_gateway::msg::{
+            ExecuteMsg as GatewayExecuteMsg, LiquidityResponse, Rejection,
+            SudoMsg as GatewaySudoMsg,
+        };
+        use cw_flash_loan_gateway::state::FeeConfig;
+        use cw_flash_loan_gateway::ContractError as GatewayError;
+        use cw_flash_loan_testing::contracts::vault_contract;
+
+        #[test]
+        fn governance_configures_the_gateway() {
+            let (mut deployment, cw_borrower_contract) =
+                proper_instantiate(ProperInstantiateProps {
+                    initial_vault_coins: coin(100_000, "uluna"),
+                    initial_user_coins: coin(1_000, "uluna"),
+                });
+            let gateway = deployment.gateway.addr();
+
+            deployment
+                .app
+                .wasm_sudo(
+                    gateway.clone(),
+                    &GatewaySudoMsg::UpdateFees {
+                        fees: FeeConfig {
+                            rate: Decimal::percent(2),
+                            ..FeeConfig::default()
+                        },
+                    },
+                )
+                .unwrap();
+            deployment
+                .app
+                .wasm_sudo(
+                    gateway.clone(),
+                    &GatewaySudoMsg::UpdateMinLoanSizes {
+                        min_loan_sizes: vec![coin(500, "uluna")],
+                    },
+                )
+                .unwrap();
+            assert_eq!(
+                deployment
+                    .gateway
+                    .min_loan_size(&deployment.app.wrap(), "uluna")
+                    .unwrap(),
+                coin(500, "uluna")
+            );
+
+            let cosmos_msg = cw_borrower_contract
+                .call(
+                    ExecuteMsg::OpenFlashLoan {
+                        asset: coin(1_000, "uluna"),
+                        min_profit: None,
+                        msgs: vec![],
+                        swap_route: vec![],
+                    },
+                    None,
+                )
+                .unwrap();
+            deployment
+                .app
+                .execute(Addr::unchecked(ADMIN), cosmos_msg)
+                .unwrap();
+
+            let fees = deployment
+                .gateway
+                .protocol_fees(&deployment.app.wrap())
+                .unwrap();
+            assert_eq!(fees.accrued, vec![coin(20, "uluna")]);
+
+            deployment
+                .app
+                .wasm_sudo(
+                    gateway.clone(),
+                    &GatewaySudoMsg::WithdrawFees {
+                        denom: "uluna".to_string(),
+                        amount: Uint128::new(20),
+                        recipient: "treasury".to_string(),
+                    },
+                )
+                .unwrap();
+            deployment
+                .app
+                .wasm_sudo(gateway, &GatewaySudoMsg::ClearStaleLoans {})
+                .unwrap();
+
+            let balance = deployment
+                .app
+                .wrap()
+                .query_balance("treasury", "uluna")
+                .unwrap();
+            assert_eq!(balance, coin(20, "uluna"));
+
+            deployment.assert_treasury_balanced();
+        }
+
+        fn open_flash_loan(
+            deployment: &mut Deployment<MockPoolManager>,
+            borrower: &CwBorrowerContract,
+        ) -> anyhow::Result<()> {
+            let cosmos_msg = borrower
+                .call(
+                    ExecuteMsg::OpenFlashLoan {
+                        asset: coin(1_000, "uluna"),
+                        min_profit: None,
+                        msgs: vec![],
+                        swap_route: vec![],
+                    },
+                    None,
+                )
+                .unwrap();
+
+            deployment.app.execute(Addr::unchecked(ADMIN), cosmos_msg)?;
+
+            Ok(())
+        }
+
+        #[test]
+        fn governance_pauses_and_restricts_borrowers() {
+            let (mut deployment, cw_borrower_contract) =
+                proper_instantiate(ProperInstantiateProps {
+                    initial_vault_coins: coin(100_000, "uluna"),
+                    initial_user_coins: coin(1_000, "uluna"),
+                });
+            let gateway = deployment.gateway.addr();
+            let rejection = |deployment: &Deployment<MockPoolManager>| {
+                deployment
+                    .gateway
+                    .simulate_flash_loan(
+                        &deployment.app.wrap(),
+                        vec![coin(1_000, "uluna")],
+                        &cw_borrower_contract.addr(),
+                        None,
+                    )
+                    .unwrap()
+                    .rejection
+            };
+
+            deployment
+                .app
+                .wasm_sudo(gateway.clone(), &GatewaySudoMsg::Pause {})
+                .unwrap();
+
+            let err = open_flash_loan(&mut deployment, &cw_borrower_contract).unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<GatewayError>(),
+                Some(GatewayError::Paused {})
+            ));
+            assert_eq!(rejection(&deployment), Some(Rejection::Paused {}));
+
+            // Only the owner unpauses, besides governance
+            let err = deployment
+                .app
+                .execute_contract(
+                    Addr::unchecked(USER),
+                    gateway.clone(),
+                    &GatewayExecuteMsg::Unpause {},
+                    &[],
+                )
+                .unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<GatewayError>(),
+                Some(GatewayError::Unauthorized {})
+            ));
+            deployment
+                .app
+                .execute_contract(
+                    Addr::unchecked(ADMIN),
+                    gateway.clone(),
+                    &GatewayExecuteMsg::Unpause {},
+                    &[],
+                )
+                .unwrap();
+
+            deployment
+                .app
+                .wasm_sudo(
+                    gateway.clone(),
+                    &GatewaySudoMsg::UpdateAllowedBorrowers {
+                        add: vec![],
+                        remove: vec![],
+                        restrict: Some(true),
+                    },
+                )
+                .unwrap();
+
+            let err = open_flash_loan(&mut deployment, &cw_borrower_contract).unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<GatewayError>(),
+                Some(GatewayError::BorrowerNotAllowed { .. })
+            ));
+            assert_eq!(
+                rejection(&deployment),
+                Some(Rejection::BorrowerNotAllowed {})
+            );
+
+            deployment
+                .app
+                .wasm_sudo(
+                    gateway,
+                    &GatewaySudoMsg::UpdateAllowedBorrowers {
+                        add: vec![cw_borrower_contract.addr().to_string()],
+                        remove: vec![],
+                        restrict: None,
+                    },
+                )
+                .unwrap();
+
+            assert_eq!(rejection(&deployment), None);
+            open_flash_loan(&mut deployment, &cw_borrower_contract).unwrap();
+
+            deployment.assert_treasury_balanced();
+        }
+
+        #[test]
+        fn governance_registers_another_vault() {
+            let (mut deployment, cw_borrower_contract) =
+                proper_instantiate(ProperInstantiateProps {
+                    initial_vault_coins: coin(100_000, "uluna"),
+                    initial_user_coins: coin(1_000, "uluna"),
+                });
+
+            let vault_id = deployment.app.store_code(vault_contract());
+            let vault = deployment
+                .app
+                .instantiate_contract(
+                    vault_id,
+                    Addr::unchecked(ADMIN),
+                    &cw_flash_loan_vault::msg::InstantiateMsg {
+                        denom: "uluna".to_string(),
+                        gateway: Some(deployment.gateway.addr().to_string()),
+                        staking: None,
+                        withdrawal_cooldown: 0,
+                        share_token: None,
+                        fee_curve: None,
+                    },
+                    &[coin(50_000, "uluna")],
+                    "another vault",
+                    None,
+                )
+                .unwrap();
+
+            deployment
+                .app
+                .wasm_sudo(
+                    deployment.gateway.addr(),
+                    &GatewaySudoMsg::UpdateVault {
+                        vault: vault.to_string(),
+                    },
+                )
+                .unwrap();
+
+            // Borrowers see the liquidity of the vault now lending
+            let liquidity = deployment
+                .gateway
+                .liquidity(&deployment.app.wrap())
+                .unwrap();
+            assert_eq!(
+                liquidity,
+                LiquidityResponse {
+                    vault: vault.clone(),
+                    available: coin(50_000, "uluna"),
+                    lent_remotely: coin(0, "uluna"),
+                    borrowed_remotely: coin(0, "uluna"),
+                    receiving_remotely: coin(0, "uluna"),
+                    remote_vaults: vec![],
+                }
+            );
+
+            open_flash_loan(&mut deployment, &cw_borrower_contract).unwrap();
+
+            // The new vault lent the loan and got its fee
+            assert_eq!(deployment.balance(&vault), coin(50_020, "uluna"));
+            assert_eq!(
+                deployment.balance(deployment.vault.addr()),
+                coin(100_000, "uluna")
+            );
+
+            deployment.assert_treasury_balanced();
+        }
+    }
+
+    mod referrals {
+        use super::*;
+        use cw_flash_loan_gateway::msg::{ExecuteMsg as GatewayExecuteMsg, Rejection};
+        use cw_flash_loan_gateway::ContractError as GatewayError;
+        use cw_flash_loan_testing::test_borrower::{self, Hook};
+
+        const REFERRER: &str = "referrer";
+
+        fn instantiate_referred_borrower(app: &mut TestApp) -> Addr {
+            let referred_borrower_id = app.store_code(test_borrower::contract());
+
+            app.instantiate_contract(
+                referred_borrower_id,
+                Addr::unchecked(ADMIN),
+                &Empty {},
+                &[coin(1_000, "uluna")],
+                "referred borrower",
+                None,
+            )
+            .unwrap()
+        }
+
+        fn borrow(
+            app: &mut TestApp,
+            borrower: &Addr,
+            gateway: &CwGatewayContract,
+            referrer: &str,
+        ) -> anyhow::Result<()> {
+            app.execute_contract(
+                Addr::unchecked(ADMIN),
+                borrower.clone(),
+                &test_borrower::ExecuteMsg::Borrow {
+                    gateway: gateway.addr().to_string(),
+                    asset: coin(10_000, "uluna"),
+                    referrer: Some(referrer.to_string()),
+                    hook: Hook::default(),
+                },
+                &[],
+            )?;
+
+            Ok(())
+        }
+
+        #[test]
+        fn referrer_claims_share_of_gateway_fee() {
+            let (mut deployment, _) = proper_instantiate(ProperInstantiateProps {
+                initial_vault_coins: coin(100_000, "uluna"),
+                initial_user_coins: coin(2_000, "uluna"),
+            });
+            let borrower = instantiate_referred_borrower(&mut deployment.app);
+            let referrer = Addr::unchecked(REFERRER);
+
+            borrow(
+                &mut deployment.app,
+                &borrower,
+                &deployment.gateway,
+                REFERRER,
+            )
+            .unwrap();
+            borrow(
+                &mut deployment.app,
+                &borrower,
+                &deployment.gateway,
+                REFERRER,
+            )
+            .unwrap();
+
+            // A tenth of the 100 gateway fee of each loan
+            assert_eq!(
+                deployment
+                    .gateway
+                    .referral_fees(&deployment.app.wrap(), &referrer)
+                    .unwrap(),
+                vec![coin(20, "uluna")]
+            );
+
+            deployment
+                .app
+                .execute_contract(
+                    referrer.clone(),
+                    deployment.gateway.addr(),
+                    &GatewayExecuteMsg::ClaimReferralFees {},
+                    &[],
+                )
+                .unwrap();
+
+            assert_eq!(
+                deployment
+                    .app
+                    .wrap()
+                    .query_balance(&referrer, "uluna")
+                    .unwrap(),
+                coin(20, "uluna")
+            );
+            assert_eq!(
+                deployment
+                    .app
+                    .wrap()
+                    .query_balance(deployment.gateway.addr(), "uluna")
+                    .unwrap(),
+                coin(180, "uluna")
+            );
+
+            let err = deployment
+                .app
+                .execute_contract(
+                    referrer,
+                    deployment.gateway.addr(),
+                    &GatewayExecuteMsg::ClaimReferralFees {},
+                    &[],
+                )
+                .unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<GatewayError>(),
+                Some(GatewayError::NothingToClaim {})
+            ));
+
+            deployment.assert_treasury_balanced();
+        }
+
+        #[test]
+        fn borrower_cannot_refer_itself() {
+            let (mut deployment, _) = proper_instantiate(ProperInstantiateProps {
+                initial_vault_coins: coin(100_000, "uluna"),
+                initial_user_coins: coin(2_000, "uluna"),
+            });
+            let borrower = instantiate_referred_borrower(&mut deployment.app);
+
+            let err = borrow(
+                &mut deployment.app,
+                &borrower,
+                &deployment.gateway,
+                borrower.as_str(),
+            )
+            .unwrap_err();
+
+            assert!(matches!(
+                err.downcast_ref::<GatewayError>(),
+                Some(GatewayError::SelfReferral {})
+            ));
+
+            let simulation = deployment
+                .gateway
+                .simulate_flash_loan(
+                    &deployment.app.wrap(),
+                    vec![coin(10_000, "uluna")],
+                    &borrower,
+                    Some(&borrower),
+                )
+                .unwrap();
+            assert_eq!(simulation.rejection, Some(Rejection::SelfReferral {}));
+
+            deployment.assert_treasury_balanced();
+        }
+    }
+
+    mod treasury {
+        use super::*;
+        use crate::msg::ExecuteMsg;
+        use cw_flash_loan_gateway::msg::{ExecuteMsg as GatewayExecuteMsg, ProtocolFeesResponse};
+        use cw_flash_loan_gateway::ContractError as GatewayError;
+
+        fn withdraw_fees(
+            app: &mut TestApp,
+            sender: &str,
+            cw_gateway_contract: &CwGatewayContract,
+            amount: u128,
+        ) -> anyhow::Result<()> {
+            app.execute_contract(
+                Addr::unchecked(sender),
+                cw_gateway_contract.addr(),
+                &GatewayExecuteMsg::WithdrawFees {
+                    denom: "uluna".to_string(),
+                    amount: Uint128::new(amount),
+                    recipient: "treasury".to_string(),
+                },
+                &[],
+            )?;
+
+            Ok(())
+        }
+
+        #[test]
+        fn owner_withdraws_accrued_fees() {
+            let (mut deployment, cw_borrower_contract) =
+                proper_instantiate(ProperInstantiateProps {
+                    initial_vault_coins: coin(200_000, "uluna"),
+                    initial_user_coins: coin(50, "uluna"),
+                });
+
+            deployment
+                .app
+                .execute(
+                    Addr::unchecked(ADMIN),
+                    cw_borrower_contract
+                        .call(
+                            ExecuteMsg::OpenFlashLoan {
+                                asset: coin(1_000, "uluna"),
+                                min_profit: None,
+                                msgs: vec![],
+                                swap_route: vec![],
+                            },
+                            None,
+                        )
+                        .unwrap(),
+                )
+                .unwrap();
+
+            assert_eq!(
+                deployment
+                    .gateway
+                    .protocol_fees(&deployment.app.wrap())
+                    .unwrap(),
+                ProtocolFeesResponse {
+                    accrued: vec![coin(10, "uluna")],
+                    referral_fees_owed: vec![],
+                }
+            );
+            deployment.assert_treasury_balanced();
+
+            let err =
+                withdraw_fees(&mut deployment.app, USER, &deployment.gateway, 10).unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<GatewayError>(),
+                Some(GatewayError::Unauthorized {})
+            ));
+
+            let err =
+                withdraw_fees(&mut deployment.app, ADMIN, &deployment.gateway, 11).unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<GatewayError>(),
+                Some(GatewayError::InsufficientProtocolFees { available })
+                    if available == &coin(10, "uluna")
+            ));
+
+            withdraw_fees(&mut deployment.app, ADMIN, &deployment.gateway, 4).unwrap();
+
+            assert_eq!(
+                deployment
+                    .app
+                    .wrap()
+                    .query_balance("treasury", "uluna")
+                    .unwrap(),
+                coin(4, "uluna")
+            );
+            assert_eq!(
+                deployment
+                    .gateway
+                    .protocol_fees(&deployment.app.wrap())
+                    .unwrap()
+                    .accrued,
+                vec![coin(6, "uluna")]
+            );
+
+            deployment.assert_treasury_balanced();
+        }
+    }
+
+    /// Off-chain clients driving a local deployment
+    mod client {
+        use super::*;
+        use crate::msg::ExecuteMsg;
+        use cw_flash_loan_gateway::client::{GatewayClient, MsgExecuteContract};
+        use cw_flash_loan_vault::client::VaultClient;
+
+        fn run(app: &mut TestApp, msg: MsgExecuteContract) {
+            app.execute(Addr::unchecked(&msg.sender), msg.to_cosmos_msg().unwrap())
+                .unwrap();
+        }
+
+        #[test]
+        fn clients_work_against_multi_test() {
+            let (mut deployment, cw_borrower_contract) =
+                proper_instantiate(ProperInstantiateProps {
+                    initial_vault_coins: coin(100_000, "uluna"),
+                    initial_user_coins: coin(50, "uluna"),
+                });
+            let vault = VaultClient::new(deployment.vault.addr());
+            let gateway = GatewayClient::new(deployment.gateway.addr());
+            let borrower = cw_borrower_contract.addr().to_string();
+
+            run(
+                &mut deployment.app,
+                vault.deposit(ADMIN, vec![coin(100_000, "uluna")]).unwrap(),
+            );
+            assert_eq!(
+                vault.total_assets(&deployment.app.wrap()).unwrap().total,
+                Uint128::new(200_000)
+            );
+
+            let quote = vault
+                .fee_quote(&deployment.app.wrap(), coin(1_000, "uluna"))
+                .unwrap();
+            let simulation = gateway
+                .simulate_flash_loan(
+                    &deployment.app.wrap(),
+                    vec![coin(1_000, "uluna")],
+                    &borrower,
+                    None,
+                )
+                .unwrap();
+            assert_eq!(simulation.loans[0].vault_fee, quote.fee);
+
+            run(
+                &mut deployment.app,
+                MsgExecuteContract::new(
+                    ADMIN,
+                    &borrower,
+                    &ExecuteMsg::OpenFlashLoan {
+                        asset: coin(1_000, "uluna"),
+                        min_profit: None,
+                        msgs: vec![],
+                        swap_route: vec![],
+                    },
+                    vec![],
+                )
+                .unwrap(),
+            );
+            assert_eq!(
+                gateway
+                    .borrower_tier(&deployment.app.wrap(), &borrower, "uluna")
+                    .unwrap()
+                    .volume,
+                Uint128::new(1_000)
+            );
+            assert_eq!(
+                gateway
+                    .protocol_fees(&deployment.app.wrap())
+                    .unwrap()
+                    .accrued,
+                vec![simulation.loans[0].gateway_fee.clone()]
+            );
+
+            run(
+                &mut deployment.app,
+                gateway
+                    .withdraw_fees(ADMIN, "uluna", Uint128::new(10), "treasury")
+                    .unwrap(),
+            );
+            assert_eq!(
+                deployment
+                    .app
+                    .wrap()
+                    .query_balance("treasury", "uluna")
+                    .unwrap(),
+                coin(10, "uluna")
+            );
+
+            deployment.assert_treasury_balanced();
+        }
+    }
+
+    /// Stand-in for the Osmosis poolmanager module, routing swaps to mock AMM pools.
+    mod osmosis {
+        use crate::swap::osmosis::{
+            EstimateSwapExactAmountInRequest, EstimateSwapExactAmountInResponse,
+            MsgSwapExactAmountIn, ESTIMATE_SWAP_EXACT_AMOUNT_IN_PATH,
+            MSG_SWAP_EXACT_AMOUNT_IN_TYPE_URL,
+        };
+        use anyhow::{bail, Result as AnyResult};
+        use cosmwasm_std::{
+            coin, to_json_binary, Addr, Api, Binary, BlockInfo, Coin, CustomMsg, CustomQuery,
+            Empty, Querier, QuerierWrapper, Storage, Uint128, WasmMsg,
+        };
+        use cw_flash_loan_mock_amm::msg::{
+            Asset, AssetInfo, ExecuteMsg as PoolExecuteMsg, QueryMsg as PoolQueryMsg,
+            SimulationResponse,
+        };
+        use cw_multi_test::{
+            AppResponse, CosmosRouter, Module, Stargate, StargateMsg, StargateQuery,
+        };
+        use cw_storage_plus::Map;
+        use serde::de::DeserializeOwned;
+
+        const POOLS: Map<u64, Addr> = Map::new("osmosis_pools");
+
+        #[derive(Default)]
+        pub struct MockPoolManager {}
+
+        impl MockPoolManager {
+            pub fn register_pool(&self, storage: &mut dyn Storage, pool_id: u64, pool: Addr) {
+                POOLS.save(storage, pool_id, &pool).unwrap();
+            }
+
+            fn simulate(
+                storage: &dyn Storage,
+                querier: &QuerierWrapper,
+                pool_id: u64,
+                offer: &Coin,
+            ) -> AnyResult<Uint128> {
+                let simulation: SimulationResponse = querier.query_wasm_smart(
+                    POOLS.load(storage, pool_id)?,
+                    &PoolQueryMsg::Simulation {
+                        offer_asset: native(offer),
+                        ask_asset_info: None,
+                    },
+                )?;
+
+                Ok(simulation.return_amount)
+            }
+        }
+
+        fn native(offer: &Coin) -> Asset {
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: offer.denom.clone(),
+                },
+                amount: offer.amount,
+            }
+        }
+
+        /// Parses coins formatted as `1000uosmo`.
+        fn parse_coin(value: &str) -> AnyResult<Coin> {
+            let split = value
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(value.len());
+            let (amount, denom) = value.split_at(split);
+
+            Ok(coin(amount.parse()?, denom))
+        }
+
+        impl Module for MockPoolManager {
+            type ExecT = StargateMsg;
+            type QueryT = StargateQuery;
+            type SudoT = Empty;
+
+            fn execute<ExecC, QueryC>(
+                &self,
+                api: &dyn Api,
+                storage: &mut dyn Storage,
+                router: &dyn CosmosRouter<ExecC = ExecC, QueryC = QueryC>,
+                block: &BlockInfo,
+                sender: Addr,
+                msg: StargateMsg,
+            ) -> AnyResult<AppResponse>
+            where
+                ExecC: CustomMsg + DeserializeOwned + 'static,
+                QueryC: CustomQuery + DeserializeOwned + 'static,
+            {
+                if msg.type_url != MSG_SWAP_EXACT_AMOUNT_IN_TYPE_URL {
+                    bail!("Unsupported message {}", msg.type_url);
+                }
+
+                let msg: MsgSwapExactAmountIn = prost::Message::decode(msg.value.as_slice())?;
+                let token_in = msg.token_in.unwrap_or_default();
+                let mut offer = coin(token_in.amount.parse()?, token_in.denom);
+                let mut response = AppResponse::default();
+
+                for route in msg.routes {
+                    let pool = POOLS.load(storage, route.pool_id)?;
+
+                    let swap_response = router.execute(
+                        api,
+                        storage,
+                        block,
+                        sender.clone(),
+                        WasmMsg::Execute {
+                            contract_addr: pool.to_string(),
+                            msg: to_json_binary(&PoolExecuteMsg::Swap {
+                                offer_asset: native(&offer),
+                                ask_asset_info: Some(AssetInfo::NativeToken {
+                                    denom: route.token_out_denom.clone(),
+                                }),
+                                belief_price: None,
+                                max_spread: None,
+                                to: None,
+                            })?,
+                            funds: vec![offer.clone()],
+                        }
+                        .into(),
+                    )?;
+
+                    let return_amount = swap_response
+                        .events
+                        .iter()
+                        .flat_map(|event| event.attributes.iter())
+                        .find(|attribute| attribute.key == "return_amount")
+                        .map(|attribute| attribute.value.parse::<u128>())
+                        .transpose()?
+                        .unwrap_or_default();
+
+                    response.events.extend(swap_response.events);
+                    offer = coin(return_amount, route.token_out_denom);
+                }
+
+                if offer.amount < Uint128::new(msg.token_out_min_amount.parse()?) {
+                    bail!("Swap returned less than {}", msg.token_out_min_amount);
+                }
+
+                Ok(response)
+            }
+
+            fn query(
+                &self,
+                _api: &dyn Api,
+                storage: &dyn Storage,
+                querier: &dyn Querier,
+                _block: &BlockInfo,
+                request: StargateQuery,
+            ) -> AnyResult<Binary> {
+                if request.path != ESTIMATE_SWAP_EXACT_AMOUNT_IN_PATH {
+                    bail!("Unsupported query {}", request.path);
+                }
+
+                let request: EstimateSwapExactAmountInRequest =
+                    prost::Message::decode(request.data.as_slice())?;
+                let querier = QuerierWrapper::<Empty>::new(querier);
+                let mut offer = parse_coin(&request.token_in)?;
+
+                for route in request.routes {
+                    let return_amount = Self::simulate(storage, &querier, route.pool_id, &offer)?;
+                    offer = coin(return_amount.u128(), route.token_out_denom);
+                }
+
+                Ok(to_json_binary(&EstimateSwapExactAmountInResponse {
+                    token_out_amount: offer.amount.to_string(),
+                })?)
+            }
+
+            fn sudo<ExecC, QueryC>(
+                &self,
+                _api: &dyn Api,
+                _storage: &mut dyn Storage,
+                _router: &dyn CosmosRouter<ExecC = ExecC, QueryC = QueryC>,
+                _block: &BlockInfo,
+                _msg: Empty,
+            ) -> AnyResult<AppResponse>
+            where
+                ExecC: CustomMsg + DeserializeOwned + 'static,
+                QueryC: CustomQuery + DeserializeOwned + 'static,
+            {
+                bail!("Sudo is not supported")
+            }
+        }
+
+        impl Stargate for MockPoolManager {}
+    }
+
+    mod swap_route {
+        use super::*;
+        use crate::msg::ExecuteMsg;
+        use crate::swap::{Dex, SwapStep};
+        use crate::ContractError;
+        use cosmwasm_std::{to_json_binary, WasmMsg};
+        use cw_flash_loan_mock_amm::msg::ExecuteMsg as PoolExecuteMsg;
+
+        struct Pools {
+            cheap: Addr,
+            expensive: Addr,
+        }
+
+        fn instantiate_mispriced_pools(app: &mut TestApp) -> Pools {
+            // 1 uluna = 2 uusd
+            let cheap = instantiate_pool(app, vec![coin(100_000, "uluna"), coin(200_000, "uusd")]);
+            // 1 uluna = 3 uusd
+            let expensive =
+                instantiate_pool(app, vec![coin(100_000, "uluna"), coin(300_000, "uusd")]);
+
+            Pools { cheap, expensive }
+        }
+
+        fn expected_uluna_bought_back(app: &TestApp, pools: &Pools, borrowed: &Coin) -> u128 {
+            let uusd_amount = simulate_swap(app, &pools.expensive, borrowed);
+            simulate_swap(app, &pools.cheap, &coin(uusd_amount.u128(), "uusd")).u128()
+        }
+
+        fn open_flash_loan(
+            app: &mut TestApp,
+            cw_borrower_contract: &CwBorrowerContract,
+            borrowed: Coin,
+            swap_route: Vec<SwapStep>,
+        ) {
+            let cosmos_msg = cw_borrower_contract
+                .call(
+                    ExecuteMsg::OpenFlashLoan {
+                        asset: borrowed,
+                        min_profit: Some(Uint128::new(100)),
+                        msgs: vec![],
+                        swap_route,
+                    },
+                    None,
+                )
+                .unwrap();
+
+            app.execute(Addr::unchecked(ADMIN), cosmos_msg).unwrap();
+        }
+
+        #[test]
+        fn astroport_route_arbitrage() {
+            let borrowed = coin(1_000, "uluna");
+
+            let (mut deployment, cw_borrower_contract) =
+                proper_instantiate(ProperInstantiateProps {
+                    initial_vault_coins: coin(200_000, "uluna"),
+                    initial_user_coins: coin(50, "uluna"),
+                });
+            let pools = instantiate_mispriced_pools(&mut deployment.app);
+            let uluna_bought_back = expected_uluna_bought_back(&deployment.app, &pools, &borrowed);
+
+            open_flash_loan(
+                &mut deployment.app,
+                &cw_borrower_contract,
+                borrowed,
+                vec![
+                    SwapStep {
+                        dex: Dex::Astroport {
+                            pair_addr: pools.expensive.to_string(),
+                        },
+                        ask_denom: "uusd".to_string(),
+                    },
+                    SwapStep {
+                        dex: Dex::Astroport {
+                            pair_addr: pools.cheap.to_string(),
+                        },
+                        ask_denom: "uluna".to_string(),
+                    },
+                ],
+            );
+
+            let borrower_balance = deployment
+                .app
+                .wrap()
+                .query_balance(cw_borrower_contract.addr(), "uluna")
+                .unwrap();
+
+            assert_eq!(
+                borrower_balance,
+                coin(50 + uluna_bought_back - 1_030, "uluna")
+            );
+
+            deployment.assert_treasury_balanced();
+        }
+
+        #[test]
+        fn osmosis_route_arbitrage() {
+            let borrowed = coin(1_000, "uluna");
+
+            let (mut deployment, cw_borrower_contract) =
+                proper_instantiate(ProperInstantiateProps {
+                    initial_vault_coins: coin(200_000, "uluna"),
+                    initial_user_coins: coin(50, "uluna"),
+                });
+            let pools = instantiate_mispriced_pools(&mut deployment.app);
+            let uluna_bought_back = expected_uluna_bought_back(&deployment.app, &pools, &borrowed);
+
+            deployment.app.init_modules(|router, _, storage| {
+                router
+                    .stargate
+                    .register_pool(storage, 1, pools.cheap.clone());
+                router
+                    .stargate
+                    .register_pool(storage, 2, pools.expensive.clone());
+            });
+
+            open_flash_loan(
+                &mut deployment.app,
+                &cw_borrower_contract,
+                borrowed,
+                vec![
+                    SwapStep {
+                        dex: Dex::Osmosis { pool_id: 2 },
+                        ask_denom: "uusd".to_string(),
+                    },
+                    SwapStep {
+                        dex: Dex::Osmosis { pool_id: 1 },
+                        ask_denom: "uluna".to_string(),
+                    },
+                ],
+            );
+
+            let borrower_balance = deployment
+                .app
+                .wrap()
+                .query_balance(cw_borrower_contract.addr(), "uluna")
+                .unwrap();
+
+            assert_eq!(
+                borrower_balance,
+                coin(50 + uluna_bought_back - 1_030, "uluna")
+            );
+
+            deployment.assert_treasury_balanced();
+        }
+
+        #[test]
+        fn route_is_priced_after_msgs() {
+            let borrowed = coin(1_000, "uluna");
+
+            let (mut deployment, cw_borrower_contract) =
+                proper_instantiate(ProperInstantiateProps {
+                    initial_vault_coins: coin(200_000, "uluna"),
+                    initial_user_coins: coin(1_000, "uluna"),
+                });
+            let pools = instantiate_mispriced_pools(&mut deployment.app);
+
+            // Selling on the expensive pool first leaves less of a price gap for the route
+            let sold = coin(500, "uluna");
+            let uusd_bought = simulate_swap(&deployment.app, &pools.expensive, &sold);
+            let msgs = vec![WasmMsg::Execute {
+                contract_addr: pools.expensive.to_string(),
+                msg: to_json_binary(&PoolExecuteMsg::Swap {
+                    offer_asset: native(&sold),
+                    ask_asset_info: None,
+                    belief_price: None,
+                    max_spread: None,
+                    to: None,
+                })
+                .unwrap(),
+                funds: vec![sold],
+            }
+            .into()];
+
+            let cosmos_msg = cw_borrower_contract
+                .call(
+                    ExecuteMsg::OpenFlashLoan {
+                        asset: borrowed,
+                        min_profit: None,
+                        msgs,
+                        swap_route: vec![
+                            SwapStep {
+                                dex: Dex::Astroport {
+                                    pair_addr: pools.expensive.to_string(),
+                                },
+                                ask_denom: "uusd".to_string(),
+                            },
+                            SwapStep {
+                                dex: Dex::Astroport {
+                                    pair_addr: pools.cheap.to_string(),
+                                },
+                                ask_denom: "uluna".to_string(),
+                            },
+                        ],
+                    },
+                    None,
+                )
+                .unwrap();
+
+            // Priced before the sale, the route would ask the expensive pool for too much
+            deployment
+                .app
+                .execute(Addr::unchecked(ADMIN), cosmos_msg)
+                .unwrap();
+
+            // the route swapped exactly what it got from its first hop
+            let uusd_balance = deployment
+                .app
+                .wrap()
+                .query_balance(cw_borrower_contract.addr(), "uusd")
+                .unwrap();
+            assert_eq!(uusd_balance.amount, uusd_bought);
+
+            deployment.assert_treasury_balanced();
+        }
+
+        #[test]
+        fn only_borrower_swaps_along_the_route() {
+            let (mut deployment, cw_borrower_contract) =
+                proper_instantiate(ProperInstantiateProps {
+                    initial_vault_coins: coin(200_000, "uluna"),
+                    initial_user_coins: coin(50, "uluna"),
+                });
+
+            let cosmos_msg = cw_borrower_contract
+                .call(ExecuteMsg::SwapRoute {}, None)
+                .unwrap();
+
+            let err = deployment
+                .app
+                .execute(Addr::unchecked(ADMIN), cosmos_msg)
+                .unwrap_err();
+
+            assert!(matches!(
+                err.downcast_ref::<ContractError>(),
+                Some(ContractError::Unauthorized {})
+            ));
         }
     }
 }
@@ -4,5 +4,6 @@ pub mod helpers;
 pub mod integration_tests;
 pub mod msg;
 pub mod state;
+pub mod swap;
 
 pub use crate::error::ContractError;
@@ -1,26 +1,64 @@
-use cosmwasm_std::Coin;
+use cosmwasm_schema::QueryResponses;
+use cosmwasm_std::{Coin, CosmosMsg, Uint128};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::state::Config;
+use crate::swap::SwapStep;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InstantiateMsg {
     pub cw_gateway_contract_addr: String,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateMsg {}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
     OpenFlashLoan {
-        asset_to_borrow: Coin,
+        asset: Coin,
+        /// When set, the whole transaction fails unless the loan increases
+        /// the borrower's balance of the borrowed denom by at least this much
+        min_profit: Option<Uint128>,
+        /// Messages executed by the borrower with the flash-loaned funds,
+        /// right before the loan gets repaid
+        #[serde(default)]
+        msgs: Vec<CosmosMsg>,
+        /// Swaps executed after `msgs`, starting with the borrowed asset.
+        /// They are priced once `msgs` ran, so each pool should only appear once in the route.
+        #[serde(default)]
+        swap_route: Vec<SwapStep>,
     },
     OnFlashLoanProvided {},
+    /// Swaps the borrowed asset along the loan's route, only callable by the contract itself
+    SwapRoute {},
+    /// Final step of the flash loan, only callable by the contract itself
+    CloseFlashLoan {},
+    /// Sends accumulated profits out of the contract, owner only
+    Withdraw {
+        amount: Coin,
+        recipient: String,
+    },
 }
 
+/// Data the `OnFlashLoanProvided` hook responds to the gateway with,
+/// and `OpenFlashLoan` to the owner
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FlashLoanProfit {
+    /// Balance of the borrowed denom before the loan was opened
+    pub initial_balance: Coin,
+    /// Balance of the borrowed denom once the loan was repaid
+    pub final_balance: Coin,
+    /// Zero when the loan cost more than it brought
+    pub profit: Coin,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, QueryResponses)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
+    #[returns(StateResponse)]
     State,
 }
 
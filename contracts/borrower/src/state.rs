@@ -1,12 +1,30 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::Addr;
+use cosmwasm_std::{Addr, Coin, CosmosMsg, Uint128};
 use cw_storage_plus::Item;
 
+use crate::swap::SwapStep;
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Config {
+    /// The only address allowed to open flash loans and withdraw profits
+    pub owner: Addr,
     pub cw_gateway_contract_addr: Addr,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FlashLoan {
+    pub asset: Coin,
+    pub min_profit: Option<Uint128>,
+    /// Balance of the borrowed denom before the loan was opened
+    pub initial_balance: Uint128,
+    /// Messages to execute once the loan is provided
+    pub msgs: Vec<CosmosMsg>,
+    /// Swaps to run once `msgs` are done
+    pub swap_route: Vec<SwapStep>,
+}
+
 pub const CONFIG: Item<Config> = Item::new("config");
+
+pub const FLASH_LOAN: Item<FlashLoan> = Item::new("flash_loan");
@@ -0,0 +1,410 @@
+//! Builds DEX swap messages and simulation queries for flash loan strategies.
+//!
+//! A strategy describes its swaps as a list of [`SwapStep`]s and doesn't need to know
+//! which DEX will execute them: every supported DEX is hidden behind [`SwapAdapter`].
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{
+    coin, to_json_binary, Addr, Api, Binary, Coin, CosmosMsg, Decimal, QuerierWrapper,
+    QueryRequest, StdError, StdResult, Uint128, WasmMsg,
+};
+
+/// Common interface of all supported DEXes.
+pub trait SwapAdapter {
+    /// Message swapping `offer` for `ask_denom` on behalf of `sender`.
+    /// The swap fails when it would return less than `min_return`.
+    fn swap_msg(
+        &self,
+        sender: &Addr,
+        offer: Coin,
+        ask_denom: &str,
+        min_return: Uint128,
+    ) -> StdResult<CosmosMsg>;
+
+    /// Tells how much of `ask_denom` would be returned for `offer`.
+    fn simulate_swap(
+        &self,
+        querier: &QuerierWrapper,
+        offer: &Coin,
+        ask_denom: &str,
+    ) -> StdResult<Uint128>;
+}
+
+/// DEX to run a swap on, as provided in messages.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Dex {
+    /// Astroport pair contract
+    Astroport { pair_addr: String },
+    /// Osmosis pool, swapped through the poolmanager module
+    Osmosis { pool_id: u64 },
+}
+
+impl Dex {
+    pub fn adapter(&self, api: &dyn Api) -> StdResult<Box<dyn SwapAdapter>> {
+        Ok(match self {
+            Dex::Astroport { pair_addr } => Box::new(Astroport {
+                pair_addr: api.addr_validate(pair_addr)?,
+            }),
+            Dex::Osmosis { pool_id } => Box::new(Osmosis { pool_id: *pool_id }),
+        })
+    }
+}
+
+/// A single hop of a swap route.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SwapStep {
+    pub dex: Dex,
+    pub ask_denom: String,
+}
+
+/// Builds messages swapping `offer` along the `route`.
+///
+/// Every hop is simulated up front, against the pools as they are when the messages are built,
+/// and the swap is expected to return exactly the simulated amount, so each pool should only
+/// appear once in the route.
+pub fn swap_route_msgs(
+    api: &dyn Api,
+    querier: &QuerierWrapper,
+    sender: &Addr,
+    offer: Coin,
+    route: &[SwapStep],
+) -> StdResult<(Vec<CosmosMsg>, Coin)> {
+    let mut msgs = Vec::with_capacity(route.len());
+    let mut offer = offer;
+
+    for step in route {
+        let adapter = step.dex.adapter(api)?;
+        let return_amount = adapter.simulate_swap(querier, &offer, &step.ask_denom)?;
+
+        msgs.push(adapter.swap_msg(sender, offer, &step.ask_denom, return_amount)?);
+
+        offer = coin(return_amount.u128(), step.ask_denom.as_str());
+    }
+
+    Ok((msgs, offer))
+}
+
+pub struct Astroport {
+    pub pair_addr: Addr,
+}
+
+pub mod astroport {
+    //! Subset of the Astroport pair interface used by the adapter.
+
+    use cosmwasm_std::{Decimal, Uint128};
+    use schemars::JsonSchema;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    #[serde(rename_all = "snake_case")]
+    pub enum AssetInfo {
+        NativeToken { denom: String },
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    pub struct Asset {
+        pub info: AssetInfo,
+        pub amount: Uint128,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    #[serde(rename_all = "snake_case")]
+    pub enum ExecuteMsg {
+        Swap {
+            offer_asset: Asset,
+            ask_asset_info: Option<AssetInfo>,
+            belief_price: Option<Decimal>,
+            max_spread: Option<Decimal>,
+            to: Option<String>,
+        },
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    #[serde(rename_all = "snake_case")]
+    pub enum QueryMsg {
+        Simulation {
+            offer_asset: Asset,
+            ask_asset_info: Option<AssetInfo>,
+        },
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    pub struct SimulationResponse {
+        pub return_amount: Uint128,
+        pub spread_amount: Uint128,
+        pub commission_amount: Uint128,
+    }
+
+    pub fn native_asset(denom: &str, amount: Uint128) -> Asset {
+        Asset {
+            info: AssetInfo::NativeToken {
+                denom: denom.to_string(),
+            },
+            amount,
+        }
+    }
+}
+
+impl SwapAdapter for Astroport {
+    fn swap_msg(
+        &self,
+        _sender: &Addr,
+        offer: Coin,
+        ask_denom: &str,
+        min_return: Uint128,
+    ) -> StdResult<CosmosMsg> {
+        if min_return.is_zero() {
+            return Err(StdError::generic_err("Swap would return nothing"));
+        }
+
+        // Astroport has no minimum receive, but with no spread allowed over this price
+        // the pair refuses to return less than `min_return`
+        let belief_price = Decimal::from_ratio(offer.amount, min_return);
+
+        let msg = astroport::ExecuteMsg::Swap {
+            offer_asset: astroport::native_asset(&offer.denom, offer.amount),
+            ask_asset_info: Some(astroport::AssetInfo::NativeToken {
+                denom: ask_denom.to_string(),
+            }),
+            belief_price: Some(belief_price),
+            max_spread: Some(Decimal::zero()),
+            to: None,
+        };
+
+        Ok(WasmMsg::Execute {
+            contract_addr: self.pair_addr.to_string(),
+            msg: to_json_binary(&msg)?,
+            funds: vec![offer],
+        }
+        .into())
+    }
+
+    fn simulate_swap(
+        &self,
+        querier: &QuerierWrapper,
+        offer: &Coin,
+        ask_denom: &str,
+    ) -> StdResult<Uint128> {
+        let simulation: astroport::SimulationResponse = querier.query_wasm_smart(
+            self.pair_addr.clone(),
+            &astroport::QueryMsg::Simulation {
+                offer_asset: astroport::native_asset(&offer.denom, offer.amount),
+                ask_asset_info: Some(astroport::AssetInfo::NativeToken {
+                    denom: ask_denom.to_string(),
+                }),
+            },
+        )?;
+
+        Ok(simulation.return_amount)
+    }
+}
+
+pub struct Osmosis {
+    pub pool_id: u64,
+}
+
+pub mod osmosis {
+    //! Protobuf types of the Osmosis poolmanager module used by the adapter.
+
+    use schemars::JsonSchema;
+    use serde::{Deserialize, Serialize};
+
+    pub const MSG_SWAP_EXACT_AMOUNT_IN_TYPE_URL: &str =
+        "/osmosis.poolmanager.v1beta1.MsgSwapExactAmountIn";
+
+    pub const ESTIMATE_SWAP_EXACT_AMOUNT_IN_PATH: &str =
+        "/osmosis.poolmanager.v1beta1.Query/EstimateSwapExactAmountIn";
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Coin {
+        #[prost(string, tag = "1")]
+        pub denom: String,
+        #[prost(string, tag = "2")]
+        pub amount: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SwapAmountInRoute {
+        #[prost(uint64, tag = "1")]
+        pub pool_id: u64,
+        #[prost(string, tag = "2")]
+        pub token_out_denom: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct MsgSwapExactAmountIn {
+        #[prost(string, tag = "1")]
+        pub sender: String,
+        #[prost(message, repeated, tag = "2")]
+        pub routes: Vec<SwapAmountInRoute>,
+        #[prost(message, optional, tag = "3")]
+        pub token_in: Option<Coin>,
+        #[prost(string, tag = "4")]
+        pub token_out_min_amount: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct EstimateSwapExactAmountInRequest {
+        #[prost(uint64, tag = "2")]
+        pub pool_id: u64,
+        #[prost(string, tag = "3")]
+        pub token_in: String,
+        #[prost(message, repeated, tag = "4")]
+        pub routes: Vec<SwapAmountInRoute>,
+    }
+
+    /// Stargate query responses are handed over to contracts as JSON
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    pub struct EstimateSwapExactAmountInResponse {
+        pub token_out_amount: String,
+    }
+}
+
+impl Osmosis {
+    fn routes(&self, ask_denom: &str) -> Vec<osmosis::SwapAmountInRoute> {
+        vec![osmosis::SwapAmountInRoute {
+            pool_id: self.pool_id,
+            token_out_denom: ask_denom.to_string(),
+        }]
+    }
+}
+
+impl SwapAdapter for Osmosis {
+    fn swap_msg(
+        &self,
+        sender: &Addr,
+        offer: Coin,
+        ask_denom: &str,
+        min_return: Uint128,
+    ) -> StdResult<CosmosMsg> {
+        let msg = osmosis::MsgSwapExactAmountIn {
+            sender: sender.to_string(),
+            routes: self.routes(ask_denom),
+            token_in: Some(osmosis::Coin {
+                denom: offer.denom,
+                amount: offer.amount.to_string(),
+            }),
+            token_out_min_amount: min_return.to_string(),
+        };
+
+        Ok(CosmosMsg::Stargate {
+            type_url: osmosis::MSG_SWAP_EXACT_AMOUNT_IN_TYPE_URL.to_string(),
+            value: Binary::from(prost::Message::encode_to_vec(&msg)),
+        })
+    }
+
+    fn simulate_swap(
+        &self,
+        querier: &QuerierWrapper,
+        offer: &Coin,
+        ask_denom: &str,
+    ) -> StdResult<Uint128> {
+        let request = osmosis::EstimateSwapExactAmountInRequest {
+            pool_id: self.pool_id,
+            token_in: offer.to_string(),
+            routes: self.routes(ask_denom),
+        };
+
+        let response: osmosis::EstimateSwapExactAmountInResponse =
+            querier.query(&QueryRequest::<cosmwasm_std::Empty>::Stargate {
+                path: osmosis::ESTIMATE_SWAP_EXACT_AMOUNT_IN_PATH.to_string(),
+                data: Binary::from(prost::Message::encode_to_vec(&request)),
+            })?;
+
+        response
+            .token_out_amount
+            .parse::<u128>()
+            .map(Uint128::new)
+            .map_err(|err| StdError::parse_err("Uint128", err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::{from_json, testing::mock_dependencies};
+
+    #[test]
+    fn astroport_swap_refuses_less_than_min_return() {
+        let adapter = Astroport {
+            pair_addr: Addr::unchecked("pair"),
+        };
+
+        let msg = adapter
+            .swap_msg(
+                &Addr::unchecked("borrower"),
+                coin(1_000, "uluna"),
+                "uusd",
+                Uint128::new(2_000),
+            )
+            .unwrap();
+
+        match msg {
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr,
+                msg,
+                funds,
+            }) => {
+                assert_eq!(contract_addr, "pair");
+                assert_eq!(funds, vec![coin(1_000, "uluna")]);
+                assert_eq!(
+                    from_json::<astroport::ExecuteMsg>(&msg).unwrap(),
+                    astroport::ExecuteMsg::Swap {
+                        offer_asset: astroport::native_asset("uluna", Uint128::new(1_000)),
+                        ask_asset_info: Some(astroport::AssetInfo::NativeToken {
+                            denom: "uusd".to_string()
+                        }),
+                        belief_price: Some(Decimal::percent(50)),
+                        max_spread: Some(Decimal::zero()),
+                        to: None,
+                    }
+                );
+            }
+            msg => panic!("unexpected message {:?}", msg),
+        }
+    }
+
+    #[test]
+    fn osmosis_swap_is_a_poolmanager_message() {
+        let deps = mock_dependencies();
+        let adapter = Dex::Osmosis { pool_id: 7 }.adapter(&deps.api).unwrap();
+
+        let msg = adapter
+            .swap_msg(
+                &Addr::unchecked("borrower"),
+                coin(1_000, "uosmo"),
+                "uatom",
+                Uint128::new(99),
+            )
+            .unwrap();
+
+        match msg {
+            CosmosMsg::Stargate { type_url, value } => {
+                assert_eq!(type_url, osmosis::MSG_SWAP_EXACT_AMOUNT_IN_TYPE_URL);
+
+                let msg: osmosis::MsgSwapExactAmountIn =
+                    prost::Message::decode(value.as_slice()).unwrap();
+                assert_eq!(
+                    msg,
+                    osmosis::MsgSwapExactAmountIn {
+                        sender: "borrower".to_string(),
+                        routes: vec![osmosis::SwapAmountInRoute {
+                            pool_id: 7,
+                            token_out_denom: "uatom".to_string(),
+                        }],
+                        token_in: Some(osmosis::Coin {
+                            denom: "uosmo".to_string(),
+                            amount: "1000".to_string(),
+                        }),
+                        token_out_min_amount: "99".to_string(),
+                    }
+                );
+            }
+            msg => panic!("unexpected message {:?}", msg),
+        }
+    }
+}
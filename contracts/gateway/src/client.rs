@@ -0,0 +1,225 @@
+//! Typed client for using the gateway from off-chain code, see the vault's client.
+
+use cosmwasm_std::{to_json_binary, Addr, Coin, StdError, StdResult, Uint128};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+pub use cw_flash_loan_vault::client::{ContractQuerier, MsgExecuteContract};
+
+use crate::msg::{
+    BorrowerTierResponse, ExecuteMsg, LiquidityResponse, ProtocolFeesResponse, QueryMsg,
+    SimulateFlashLoanResponse,
+};
+use crate::state::FeeConfig;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GatewayClient {
+    pub contract: String,
+}
+
+impl GatewayClient {
+    pub fn new(contract: impl Into<String>) -> Self {
+        GatewayClient {
+            contract: contract.into(),
+        }
+    }
+
+    pub fn execute(
+        &self,
+        sender: &str,
+        msg: &ExecuteMsg,
+        funds: Vec<Coin>,
+    ) -> StdResult<MsgExecuteContract> {
+        MsgExecuteContract::new(sender, &self.contract, msg, funds)
+    }
+
+    /// `sender` must be a contract handling `on_funded_msg` and repaying the loan
+    pub fn request_flash_loan<M: Serialize>(
+        &self,
+        sender: &str,
+        asset: Coin,
+        on_funded_msg: &M,
+        referrer: Option<&str>,
+    ) -> StdResult<MsgExecuteContract> {
+        let msg = ExecuteMsg::RequestFlashLoan {
+            asset,
+            on_funded_msg: to_json_binary(on_funded_msg)?,
+            referrer: referrer.map(str::to_string),
+        };
+
+        self.execute(sender, &msg, vec![])
+    }
+
+    /// Only the vault can tell the gateway a loan was provided
+    pub fn flash_loan_provided(&self, sender: &str, asset: Coin) -> StdResult<MsgExecuteContract> {
+        self.execute(sender, &ExecuteMsg::FlashLoanProvided { asset }, vec![])
+    }
+
+    pub fn claim_referral_fees(&self, sender: &str) -> StdResult<MsgExecuteContract> {
+        self.execute(sender, &ExecuteMsg::ClaimReferralFees {}, vec![])
+    }
+
+    pub fn withdraw_fees(
+        &self,
+        sender: &str,
+        denom: &str,
+        amount: Uint128,
+        recipient: &str,
+    ) -> StdResult<MsgExecuteContract> {
+        let msg = ExecuteMsg::WithdrawFees {
+            denom: denom.to_string(),
+            amount,
+            recipient: recipient.to_string(),
+        };
+
+        self.execute(sender, &msg, vec![])
+    }
+
+    pub fn update_fees(&self, sender: &str, fees: FeeConfig) -> StdResult<MsgExecuteContract> {
+        self.execute(sender, &ExecuteMsg::UpdateFees { fees }, vec![])
+    }
+
+    pub fn update_min_loan_sizes(
+        &self,
+        sender: &str,
+        min_loan_sizes: Vec<Coin>,
+    ) -> StdResult<MsgExecuteContract> {
+        let msg = ExecuteMsg::UpdateMinLoanSizes { min_loan_sizes };
+
+        self.execute(sender, &msg, vec![])
+    }
+
+    pub fn clear_stale_loans(&self, sender: &str) -> StdResult<MsgExecuteContract> {
+        self.execute(sender, &ExecuteMsg::ClearStaleLoans {}, vec![])
+    }
+
+    pub fn pause(&self, sender: &str) -> StdResult<MsgExecuteContract> {
+        self.execute(sender, &ExecuteMsg::Pause {}, vec![])
+    }
+
+    pub fn unpause(&self, sender: &str) -> StdResult<MsgExecuteContract> {
+        self.execute(sender, &ExecuteMsg::Unpause {}, vec![])
+    }
+
+    pub fn update_allowed_borrowers(
+        &self,
+        sender: &str,
+        add: Vec<String>,
+        remove: Vec<String>,
+        restrict: Option<bool>,
+    ) -> StdResult<MsgExecuteContract> {
+        let msg = ExecuteMsg::UpdateAllowedBorrowers {
+            add,
+            remove,
+            restrict,
+        };
+
+        self.execute(sender, &msg, vec![])
+    }
+
+    pub fn update_vault(&self, sender: &str, vault: &str) -> StdResult<MsgExecuteContract> {
+        let msg = ExecuteMsg::UpdateVault {
+            vault: vault.to_string(),
+        };
+
+        self.execute(sender, &msg, vec![])
+    }
+
+    pub fn query<T: DeserializeOwned>(
+        &self,
+        querier: &impl ContractQuerier,
+        msg: &QueryMsg,
+    ) -> StdResult<T> {
+        querier.query_smart(&self.contract, &to_json_binary(msg)?)
+    }
+
+    /// Total repayment and the vault's part of it, while `borrower` has a loan out
+    pub fn debt_remaining(
+        &self,
+        querier: &impl ContractQuerier,
+        borrower: &str,
+    ) -> StdResult<(Coin, Coin)> {
+        let msg = QueryMsg::DebtRemaining {
+            borrower: Addr::unchecked(borrower),
+        };
+
+        self.query(querier, &msg)
+    }
+
+    pub fn simulate_flash_loan(
+        &self,
+        querier: &impl ContractQuerier,
+        assets: Vec<Coin>,
+        borrower: &str,
+        referrer: Option<&str>,
+    ) -> StdResult<SimulateFlashLoanResponse> {
+        let msg = QueryMsg::SimulateFlashLoan {
+            assets,
+            borrower: borrower.to_string(),
+            referrer: referrer.map(ToString::to_string),
+        };
+
+        self.query(querier, &msg)
+    }
+
+    pub fn borrower_tier(
+        &self,
+        querier: &impl ContractQuerier,
+        borrower: &str,
+        denom: &str,
+    ) -> StdResult<BorrowerTierResponse> {
+        let msg = QueryMsg::BorrowerTier {
+            borrower: borrower.to_string(),
+            denom: Some(denom.to_string()),
+        };
+
+        let tiers: Vec<BorrowerTierResponse> = self.query(querier, &msg)?;
+
+        tiers
+            .into_iter()
+            .next()
+            .ok_or_else(|| StdError::generic_err("No tier returned for the denom"))
+    }
+
+    pub fn borrower_tiers(
+        &self,
+        querier: &impl ContractQuerier,
+        borrower: &str,
+    ) -> StdResult<Vec<BorrowerTierResponse>> {
+        let msg = QueryMsg::BorrowerTier {
+            borrower: borrower.to_string(),
+            denom: None,
+        };
+
+        self.query(querier, &msg)
+    }
+
+    pub fn referral_fees(
+        &self,
+        querier: &impl ContractQuerier,
+        referrer: &str,
+    ) -> StdResult<Vec<Coin>> {
+        let msg = QueryMsg::ReferralFees {
+            referrer: referrer.to_string(),
+        };
+
+        self.query(querier, &msg)
+    }
+
+    pub fn protocol_fees(&self, querier: &impl ContractQuerier) -> StdResult<ProtocolFeesResponse> {
+        self.query(querier, &QueryMsg::ProtocolFees {})
+    }
+
+    pub fn liquidity(&self, querier: &impl ContractQuerier) -> StdResult<LiquidityResponse> {
+        self.query(querier, &QueryMsg::Liquidity {})
+    }
+
+    pub fn min_loan_size(&self, querier: &impl ContractQuerier, denom: &str) -> StdResult<Coin> {
+        let msg = QueryMsg::MinLoanSize {
+            denom: denom.to_string(),
+        };
+
+        self.query(querier, &msg)
+    }
+}
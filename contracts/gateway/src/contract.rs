@@ -1,26 +1,39 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    coin, to_binary, Addr, BankMsg, Binary, Coin, Deps, DepsMut, Env, MessageInfo, Reply, Response,
-    StdError, StdResult, SubMsg, Uint128, WasmMsg,
+    coin, to_json_binary, Addr, BankMsg, Binary, Coin, Decimal, Deps, DepsMut, Empty, Env,
+    MessageInfo, Order, Reply, Response, StdError, StdResult, SubMsg, SubMsgResult, Uint128,
+    WasmMsg,
 };
 use cw2::set_contract_version;
+use cw_utils::parse_execute_response_data;
 
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
-use crate::state::{Config, FlashLoanState, CONFIG, FLASH_LOAN_STATE};
+use crate::fees::{borrower_denoms, borrower_volume, record_volume};
+use crate::msg::{
+    BorrowerTierResponse, ExecuteMsg, FlashLoanResponse, InstantiateMsg, LiquidityResponse,
+    LoanTerms, MigrateMsg, ProtocolFeesResponse, QueryMsg, Rejection, SimulateFlashLoanResponse,
+    SudoMsg,
+};
+use crate::state::{
+    Config, FeeConfig, FlashLoanState, ALLOWED_BORROWERS, CONFIG, FLASH_LOAN_STATE, LOAN_COUNTER,
+    MIN_LOAN_SIZES, PROTOCOL_FEES, REFERRAL_FEES, REFERRAL_FEES_OWED,
+};
+use crate::treasury;
+use cw_flash_loan_vault::helpers::Contract as CwVaultContract;
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:cw-flash-loan-gateway";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 const REPLY_ON_EXTERNAL_HANDLER_COMPLETED: u64 = 1;
+const REPLY_ON_LOAN_PROVIDED: u64 = 2;
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
     _env: Env,
-    _info: MessageInfo,
+    info: MessageInfo,
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
@@ -31,6 +44,10 @@ pub fn instantiate(
         deps.storage,
         &Config {
             cw_vault_contract_addr,
+            owner: info.sender,
+            fees: FeeConfig::default(),
+            paused: false,
+            restrict_borrowers: false,
         },
     )?;
 
@@ -48,35 +65,145 @@ pub fn execute(
         ExecuteMsg::RequestFlashLoan {
             asset,
             on_funded_msg,
-        } => execute_request_flash_loan(deps, env, info, asset, on_funded_msg),
-        ExecuteMsg::FlashLoanProvided { asset } => execute_on_flash_loan_provided(deps, env, asset),
+            referrer,
+        } => execute_request_flash_loan(deps, env, info, asset, on_funded_msg, referrer),
+        ExecuteMsg::FlashLoanProvided { asset } => {
+            execute_on_flash_loan_provided(deps, env, info, asset)
+        }
+        ExecuteMsg::ClaimReferralFees {} => execute_claim_referral_fees(deps, info),
+        ExecuteMsg::WithdrawFees {
+            denom,
+            amount,
+            recipient,
+        } => {
+            ensure_owner(deps.as_ref(), &info)?;
+            withdraw_fees(deps, coin(amount.u128(), denom), recipient)
+        }
+        ExecuteMsg::UpdateFees { fees } => {
+            ensure_owner(deps.as_ref(), &info)?;
+            update_fees(deps, fees)
+        }
+        ExecuteMsg::UpdateMinLoanSizes { min_loan_sizes } => {
+            ensure_owner(deps.as_ref(), &info)?;
+            update_min_loan_sizes(deps, min_loan_sizes)
+        }
+        ExecuteMsg::ClearStaleLoans {} => {
+            ensure_owner(deps.as_ref(), &info)?;
+            clear_stale_loans(deps, env)
+        }
+        ExecuteMsg::Pause {} => {
+            ensure_owner(deps.as_ref(), &info)?;
+            update_paused(deps, true)
+        }
+        ExecuteMsg::Unpause {} => {
+            ensure_owner(deps.as_ref(), &info)?;
+            update_paused(deps, false)
+        }
+        ExecuteMsg::UpdateAllowedBorrowers {
+            add,
+            remove,
+            restrict,
+        } => {
+            ensure_owner(deps.as_ref(), &info)?;
+            update_allowed_borrowers(deps, add, remove, restrict)
+        }
+        ExecuteMsg::UpdateVault { vault } => {
+            ensure_owner(deps.as_ref(), &info)?;
+            update_vault(deps, env, vault)
+        }
+    }
+}
+
+/// Lets governance do whatever the owner can
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn sudo(deps: DepsMut, env: Env, msg: SudoMsg) -> Result<Response, ContractError> {
+    match msg {
+        SudoMsg::WithdrawFees {
+            denom,
+            amount,
+            recipient,
+        } => withdraw_fees(deps, coin(amount.u128(), denom), recipient),
+        SudoMsg::UpdateFees { fees } => update_fees(deps, fees),
+        SudoMsg::UpdateMinLoanSizes { min_loan_sizes } => {
+            update_min_loan_sizes(deps, min_loan_sizes)
+        }
+        SudoMsg::ClearStaleLoans {} => clear_stale_loans(deps, env),
+        SudoMsg::Pause {} => update_paused(deps, true),
+        SudoMsg::Unpause {} => update_paused(deps, false),
+        SudoMsg::UpdateAllowedBorrowers {
+            add,
+            remove,
+            restrict,
+        } => update_allowed_borrowers(deps, add, remove, restrict),
+        SudoMsg::UpdateVault { vault } => update_vault(deps, env, vault),
+    }
+}
+
+fn ensure_owner(deps: Deps, info: &MessageInfo) -> Result<(), ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    if info.sender != config.owner {
+        return Err(ContractError::Unauthorized {});
     }
+
+    Ok(())
 }
 
 fn execute_request_flash_loan(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     asset: Coin,
     on_funded_msg: Binary,
+    referrer: Option<String>,
 ) -> Result<Response, ContractError> {
-    let Config {
-        cw_vault_contract_addr,
-    } = CONFIG.load(deps.storage)?;
-
     println!(
         "[Gateway: execute_request_flash_loan]: asking vault to lend {:?}",
         &asset
     );
 
+    let referrer = referrer
+        .map(|referrer| deps.api.addr_validate(&referrer))
+        .transpose()?;
+
+    ensure_loan_allowed(deps.as_ref(), &env, &info.sender, referrer.as_ref())?;
+
+    let loan_id = next_loan_id(deps.as_ref())?;
+    LOAN_COUNTER.save(deps.storage, &loan_id)?;
+
+    let LoanTerms {
+        vault,
+        vault_fee,
+        gateway_fee,
+        ..
+    } = loan_terms(deps.as_ref(), &env, loan_id, &info.sender, &asset)?;
+
     let flash_loan_state = FlashLoanState {
+        loan_id,
         borrower_contract_addr: info.sender,
         borrower_requested_asset: asset.clone(),
+        vault_fee,
+        gateway_fee,
+        referrer,
         on_funded_msg,
+        opened_at: env.block.height,
     };
 
     FLASH_LOAN_STATE.save(deps.storage, &flash_loan_state)?;
 
+    // funds sent to the gateway ahead of the loan are not a repayment of it
+    let balance = deps
+        .querier
+        .query_balance(env.contract.address, asset.denom.as_str())?;
+    let surplus = treasury::book_surplus(deps.storage, &balance)?;
+
+    if !surplus.amount.is_zero() {
+        println!(
+            "[Gateway: execute_request_flash_loan]: booked {:?} sent beforehand as fees",
+            &surplus
+        );
+    }
+
     // firstly, request funds from vault
     let provide_asset_msg = cw_flash_loan_vault::msg::ExecuteMsg::ProvideAsset {
         asset,
@@ -84,24 +211,291 @@ fn execute_request_flash_loan(
     };
 
     let msg = WasmMsg::Execute {
-        contract_addr: cw_vault_contract_addr.to_string(),
+        contract_addr: vault.to_string(),
         funds: vec![],
-        msg: to_binary(&provide_asset_msg)?,
+        msg: to_json_binary(&provide_asset_msg)?,
     };
 
-    // We'll expect the response being provided by the vault by calling FlashLoanProvided msg
+    // We'll expect the response being provided by the vault by calling FlashLoanProvided msg,
+    // the loan's outcome then comes back as the data of the vault's response
+    let submsg = SubMsg::reply_on_success(msg, REPLY_ON_LOAN_PROVIDED);
 
-    Ok(Response::new().add_message(msg).add_attributes(vec![
+    Ok(Response::new().add_submessage(submsg).add_attributes(vec![
         ("module", "gateway"),
         ("action", "execute_request_flash_loan"),
     ]))
 }
 
+fn update_fees(deps: DepsMut, fees: FeeConfig) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+
+    fees.validate()?;
+
+    config.fees = fees;
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new().add_attributes(vec![("module", "gateway"), ("action", "update_fees")]))
+}
+
+fn update_min_loan_sizes(
+    deps: DepsMut,
+    min_loan_sizes: Vec<Coin>,
+) -> Result<Response, ContractError> {
+    for min in min_loan_sizes {
+        MIN_LOAN_SIZES.save(deps.storage, min.denom.as_str(), &min.amount)?;
+    }
+
+    Ok(Response::new().add_attributes(vec![
+        ("module", "gateway"),
+        ("action", "update_min_loan_sizes"),
+    ]))
+}
+
+fn update_paused(deps: DepsMut, paused: bool) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+
+    config.paused = paused;
+    CONFIG.save(deps.storage, &config)?;
+
+    let action = if paused { "pause" } else { "unpause" };
+
+    Ok(Response::new().add_attributes(vec![("module", "gateway"), ("action", action)]))
+}
+
+fn update_allowed_borrowers(
+    deps: DepsMut,
+    add: Vec<String>,
+    remove: Vec<String>,
+    restrict: Option<bool>,
+) -> Result<Response, ContractError> {
+    for borrower in &add {
+        let borrower = deps.api.addr_validate(borrower)?;
+        ALLOWED_BORROWERS.save(deps.storage, &borrower, &Empty {})?;
+    }
+
+    for borrower in &remove {
+        let borrower = deps.api.addr_validate(borrower)?;
+        ALLOWED_BORROWERS.remove(deps.storage, &borrower);
+    }
+
+    if let Some(restrict) = restrict {
+        let mut config = CONFIG.load(deps.storage)?;
+        config.restrict_borrowers = restrict;
+        CONFIG.save(deps.storage, &config)?;
+    }
+
+    Ok(Response::new().add_attributes(vec![
+        ("module", "gateway"),
+        ("action", "update_allowed_borrowers"),
+    ]))
+}
+
+fn update_vault(deps: DepsMut, env: Env, vault: String) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+
+    // the vault lending the current loan is the one expecting it back
+    if active_loan(deps.as_ref(), &env)?.is_some() {
+        return Err(ContractError::LoanInProgress {});
+    }
+
+    config.cw_vault_contract_addr = deps.api.addr_validate(&vault)?;
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("module", "gateway"),
+        ("action", "update_vault"),
+        ("vault", vault.as_str()),
+    ]))
+}
+
+fn clear_stale_loans(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    let mut response = Response::new()
+        .add_attributes(vec![("module", "gateway"), ("action", "clear_stale_loans")]);
+
+    if let Some(stale) = FLASH_LOAN_STATE.may_load(deps.storage)? {
+        if stale.opened_at < env.block.height {
+            println!(
+                "[Gateway: clear_stale_loans]: clearing loan #{} opened at block {}",
+                stale.loan_id, stale.opened_at
+            );
+
+            FLASH_LOAN_STATE.remove(deps.storage);
+            response = response.add_attribute("cleared_loan_id", stale.loan_id.to_string());
+        }
+    }
+
+    Ok(response)
+}
+
+fn execute_claim_referral_fees(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let fees = treasury::take_referral_fees(deps.storage, &info.sender)?;
+
+    if fees.is_empty() {
+        return Err(ContractError::NothingToClaim {});
+    }
+
+    println!(
+        "[Gateway: execute_claim_referral_fees]: paying {:?} to {}",
+        &fees, &info.sender
+    );
+
+    let msg = BankMsg::Send {
+        to_address: info.sender.to_string(),
+        amount: fees,
+    };
+
+    Ok(Response::new().add_message(msg).add_attributes(vec![
+        ("module", "gateway"),
+        ("action", "execute_claim_referral_fees"),
+    ]))
+}
+
+fn withdraw_fees(
+    deps: DepsMut,
+    amount: Coin,
+    recipient: String,
+) -> Result<Response, ContractError> {
+    let recipient = deps.api.addr_validate(&recipient)?;
+
+    treasury::take_protocol_fees(deps.storage, &amount)?;
+
+    println!(
+        "[Gateway: withdraw_fees]: sending {:?} to {}",
+        &amount, &recipient
+    );
+
+    let msg = BankMsg::Send {
+        to_address: recipient.to_string(),
+        amount: vec![amount],
+    };
+
+    Ok(Response::new()
+        .add_message(msg)
+        .add_attributes(vec![("module", "gateway"), ("action", "withdraw_fees")]))
+}
+
+/// Loan in progress, state left behind in an earlier block doesn't count
+fn active_loan(deps: Deps, env: &Env) -> StdResult<Option<FlashLoanState>> {
+    Ok(FLASH_LOAN_STATE
+        .may_load(deps.storage)?
+        .filter(|loan| loan.opened_at >= env.block.height))
+}
+
+fn next_loan_id(deps: Deps) -> StdResult<u64> {
+    Ok(LOAN_COUNTER.may_load(deps.storage)?.unwrap_or_default() + 1)
+}
+
+/// Smallest loan allowed in `denom`, never below 1 so nobody borrows nothing
+fn min_loan_size(deps: Deps, denom: &str) -> StdResult<Coin> {
+    let min = MIN_LOAN_SIZES
+        .may_load(deps.storage, denom)?
+        .unwrap_or_default();
+
+    Ok(coin(min.max(Uint128::one()).u128(), denom))
+}
+
+/// Checks whether `asset` can be lent to `borrower` and on which terms.
+/// Shared by loan requests and their simulation, so both always agree.
+/// Checks on the request itself, before any of its assets
+fn ensure_loan_allowed(
+    deps: Deps,
+    env: &Env,
+    borrower: &Addr,
+    referrer: Option<&Addr>,
+) -> Result<(), ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    if config.paused {
+        return Err(ContractError::Paused {});
+    }
+
+    if config.restrict_borrowers && !ALLOWED_BORROWERS.has(deps.storage, borrower) {
+        return Err(ContractError::BorrowerNotAllowed {
+            borrower: borrower.to_string(),
+        });
+    }
+
+    if referrer == Some(borrower) {
+        return Err(ContractError::SelfReferral {});
+    }
+
+    // one loan at a time, a borrower re-entering from its hook would overwrite the loan's state
+    if active_loan(deps, env)?.is_some() {
+        return Err(ContractError::LoanInProgress {});
+    }
+
+    Ok(())
+}
+
+fn loan_terms(
+    deps: Deps,
+    env: &Env,
+    loan_id: u64,
+    borrower: &Addr,
+    asset: &Coin,
+) -> Result<LoanTerms, ContractError> {
+    let Config {
+        cw_vault_contract_addr,
+        fees,
+        ..
+    } = CONFIG.load(deps.storage)?;
+
+    // dust loans would cost next to nothing while still taking up the vault
+    let min = min_loan_size(deps, asset.denom.as_str())?;
+    if asset.amount < min.amount {
+        return Err(ContractError::LoanTooSmall { min });
+    }
+
+    // the vault's fee depends on how much of its liquidity the loan takes,
+    // loans it cannot cover are refused right away rather than somewhere down the line
+    let quote = CwVaultContract(cw_vault_contract_addr.clone())
+        .fee_quote(&deps.querier, asset.clone())
+        .map_err(|err| ContractError::VaultRefused {
+            loan_id,
+            reason: err.to_string(),
+        })?;
+
+    // high volume borrowers get a discount on the gateway's part
+    let volume = borrower_volume(
+        deps.storage,
+        borrower,
+        &asset.denom,
+        env.block.time,
+        fees.volume_window,
+    )?;
+    let gateway_fee = fees.fee(asset, volume);
+
+    let (total_repayment, _, _) = calculate_debt_remaining(
+        asset.clone(),
+        quote.fee.clone(),
+        gateway_fee.clone(),
+        Decimal::zero(),
+    )?;
+
+    Ok(LoanTerms {
+        asset: asset.clone(),
+        vault: cw_vault_contract_addr,
+        gateway_fee,
+        vault_fee: quote.fee,
+        total_repayment,
+    })
+}
+
 fn execute_on_flash_loan_provided(
     deps: DepsMut,
     _env: Env,
+    info: MessageInfo,
     asset: Coin,
 ) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    if info.sender != config.cw_vault_contract_addr {
+        return Err(ContractError::Unauthorized {});
+    }
+
     println!(
         "[Gateway: execute_on_flash_loan_provided]: vault has granted the loan {:?}",
         &asset
@@ -115,7 +509,8 @@ fn execute_on_flash_loan_provided(
     
     let submsg =
     // secondly, send funds from gateway to the borrower
-    SubMsg::reply_on_success(
+    // replying on errors as well, so a failing borrower gets reported as such
+    SubMsg::reply_always(
         WasmMsg::Execute {
             contract_addr: flash_loan_state.borrower_contract_addr.to_string(),
             funds: vec![],
@@ -132,32 +527,50 @@ fn execute_on_flash_loan_provided(
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
-    if msg.result.is_err() {
-        return Err(ContractError::Std(StdError::GenericErr {
-            msg: msg.result.unwrap_err(),
-        }));
-    }
-
     match msg.id {
-        REPLY_ON_EXTERNAL_HANDLER_COMPLETED => reply_on_external_handler_completed(deps, env),
+        REPLY_ON_EXTERNAL_HANDLER_COMPLETED => {
+            reply_on_external_handler_completed(deps, env, msg.result)
+        }
+        REPLY_ON_LOAN_PROVIDED => reply_on_loan_provided(msg.result),
         _ => Err(ContractError::Std(StdError::GenericErr {
             msg: format!("reply id `{:?}` is invalid", msg.id),
         })),
     }
 }
-fn reply_on_external_handler_completed(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+fn reply_on_external_handler_completed(
+    deps: DepsMut,
+    env: Env,
+    result: SubMsgResult,
+) -> Result<Response, ContractError> {
     let config = CONFIG.load(deps.storage)?;
     let flash_loan_state = FLASH_LOAN_STATE.load(deps.storage)?;
 
+    let hook_data = match result {
+        SubMsgResult::Ok(response) => execute_response_data(response.data)?,
+        SubMsgResult::Err(reason) => {
+            return Err(ContractError::BorrowerHookFailed {
+                loan_id: flash_loan_state.loan_id,
+                reason,
+            })
+        }
+    };
+
     let repayment_amount_base = flash_loan_state.borrower_requested_asset;
     println!(
         "[Gateway: repay the loan to the vault]: base = {:?}",
         &repayment_amount_base
     );
 
-    let (repayment_amount_total, repayment_amount_vault) = calculate_debt_remaining(
+    let referral_share = match flash_loan_state.referrer {
+        Some(_) => config.fees.referral_share,
+        None => Decimal::zero(),
+    };
+
+    let (repayment_amount_total, repayment_amount_vault, referral_fee) = calculate_debt_remaining(
         repayment_amount_base.clone(),
-        config.cw_vault_contract_addr.clone(),
+        flash_loan_state.vault_fee.clone(),
+        flash_loan_state.gateway_fee.clone(),
+        referral_share,
     )?;
 
     println!(
@@ -176,10 +589,29 @@ fn reply_on_external_handler_completed(deps: DepsMut, env: Env) -> Result<Respon
         repayment_amount_base.denom.as_str(),
     )?;
 
-    if gateway_current_balance.amount < repayment_amount_total.amount {
-        return Err(ContractError::NotEnoughFundsToCoverFee);
+    // fees held by the gateway are not the borrower's to repay with
+    let reserved = treasury::reserved(deps.storage, repayment_amount_base.denom.as_str())?;
+    let repaid = coin(
+        gateway_current_balance.amount.saturating_sub(reserved).u128(),
+        repayment_amount_base.denom.as_str(),
+    );
+
+    if repaid.amount < repayment_amount_total.amount {
+        return Err(ContractError::RepaymentShortfall {
+            loan_id: flash_loan_state.loan_id,
+            required: repayment_amount_total,
+            available: repaid,
+        });
     }
 
+    // whatever the borrower paid on top of the vault's part is fees, overpayments included,
+    // so the ledger keeps matching the balance
+    let fees_paid = repaid.amount - repayment_amount_vault.amount;
+    let protocol_fee = coin(
+        (fees_paid - referral_fee.amount).u128(),
+        repayment_amount_base.denom.as_str(),
+    );
+
     let msgs = vec![SubMsg::new(BankMsg::Send {
         to_address: config.cw_vault_contract_addr.into(),
         // amount: vec![repayment_amount],
@@ -187,55 +619,242 @@ fn reply_on_external_handler_completed(deps: DepsMut, env: Env) -> Result<Respon
         amount: vec![repayment_amount_vault],
     })];
 
+    // the referrer's share stays with the gateway until claimed
+    if let Some(referrer) = &flash_loan_state.referrer {
+        treasury::accrue_referral_fee(deps.storage, referrer, &referral_fee)?;
+    }
+    treasury::accrue_protocol_fee(deps.storage, &protocol_fee)?;
+
+    record_volume(
+        deps.storage,
+        &flash_loan_state.borrower_contract_addr,
+        &repayment_amount_base,
+        env.block.time,
+        config.fees.volume_window,
+    )?;
+
     FLASH_LOAN_STATE.remove(deps.storage);
 
-    Ok(Response::new().add_submessages(msgs).add_attributes(vec![
+    // makes its way back to the borrower through the vault, as the data of its request
+    let data = FlashLoanResponse {
+        loan_id: flash_loan_state.loan_id,
+        vault_fee: flash_loan_state.vault_fee,
+        gateway_fee: flash_loan_state.gateway_fee,
+        hook_data,
+    };
+
+    Ok(Response::new()
+        .add_submessages(msgs)
+        .set_data(to_json_binary(&data)?)
+        .add_attributes(vec![
+            ("module", "gateway"),
+            ("action", "reply_on_external_handler_completed"),
+        ]))
+}
+
+/// Passes the loan's outcome on to the borrower, as the data of its request
+fn reply_on_loan_provided(result: SubMsgResult) -> Result<Response, ContractError> {
+    let mut response = Response::new().add_attributes(vec![
         ("module", "gateway"),
-        ("action", "reply_on_external_handler_completed"),
-    ]))
+        ("action", "reply_on_loan_provided"),
+    ]);
+
+    if let SubMsgResult::Ok(vault_response) = result {
+        if let Some(data) = execute_response_data(vault_response.data)? {
+            response = response.set_data(data);
+        }
+    }
+
+    Ok(response)
+}
+
+/// Data a contract executed as a submessage responded with, out of its `MsgExecuteContractResponse`
+fn execute_response_data(data: Option<Binary>) -> Result<Option<Binary>, ContractError> {
+    match data {
+        Some(data) => Ok(parse_execute_response_data(&data)
+            .map_err(|err| StdError::generic_err(err.to_string()))?
+            .data),
+        None => Ok(None),
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::new().add_attributes(vec![("module", "gateway"), ("action", "migrate")]))
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    Ok(to_binary(&match msg {
-        QueryMsg::DebtRemaining { borrower } => query_debt_remaining(deps, borrower)?,
-    })?)
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::DebtRemaining { borrower } => {
+            to_json_binary(&query_debt_remaining(deps, env, borrower)?)
+        }
+        QueryMsg::SimulateFlashLoan {
+            assets,
+            borrower,
+            referrer,
+        } => to_json_binary(&query_simulate_flash_loan(
+            deps, env, assets, borrower, referrer,
+        )?),
+        QueryMsg::BorrowerTier { borrower, denom } => {
+            to_json_binary(&query_borrower_tier(deps, env, borrower, denom)?)
+        }
+        QueryMsg::ReferralFees { referrer } => {
+            to_json_binary(&query_referral_fees(deps, referrer)?)
+        }
+        QueryMsg::ProtocolFees {} => to_json_binary(&query_protocol_fees(deps)?),
+        QueryMsg::MinLoanSize { denom } => to_json_binary(&min_loan_size(deps, &denom)?),
+        QueryMsg::Liquidity {} => to_json_binary(&query_liquidity(deps)?),
+    }
+}
+
+fn query_simulate_flash_loan(
+    deps: Deps,
+    env: Env,
+    assets: Vec<Coin>,
+    borrower: String,
+    referrer: Option<String>,
+) -> StdResult<SimulateFlashLoanResponse> {
+    let borrower = deps.api.addr_validate(&borrower)?;
+    let referrer = referrer
+        .map(|referrer| deps.api.addr_validate(&referrer))
+        .transpose()?;
+    let loan_id = next_loan_id(deps)?;
+
+    // the same checks as the request, in the same order
+    let mut rejection = if assets.len() != 1 {
+        Some(Rejection::SingleAssetOnly {})
+    } else {
+        ensure_loan_allowed(deps, &env, &borrower, referrer.as_ref())
+            .err()
+            .map(Rejection::from)
+    };
+
+    let mut loans = vec![];
+    for asset in &assets {
+        match loan_terms(deps, &env, loan_id, &borrower, asset) {
+            Ok(terms) => loans.push(terms),
+            Err(err) => {
+                rejection.get_or_insert(err.into());
+            }
+        }
+    }
+
+    let total_repayment = loans
+        .iter()
+        .map(|terms| terms.total_repayment.clone())
+        .collect();
+
+    Ok(SimulateFlashLoanResponse {
+        loans,
+        total_repayment,
+        rejection,
+    })
 }
 
-fn query_debt_remaining(deps: Deps, borrower: Addr) -> StdResult<(Coin, Coin)> {
+fn query_borrower_tier(
+    deps: Deps,
+    env: Env,
+    borrower: String,
+    denom: Option<String>,
+) -> StdResult<Vec<BorrowerTierResponse>> {
     let config = CONFIG.load(deps.storage)?;
-    let flash_loan_state = FLASH_LOAN_STATE.load(deps.storage)?;
+    let borrower = deps.api.addr_validate(&borrower)?;
+
+    let denoms = match denom {
+        Some(denom) => vec![denom],
+        None => borrower_denoms(deps.storage, &borrower, &config.fees, env.block.time)?,
+    };
+
+    denoms
+        .into_iter()
+        .map(|denom| {
+            let volume = borrower_volume(
+                deps.storage,
+                &borrower,
+                &denom,
+                env.block.time,
+                config.fees.volume_window,
+            )?;
+
+            Ok(BorrowerTierResponse {
+                tier: config.fees.tier(&denom, volume).cloned(),
+                fee_rate: config.fees.rate(&denom, volume),
+                denom,
+                volume,
+            })
+        })
+        .collect()
+}
+
+fn query_referral_fees(deps: Deps, referrer: String) -> StdResult<Vec<Coin>> {
+    let referrer = deps.api.addr_validate(&referrer)?;
+
+    REFERRAL_FEES
+        .prefix(&referrer)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(denom, amount)| coin(amount.u128(), denom)))
+        .collect()
+}
+
+fn query_protocol_fees(deps: Deps) -> StdResult<ProtocolFeesResponse> {
+    Ok(ProtocolFeesResponse {
+        accrued: treasury::ledger_coins(deps.storage, &PROTOCOL_FEES)?,
+        referral_fees_owed: treasury::ledger_coins(deps.storage, &REFERRAL_FEES_OWED)?,
+    })
+}
+
+/// Only the vault's own liquidity can be lent, what it borrowed remotely is part of it
+fn query_liquidity(deps: Deps) -> StdResult<LiquidityResponse> {
+    let vault = CwVaultContract(CONFIG.load(deps.storage)?.cw_vault_contract_addr);
+    let denom = vault.config(&deps.querier)?.denom;
+    let assets = vault.total_assets(&deps.querier)?;
+
+    Ok(LiquidityResponse {
+        vault: vault.addr(),
+        available: coin(assets.liquid.u128(), denom.as_str()),
+        lent_remotely: coin(assets.lent_remotely.u128(), denom.as_str()),
+        borrowed_remotely: coin(assets.borrowed_remotely.u128(), denom.as_str()),
+        receiving_remotely: coin(assets.receiving_remotely.u128(), denom.as_str()),
+        remote_vaults: vault.remote_vaults(&deps.querier)?.vaults,
+    })
+}
+
+fn query_debt_remaining(deps: Deps, env: Env, borrower: Addr) -> StdResult<(Coin, Coin)> {
+    let flash_loan_state = active_loan(deps, &env)?
+        .ok_or_else(|| StdError::generic_err("No flash loan in progress"))?;
 
     if flash_loan_state.borrower_contract_addr != borrower {
         return Err(StdError::generic_err("Cannot retrieve debt remaining"));
     }
 
-    Ok(calculate_debt_remaining(
+    let (total, vault, _) = calculate_debt_remaining(
         flash_loan_state.borrower_requested_asset,
-        config.cw_vault_contract_addr,
-    )?)
+        flash_loan_state.vault_fee,
+        flash_loan_state.gateway_fee,
+        Decimal::zero(),
+    )?;
+
+    Ok((total, vault))
 }
 
+/// Total repayment, the vault's part of it and the referrer's share of the gateway fee,
+/// given the fees set when the loan was requested
 fn calculate_debt_remaining(
     repayment_amount_base: Coin,
-    _vault_addr: Addr,
-) -> StdResult<(Coin, Coin)> {
-    let repayment_amount_base_gatway_fee = repayment_amount_base
-        .amount
-        // TODO: load config param to check current gateway
-        .checked_div(Uint128::from(100u128))?;
-
-    let repayment_amount_base_vault_fee = repayment_amount_base
-        .amount
-        // TODO: query vault to get its current fee
-        .checked_div(Uint128::from(50u128))?;
-
+    vault_fee: Coin,
+    gateway_fee: Coin,
+    referral_share: Decimal,
+) -> StdResult<(Coin, Coin, Coin)> {
     let repayment_amount_for_vault = repayment_amount_base
         .amount
-        .checked_add(repayment_amount_base_vault_fee.clone())?;
+        .checked_add(vault_fee.amount)?;
+
+    let repayment_total_value = repayment_amount_for_vault.checked_add(gateway_fee.amount)?;
 
-    let repayment_total_value =
-        repayment_amount_for_vault.checked_add(repayment_amount_base_gatway_fee)?;
+    let referral_fee = gateway_fee.amount * referral_share;
 
     Ok((
         coin(
@@ -246,5 +865,6 @@ fn calculate_debt_remaining(
             repayment_amount_for_vault.u128(),
             repayment_amount_base.denom.as_str(),
         ),
+        coin(referral_fee.u128(), repayment_amount_base.denom.as_str()),
     ))
 }
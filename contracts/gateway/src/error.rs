@@ -1,6 +1,8 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{Coin, StdError};
 use thiserror::Error;
 
+use crate::msg::Rejection;
+
 #[derive(Error, Debug)]
 pub enum ContractError {
     #[error("{0}")]
@@ -12,8 +14,21 @@ pub enum ContractError {
     #[error("Custom Error val: {val:?}")]
     CustomError{val: String},
 
-    #[error("Not enough funds to cover fee")]
-    NotEnoughFundsToCoverFee,
+    #[error("A flash loan covers exactly one asset")]
+    SingleAssetOnly {},
+
+    #[error("Flash loan #{loan_id}: vault refused to lend: {reason}")]
+    VaultRefused { loan_id: u64, reason: String },
+
+    #[error("Flash loan #{loan_id}: borrower hook failed: {reason}")]
+    BorrowerHookFailed { loan_id: u64, reason: String },
+
+    #[error("Flash loan #{loan_id}: repayment shortfall, {required} required but only {available} available")]
+    RepaymentShortfall {
+        loan_id: u64,
+        required: Coin,
+        available: Coin,
+    },
 
     #[error("Not enough funds to cover loan repayment")]
     NotEnoughFundsToCoverLoanRepayment,
@@ -21,6 +36,47 @@ pub enum ContractError {
     #[error("Requested asset was not provided by vault")]
     RequestedAssetNotProvided,
 
+    #[error("Fee rate, tier discounts and referral share cannot exceed 100%")]
+    InvalidFees {},
+
+    #[error("Borrowers cannot refer themselves")]
+    SelfReferral {},
+
+    #[error("No referral fees to claim")]
+    NothingToClaim {},
+
+    #[error("Only {available} of protocol fees accrued")]
+    InsufficientProtocolFees { available: Coin },
+
+    #[error("A flash loan is in progress")]
+    LoanInProgress {},
+
+    #[error("Loans must borrow at least {min}")]
+    LoanTooSmall { min: Coin },
+
+    #[error("Flash loans are paused")]
+    Paused {},
+
+    #[error("Borrower {borrower} is not allowed to borrow")]
+    BorrowerNotAllowed { borrower: String },
+
     // Add any other custom errors you like here.
     // Look at https://docs.rs/thiserror/1.0.21/thiserror/ for details.
 }
+
+impl From<ContractError> for Rejection {
+    fn from(err: ContractError) -> Self {
+        match err {
+            ContractError::Paused {} => Rejection::Paused {},
+            ContractError::BorrowerNotAllowed { .. } => Rejection::BorrowerNotAllowed {},
+            ContractError::SingleAssetOnly {} => Rejection::SingleAssetOnly {},
+            ContractError::SelfReferral {} => Rejection::SelfReferral {},
+            ContractError::LoanInProgress {} => Rejection::LoanInProgress {},
+            ContractError::LoanTooSmall { min } => Rejection::LoanTooSmall { min },
+            ContractError::VaultRefused { reason, .. } => Rejection::VaultRefused { reason },
+            err => Rejection::Other {
+                reason: err.to_string(),
+            },
+        }
+    }
+}
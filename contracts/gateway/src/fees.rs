@@ -0,0 +1,329 @@
+//! Gateway fees, discounted for borrowers with a high volume of loans.
+//!
+//! A borrower's volume is the sum of what it repaid in a denom over the rolling window.
+//! Repayments are added up in buckets, a fraction of the window long, which are dropped
+//! once they leave it, so the volume costs the same to look up however many loans it took.
+
+use cosmwasm_std::{coin, Addr, Coin, Decimal, Order, StdResult, Storage, Timestamp, Uint128};
+use cw_storage_plus::Bound;
+
+use crate::error::ContractError;
+use crate::state::{FeeConfig, FeeTier, BORROWER_VOLUME};
+
+/// Buckets the window is split into. Volume leaves the window along with its bucket,
+/// so up to a bucket's length early.
+const VOLUME_BUCKETS: u64 = 30;
+
+impl Default for FeeConfig {
+    /// 1% fee, no tiers, over 30 days, a tenth of which goes to referrers
+    fn default() -> Self {
+        FeeConfig {
+            rate: Decimal::percent(1),
+            tiers: vec![],
+            volume_window: 30 * 24 * 60 * 60,
+            referral_share: Decimal::percent(10),
+        }
+    }
+}
+
+impl FeeConfig {
+    pub fn validate(&self) -> Result<(), ContractError> {
+        if self.rate > Decimal::one()
+            || self.referral_share > Decimal::one()
+            || self.tiers.iter().any(|tier| tier.discount > Decimal::one())
+        {
+            return Err(ContractError::InvalidFees {});
+        }
+
+        Ok(())
+    }
+
+    /// Best tier of `denom` reached with `volume`, if any
+    pub fn tier(&self, denom: &str, volume: Uint128) -> Option<&FeeTier> {
+        self.tiers
+            .iter()
+            .filter(|tier| tier.denom == denom && tier.min_volume <= volume)
+            .max_by_key(|tier| tier.min_volume)
+    }
+
+    /// Gateway fee rate on `denom` for a borrower with `volume` of it
+    pub fn rate(&self, denom: &str, volume: Uint128) -> Decimal {
+        match self.tier(denom, volume) {
+            Some(tier) => self.rate * (Decimal::one() - tier.discount),
+            None => self.rate,
+        }
+    }
+
+    /// Gateway fee on `asset` for a borrower with `volume` of its denom, rounded up like the vault's.
+    /// Rates are capped at 100%, so it never exceeds the loan itself.
+    pub fn fee(&self, asset: &Coin, volume: Uint128) -> Coin {
+        coin(
+            asset
+                .amount
+                .mul_ceil(self.rate(&asset.denom, volume))
+                .u128(),
+            asset.denom.as_str(),
+        )
+    }
+}
+
+fn bucket_start(now: Timestamp, window: u64) -> u64 {
+    let length = (window / VOLUME_BUCKETS).max(1);
+    now.seconds() - now.seconds() % length
+}
+
+/// Adds `repaid` to the volume of `borrower`, dropping its buckets of the denom which left the window
+pub fn record_volume(
+    storage: &mut dyn Storage,
+    borrower: &Addr,
+    repaid: &Coin,
+    now: Timestamp,
+    window: u64,
+) -> StdResult<()> {
+    let since = now.seconds().saturating_sub(window);
+    let expired = BORROWER_VOLUME
+        .prefix((borrower, repaid.denom.as_str()))
+        .keys(
+            storage,
+            None,
+            Some(Bound::inclusive(since)),
+            Order::Ascending,
+        )
+        .collect::<StdResult<Vec<_>>>()?;
+    for start in expired {
+        BORROWER_VOLUME.remove(storage, (borrower, repaid.denom.as_str(), start));
+    }
+
+    BORROWER_VOLUME.update(
+        storage,
+        (borrower, repaid.denom.as_str(), bucket_start(now, window)),
+        |volume| -> StdResult<_> { Ok(volume.unwrap_or_default() + repaid.amount) },
+    )?;
+
+    Ok(())
+}
+
+/// Sum of the loans of `denom` which `borrower` repaid within `window` seconds before `now`
+pub fn borrower_volume(
+    storage: &dyn Storage,
+    borrower: &Addr,
+    denom: &str,
+    now: Timestamp,
+    window: u64,
+) -> StdResult<Uint128> {
+    let since = now.seconds().saturating_sub(window);
+
+    BORROWER_VOLUME
+        .prefix((borrower, denom))
+        .range(
+            storage,
+            Some(Bound::exclusive(since)),
+            None,
+            Order::Ascending,
+        )
+        .map(|item| item.map(|(_, volume)| volume))
+        .sum()
+}
+
+/// Denoms the fees of `borrower` can differ in: those with tiers, and those it has
+/// volume of within the window before `now`
+pub fn borrower_denoms(
+    storage: &dyn Storage,
+    borrower: &Addr,
+    fees: &FeeConfig,
+    now: Timestamp,
+) -> StdResult<Vec<String>> {
+    let since = now.seconds().saturating_sub(fees.volume_window);
+
+    let mut denoms: Vec<String> = fees.tiers.iter().map(|tier| tier.denom.clone()).collect();
+    for key in BORROWER_VOLUME
+        .sub_prefix(borrower)
+        .keys(storage, None, None, Order::Ascending)
+    {
+        let (denom, start) = key?;
+        if start > since {
+            denoms.push(denom);
+        }
+    }
+    denoms.sort();
+    denoms.dedup();
+
+    Ok(denoms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::testing::MockStorage;
+    use cosmwasm_std::Uint256;
+    use proptest::prelude::*;
+
+    fn fee_config() -> FeeConfig {
+        FeeConfig {
+            rate: Decimal::percent(1),
+            tiers: vec![
+                FeeTier {
+                    denom: "uluna".to_string(),
+                    min_volume: Uint128::new(5_000),
+                    discount: Decimal::one(),
+                },
+                FeeTier {
+                    denom: "uluna".to_string(),
+                    min_volume: Uint128::new(2_000),
+                    discount: Decimal::percent(50),
+                },
+            ],
+            volume_window: 3_600,
+            referral_share: Decimal::zero(),
+        }
+    }
+
+    #[test]
+    fn volume_unlocks_discounts() {
+        let fees = fee_config();
+
+        assert_eq!(fees.rate("uluna", Uint128::new(1_999)), Decimal::percent(1));
+        assert_eq!(
+            fees.rate("uluna", Uint128::new(2_000)),
+            Decimal::permille(5)
+        );
+        assert_eq!(fees.rate("uluna", Uint128::new(10_000)), Decimal::zero());
+        // the tiers only apply to the denom they were set for
+        assert_eq!(fees.rate("uusd", Uint128::new(10_000)), Decimal::percent(1));
+    }
+
+    #[test]
+    fn volume_is_counted_over_the_window() {
+        let mut storage = MockStorage::new();
+        let borrower = Addr::unchecked("borrower");
+
+        for repaid_at in [1_000u64, 4_000, 5_000] {
+            record_volume(
+                &mut storage,
+                &borrower,
+                &coin(1_000, "uluna"),
+                Timestamp::from_seconds(repaid_at),
+                3_600,
+            )
+            .unwrap();
+        }
+        record_volume(
+            &mut storage,
+            &borrower,
+            &coin(1_000, "uusd"),
+            Timestamp::from_seconds(5_000),
+            3_600,
+        )
+        .unwrap();
+
+        let volume = borrower_volume(
+            &storage,
+            &borrower,
+            "uluna",
+            Timestamp::from_seconds(5_000),
+            3_600,
+        )
+        .unwrap();
+        assert_eq!(volume, Uint128::new(2_000));
+
+        let volume = borrower_volume(
+            &storage,
+            &borrower,
+            "uluna",
+            Timestamp::from_seconds(9_000),
+            3_600,
+        )
+        .unwrap();
+        assert_eq!(volume, Uint128::zero());
+    }
+
+    #[test]
+    fn denoms_with_tiers_or_volume_are_listed() {
+        let mut storage = MockStorage::new();
+        let borrower = Addr::unchecked("borrower");
+        let fees = fee_config();
+
+        for (denom, repaid_at) in [("uusd", 1_000u64), ("ibc/atom", 4_000)] {
+            record_volume(
+                &mut storage,
+                &borrower,
+                &coin(1_000, denom),
+                Timestamp::from_seconds(repaid_at),
+                fees.volume_window,
+            )
+            .unwrap();
+        }
+
+        let denoms =
+            borrower_denoms(&storage, &borrower, &fees, Timestamp::from_seconds(5_000)).unwrap();
+        assert_eq!(denoms, vec!["ibc/atom", "uluna"]);
+    }
+
+    #[test]
+    fn expired_volume_is_pruned() {
+        let mut storage = MockStorage::new();
+        let borrower = Addr::unchecked("borrower");
+
+        for repaid_at in [1_000u64, 1_010, 2_000, 9_000] {
+            record_volume(
+                &mut storage,
+                &borrower,
+                &coin(1_000, "uluna"),
+                Timestamp::from_seconds(repaid_at),
+                3_600,
+            )
+            .unwrap();
+        }
+
+        // only the bucket still within the window is left
+        let buckets = BORROWER_VOLUME
+            .prefix((&borrower, "uluna"))
+            .range(&storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()
+            .unwrap();
+        assert_eq!(buckets, vec![(9_000, Uint128::new(1_000))]);
+    }
+
+    #[test]
+    fn dust_loans_pay_at_least_one_unit() {
+        let fees = fee_config();
+
+        assert_eq!(
+            fees.fee(&coin(1, "uluna"), Uint128::zero()),
+            coin(1, "uluna")
+        );
+        assert_eq!(
+            fees.fee(&coin(101, "uluna"), Uint128::zero()),
+            coin(2, "uluna")
+        );
+        // fully discounted loans are free though
+        assert_eq!(
+            fees.fee(&coin(1, "uluna"), Uint128::new(5_000)),
+            coin(0, "uluna")
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn fee_never_rounds_in_the_borrowers_favour(
+            amount in any::<u128>(),
+            rate in 0..=Decimal::one().atomics().u128(),
+            discount in 0..=Decimal::one().atomics().u128(),
+            volume in 0..10_000u128,
+        ) {
+            let mut fees = fee_config();
+            fees.rate = Decimal::raw(rate);
+            fees.tiers[1].discount = Decimal::raw(discount);
+
+            let volume = Uint128::new(volume);
+            let fee = fees.fee(&coin(amount, "uluna"), volume);
+            let owed = Uint256::from(amount) * Uint256::from(fees.rate("uluna", volume).atomics());
+            let charged = Uint256::from(fee.amount) * Uint256::from(Decimal::one().atomics());
+
+            prop_assert!(fee.amount.u128() <= amount);
+            prop_assert!(charged >= owed);
+            prop_assert!(charged < owed + Uint256::from(Decimal::one().atomics()));
+        }
+    }
+}
@@ -1,9 +1,16 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{to_binary, Addr, BankMsg, Coin, CosmosMsg, StdResult, WasmMsg, QuerierWrapper};
+use cosmwasm_std::{
+    from_json, to_json_binary, Addr, BankMsg, Coin, CosmosMsg, QuerierWrapper, Reply, StdError,
+    StdResult, WasmMsg,
+};
+use cw_utils::parse_execute_response_data;
 
-use crate::msg::{ExecuteMsg, QueryMsg};
+use crate::msg::{
+    BorrowerTierResponse, ExecuteMsg, FlashLoanResponse, LiquidityResponse, ProtocolFeesResponse,
+    QueryMsg, SimulateFlashLoanResponse,
+};
 
 /// CwBridgeContract is a wrapper around Addr that provides a lot of helpers
 /// for working with this.
@@ -20,7 +27,7 @@ impl Contract {
         msg: T,
         funds: Option<Vec<Coin>>,
     ) -> StdResult<CosmosMsg> {
-        let msg = to_binary(&msg.into())?;
+        let msg = to_json_binary(&msg.into())?;
         Ok(WasmMsg::Execute {
             contract_addr: self.addr().into(),
             msg,
@@ -36,7 +43,8 @@ impl Contract {
     ) -> StdResult<CosmosMsg> {
         let request_flash_loan_msg = ExecuteMsg::RequestFlashLoan {
             asset: request_flash_loan_props.asset,
-            on_funded_msg: to_binary(&request_flash_loan_props.on_flash_loan_provided_hook)?,
+            on_funded_msg: to_json_binary(&request_flash_loan_props.on_flash_loan_provided_hook)?,
+            referrer: request_flash_loan_props.referrer,
         };
 
         self.call(request_flash_loan_msg, None)
@@ -60,6 +68,108 @@ impl Contract {
 
         Ok(total_repayment)
     }
+
+    /// Tells whether a flash loan of `assets` requested by `borrower`, referred by `referrer`,
+    /// would go through and what it would cost
+    pub fn simulate_flash_loan(
+        &self,
+        querier: &QuerierWrapper,
+        assets: Vec<Coin>,
+        borrower: &Addr,
+        referrer: Option<&Addr>,
+    ) -> StdResult<SimulateFlashLoanResponse> {
+        querier.query_wasm_smart(
+            self.addr(),
+            &QueryMsg::SimulateFlashLoan {
+                assets,
+                borrower: borrower.to_string(),
+                referrer: referrer.map(ToString::to_string),
+            },
+        )
+    }
+
+    /// Tells the volume of `borrower` in `denom` over the fee window and the gateway fee it gets on it
+    pub fn borrower_tier(
+        &self,
+        querier: &QuerierWrapper,
+        borrower: &Addr,
+        denom: &str,
+    ) -> StdResult<BorrowerTierResponse> {
+        let tiers: Vec<BorrowerTierResponse> = querier.query_wasm_smart(
+            self.addr(),
+            &QueryMsg::BorrowerTier {
+                borrower: borrower.to_string(),
+                denom: Some(denom.to_string()),
+            },
+        )?;
+
+        tiers
+            .into_iter()
+            .next()
+            .ok_or_else(|| StdError::generic_err("No tier returned for the denom"))
+    }
+
+    /// Tells the same as `borrower_tier` for every denom with tiers or volume of `borrower`
+    pub fn borrower_tiers(
+        &self,
+        querier: &QuerierWrapper,
+        borrower: &Addr,
+    ) -> StdResult<Vec<BorrowerTierResponse>> {
+        querier.query_wasm_smart(
+            self.addr(),
+            &QueryMsg::BorrowerTier {
+                borrower: borrower.to_string(),
+                denom: None,
+            },
+        )
+    }
+
+    /// Tells the referral fees `referrer` can claim
+    pub fn referral_fees(&self, querier: &QuerierWrapper, referrer: &Addr) -> StdResult<Vec<Coin>> {
+        querier.query_wasm_smart(
+            self.addr(),
+            &QueryMsg::ReferralFees {
+                referrer: referrer.to_string(),
+            },
+        )
+    }
+
+    /// Tells the fees held by the gateway
+    pub fn protocol_fees(&self, querier: &QuerierWrapper) -> StdResult<ProtocolFeesResponse> {
+        querier.query_wasm_smart(self.addr(), &QueryMsg::ProtocolFees {})
+    }
+
+    /// Tells the vault's liquidity, and what it moved to or from vaults on other chains
+    pub fn liquidity(&self, querier: &QuerierWrapper) -> StdResult<LiquidityResponse> {
+        querier.query_wasm_smart(self.addr(), &QueryMsg::Liquidity {})
+    }
+
+    /// Tells the smallest loan allowed in `denom`
+    pub fn min_loan_size(&self, querier: &QuerierWrapper, denom: &str) -> StdResult<Coin> {
+        querier.query_wasm_smart(
+            self.addr(),
+            &QueryMsg::MinLoanSize {
+                denom: denom.to_string(),
+            },
+        )
+    }
+}
+
+/// Reads what a `RequestFlashLoan` sent as a submessage responded with, in the reply to it
+pub fn parse_flash_loan_reply(msg: Reply) -> StdResult<FlashLoanResponse> {
+    let data = msg
+        .result
+        .into_result()
+        .map_err(StdError::generic_err)?
+        .data
+        .ok_or_else(|| StdError::generic_err("Flash loan responded without data"))?;
+
+    let data = parse_execute_response_data(&data)
+        .map_err(|err| StdError::generic_err(err.to_string()))?
+        .data
+        .ok_or_else(|| StdError::generic_err("Flash loan responded without data"))?;
+
+    from_json(&data)
 }
 
 pub struct RequestFlashLoanProps<'a, Msg: Serialize> {
@@ -67,6 +177,8 @@ pub struct RequestFlashLoanProps<'a, Msg: Serialize> {
     pub asset: Coin,
     /// The message to be called back when flash-borrowed funds are available
     pub on_flash_loan_provided_hook: &'a Msg,
+    /// Who routed the borrower to the gateway, if anyone
+    pub referrer: Option<String>,
 }
 
 pub struct PayFlashLoanBackProps {
@@ -1,7 +1,11 @@
+#[cfg(feature = "client")]
+pub mod client;
 pub mod contract;
 mod error;
+pub mod fees;
 pub mod helpers;
 pub mod msg;
 pub mod state;
+pub mod treasury;
 
 pub use crate::error::ContractError;
@@ -1,25 +1,191 @@
-use cosmwasm_std::{Binary, Coin, Addr};
+use cosmwasm_schema::QueryResponses;
+use cosmwasm_std::{Binary, Coin, Addr, Decimal, Uint128};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::state::Config;
+use crate::state::{Config, FeeConfig, FeeTier};
+use cw_flash_loan_vault::state::RemoteVault;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InstantiateMsg {
     pub cw_vault_contract_addr: String,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateMsg {}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
-    RequestFlashLoan { asset: Coin, on_funded_msg: Binary },
+    RequestFlashLoan {
+        asset: Coin,
+        on_funded_msg: Binary,
+        /// Gets a share of the gateway fee once the loan is repaid
+        #[serde(default)]
+        referrer: Option<String>,
+    },
     FlashLoanProvided { asset: Coin },
+    /// Pays out the referral fees accrued to the sender
+    ClaimReferralFees {},
+    /// Owner only, sends accrued protocol fees to `recipient`
+    WithdrawFees {
+        denom: String,
+        amount: Uint128,
+        recipient: String,
+    },
+    /// Owner only
+    UpdateFees { fees: FeeConfig },
+    /// Owner only, sets the smallest loan allowed in each of the coins' denoms
+    UpdateMinLoanSizes { min_loan_sizes: Vec<Coin> },
+    /// Owner only, removes the state of a loan opened in an earlier block
+    ClearStaleLoans {},
+    /// Owner only, stops loans from being requested until unpaused
+    Pause {},
+    /// Owner only
+    Unpause {},
+    /// Owner only, sets which borrowers can borrow while borrowers are restricted,
+    /// and turns the restriction on or off if `restrict` is set
+    UpdateAllowedBorrowers {
+        add: Vec<String>,
+        remove: Vec<String>,
+        #[serde(default)]
+        restrict: Option<bool>,
+    },
+    /// Owner only, lends from another vault, which must have the gateway set as its own.
+    /// No loan can be in progress.
+    UpdateVault { vault: String },
 }
 
+/// Data `RequestFlashLoan` responds with once the loan got repaid
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FlashLoanResponse {
+    pub loan_id: u64,
+    pub vault_fee: Coin,
+    pub gateway_fee: Coin,
+    /// Data the borrower's hook responded with, such as the outcome of its strategy
+    pub hook_data: Option<Binary>,
+}
+
+/// Governance messages mirroring the owner's, for chains where the gateway is governance-owned
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SudoMsg {
+    WithdrawFees {
+        denom: String,
+        amount: Uint128,
+        recipient: String,
+    },
+    UpdateFees { fees: FeeConfig },
+    UpdateMinLoanSizes { min_loan_sizes: Vec<Coin> },
+    ClearStaleLoans {},
+    Pause {},
+    Unpause {},
+    UpdateAllowedBorrowers {
+        add: Vec<String>,
+        remove: Vec<String>,
+        #[serde(default)]
+        restrict: Option<bool>,
+    },
+    UpdateVault { vault: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, QueryResponses)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
+    #[returns((Coin, Coin))]
     DebtRemaining { borrower: Addr },
+    /// Dry-runs a flash loan request, telling what it would cost and whether it would go through
+    #[returns(SimulateFlashLoanResponse)]
+    SimulateFlashLoan {
+        assets: Vec<Coin>,
+        borrower: String,
+        referrer: Option<String>,
+    },
+    /// Volume of the borrower in the denom over the rolling window and the fee it gets on it.
+    /// Without a denom, in every denom with tiers or volume of the borrower.
+    #[returns(Vec<BorrowerTierResponse>)]
+    BorrowerTier {
+        borrower: String,
+        denom: Option<String>,
+    },
+    /// Referral fees the referrer can claim
+    #[returns(Vec<Coin>)]
+    ReferralFees { referrer: String },
+    /// Fees held by the gateway
+    #[returns(ProtocolFeesResponse)]
+    ProtocolFees {},
+    /// Smallest loan allowed in the denom
+    #[returns(Coin)]
+    MinLoanSize { denom: String },
+    /// Liquidity of the vault, and what it lent to or borrowed from vaults on other chains
+    #[returns(LiquidityResponse)]
+    Liquidity {},
+}
+
+/// Terms a single asset gets lent on
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LoanTerms {
+    pub asset: Coin,
+    /// Vault lending the asset
+    pub vault: Addr,
+    pub gateway_fee: Coin,
+    pub vault_fee: Coin,
+    /// Asset plus fees, to be paid back to the gateway
+    pub total_repayment: Coin,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SimulateFlashLoanResponse {
+    /// Terms of every asset which could be lent
+    pub loans: Vec<LoanTerms>,
+    pub total_repayment: Vec<Coin>,
+    /// Why the request would be rejected, if it would be
+    pub rejection: Option<Rejection>,
+}
+
+/// Reason a flash loan request gets rejected for
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Rejection {
+    Paused {},
+    BorrowerNotAllowed {},
+    SingleAssetOnly {},
+    SelfReferral {},
+    LoanInProgress {},
+    LoanTooSmall { min: Coin },
+    /// The vault cannot lend the asset, e.g. for lack of liquidity
+    VaultRefused { reason: String },
+    Other { reason: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BorrowerTierResponse {
+    pub denom: String,
+    pub volume: Uint128,
+    /// Best tier reached, if any
+    pub tier: Option<FeeTier>,
+    /// Gateway fee rate after discount
+    pub fee_rate: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LiquidityResponse {
+    pub vault: Addr,
+    /// Largest loan the vault can serve, remote liquidity has to be moved to it first
+    pub available: Coin,
+    pub lent_remotely: Coin,
+    pub borrowed_remotely: Coin,
+    /// Lent by remote vaults, but still in flight
+    pub receiving_remotely: Coin,
+    pub remote_vaults: Vec<RemoteVault>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ProtocolFeesResponse {
+    /// Withdrawable by the owner
+    pub accrued: Vec<Coin>,
+    /// Set aside for referrers until they claim
+    pub referral_fees_owed: Vec<Coin>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -1,21 +1,82 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{Addr, Binary, Coin};
-use cw_storage_plus::Item;
+use cosmwasm_std::{Addr, Binary, Coin, Decimal, Empty, Uint128};
+use cw_storage_plus::{Item, Map};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Config {
     pub cw_vault_contract_addr: Addr,
+    pub owner: Addr,
+    pub fees: FeeConfig,
+    /// No loans can be requested while paused
+    #[serde(default)]
+    pub paused: bool,
+    /// Only borrowers in `ALLOWED_BORROWERS` can borrow while restricted
+    #[serde(default)]
+    pub restrict_borrowers: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FeeConfig {
+    /// Gateway fee rate, before any discount
+    pub rate: Decimal,
+    pub tiers: Vec<FeeTier>,
+    /// Seconds over which the volume of a borrower is counted
+    pub volume_window: u64,
+    /// Share of the gateway fee going to the referrer of a loan
+    pub referral_share: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FeeTier {
+    /// Denom the tier applies to, volumes of different denoms aren't comparable
+    pub denom: String,
+    /// Volume a borrower must reach in the denom over the window to get the discount
+    pub min_volume: Uint128,
+    /// Share of the gateway fee waived
+    pub discount: Decimal,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct FlashLoanState {
+    /// Sequential identifier of the loan, reported in errors
+    pub loan_id: u64,
     pub borrower_contract_addr: Addr,
     pub borrower_requested_asset: Coin,
+    /// Vault fee quoted when the loan was requested, as liquidity changes while it's out
+    pub vault_fee: Coin,
+    pub gateway_fee: Coin,
+    /// Frontend or aggregator which routed the borrower, getting a share of the gateway fee
+    pub referrer: Option<Addr>,
     pub on_funded_msg: Binary,
+    /// Block height the loan was requested at. Loans complete within their transaction,
+    /// so state from an earlier block was left behind and is stale.
+    #[serde(default)]
+    pub opened_at: u64,
 }
 
 pub const CONFIG: Item<Config> = Item::new("config");
 
+/// Borrowers which can borrow while borrowers are restricted
+pub const ALLOWED_BORROWERS: Map<&Addr, Empty> = Map::new("allowed_borrowers");
+
+/// Number of flash loans requested so far, used to assign loan ids
+pub const LOAN_COUNTER: Item<u64> = Item::new("loan_counter");
+
 pub const FLASH_LOAN_STATE: Item<FlashLoanState> = Item::new("flash_loan_state");
+
+/// Referral fees not claimed yet, by referrer and denom
+pub const REFERRAL_FEES: Map<(&Addr, &str), Uint128> = Map::new("referral_fees");
+
+/// Referral fees not claimed yet, summed up by denom
+pub const REFERRAL_FEES_OWED: Map<&str, Uint128> = Map::new("referral_fees_owed");
+
+/// Protocol fees accrued by the gateway, by denom
+pub const PROTOCOL_FEES: Map<&str, Uint128> = Map::new("protocol_fees");
+
+/// Smallest loan allowed in each denom, loans of denoms without one must borrow at least 1
+pub const MIN_LOAN_SIZES: Map<&str, Uint128> = Map::new("min_loan_sizes");
+
+/// Volume repaid by each borrower, by denom and start of the bucket it was counted in
+pub const BORROWER_VOLUME: Map<(&Addr, &str, u64), Uint128> = Map::new("borrower_volume");
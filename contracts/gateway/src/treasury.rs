@@ -0,0 +1,151 @@
+//! Ledger of the fees the gateway holds.
+//!
+//! Everything the gateway owns is either a protocol fee, withdrawable by the owner,
+//! or a referral fee owed to a referrer. The ledger always adds up to the gateway's
+//! bank balance, so funds held for fees can never be used to repay a loan. Funds sent to
+//! the gateway outside of a repayment are booked as protocol fees once the next loan starts.
+
+use cosmwasm_std::{coin, Addr, Coin, Order, StdResult, Storage, Uint128};
+use cw_storage_plus::Map;
+
+use crate::error::ContractError;
+use crate::state::{PROTOCOL_FEES, REFERRAL_FEES, REFERRAL_FEES_OWED};
+
+/// Amount of `denom` set aside for fees
+pub fn reserved(storage: &dyn Storage, denom: &str) -> StdResult<Uint128> {
+    let protocol_fees = PROTOCOL_FEES.may_load(storage, denom)?.unwrap_or_default();
+    let referral_fees = REFERRAL_FEES_OWED
+        .may_load(storage, denom)?
+        .unwrap_or_default();
+
+    Ok(protocol_fees + referral_fees)
+}
+
+/// Books whatever the gateway holds of `balance`'s denom beyond the ledger as protocol fees,
+/// so funds sent to the gateway outside of a repayment cannot pass for one
+pub fn book_surplus(storage: &mut dyn Storage, balance: &Coin) -> StdResult<Coin> {
+    let reserved = reserved(storage, balance.denom.as_str())?;
+    let surplus = coin(
+        balance.amount.saturating_sub(reserved).u128(),
+        balance.denom.as_str(),
+    );
+
+    accrue_protocol_fee(storage, &surplus)?;
+
+    Ok(surplus)
+}
+
+pub fn accrue_protocol_fee(storage: &mut dyn Storage, fee: &Coin) -> StdResult<()> {
+    if fee.amount.is_zero() {
+        return Ok(());
+    }
+
+    PROTOCOL_FEES.update(storage, fee.denom.as_str(), |accrued| -> StdResult<_> {
+        Ok(accrued.unwrap_or_default().checked_add(fee.amount)?)
+    })?;
+
+    Ok(())
+}
+
+pub fn accrue_referral_fee(
+    storage: &mut dyn Storage,
+    referrer: &Addr,
+    fee: &Coin,
+) -> StdResult<()> {
+    if fee.amount.is_zero() {
+        return Ok(());
+    }
+
+    REFERRAL_FEES.update(
+        storage,
+        (referrer, fee.denom.as_str()),
+        |accrued| -> StdResult<_> { Ok(accrued.unwrap_or_default().checked_add(fee.amount)?) },
+    )?;
+    REFERRAL_FEES_OWED.update(storage, fee.denom.as_str(), |owed| -> StdResult<_> {
+        Ok(owed.unwrap_or_default().checked_add(fee.amount)?)
+    })?;
+
+    Ok(())
+}
+
+/// Takes all of the referral fees of `referrer` off the ledger
+pub fn take_referral_fees(storage: &mut dyn Storage, referrer: &Addr) -> StdResult<Vec<Coin>> {
+    let fees = REFERRAL_FEES
+        .prefix(referrer)
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<(String, Uint128)>>>()?;
+
+    for (denom, amount) in &fees {
+        REFERRAL_FEES.remove(storage, (referrer, denom));
+        REFERRAL_FEES_OWED.update(storage, denom, |owed| -> StdResult<_> {
+            Ok(owed.unwrap_or_default().checked_sub(*amount)?)
+        })?;
+    }
+
+    Ok(fees
+        .into_iter()
+        .map(|(denom, amount)| coin(amount.u128(), denom))
+        .collect())
+}
+
+/// Takes `amount` of protocol fees off the ledger
+pub fn take_protocol_fees(storage: &mut dyn Storage, amount: &Coin) -> Result<(), ContractError> {
+    let accrued = PROTOCOL_FEES
+        .may_load(storage, amount.denom.as_str())?
+        .unwrap_or_default();
+
+    if amount.amount > accrued {
+        return Err(ContractError::InsufficientProtocolFees {
+            available: coin(accrued.u128(), amount.denom.as_str()),
+        });
+    }
+
+    PROTOCOL_FEES.save(storage, amount.denom.as_str(), &(accrued - amount.amount))?;
+
+    Ok(())
+}
+
+/// All fees of a ledger, skipping emptied denoms
+pub fn ledger_coins(
+    storage: &dyn Storage,
+    ledger: &Map<&str, Uint128>,
+) -> StdResult<Vec<Coin>> {
+    ledger
+        .range(storage, None, None, Order::Ascending)
+        .filter(|item| !matches!(item, Ok((_, amount)) if amount.is_zero()))
+        .map(|item| item.map(|(denom, amount)| coin(amount.u128(), denom)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::testing::MockStorage;
+
+    #[test]
+    fn reserves_protocol_and_referral_fees() {
+        let mut storage = MockStorage::new();
+        let referrer = Addr::unchecked("referrer");
+
+        accrue_protocol_fee(&mut storage, &coin(90, "uluna")).unwrap();
+        accrue_referral_fee(&mut storage, &referrer, &coin(10, "uluna")).unwrap();
+        assert_eq!(reserved(&storage, "uluna").unwrap(), Uint128::new(100));
+
+        assert_eq!(
+            take_referral_fees(&mut storage, &referrer).unwrap(),
+            vec![coin(10, "uluna")]
+        );
+        assert_eq!(reserved(&storage, "uluna").unwrap(), Uint128::new(90));
+
+        let err = take_protocol_fees(&mut storage, &coin(91, "uluna")).unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::InsufficientProtocolFees { available } if available == coin(90, "uluna")
+        ));
+
+        take_protocol_fees(&mut storage, &coin(90, "uluna")).unwrap();
+        assert!(reserved(&storage, "uluna").unwrap().is_zero());
+        assert!(ledger_coins(&storage, &PROTOCOL_FEES).unwrap().is_empty());
+    }
+}
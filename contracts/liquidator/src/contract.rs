@@ -0,0 +1,348 @@
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
+use cosmwasm_std::{
+    coin, to_json_binary, wasm_execute, Api, BankMsg, Binary, Coin, Deps, DepsMut, Env,
+    MessageInfo, Response, StdResult, Storage,
+};
+
+use cw2::set_contract_version;
+use cw_flash_loan_borrower::swap::{swap_route_msgs, SwapStep};
+use cw_flash_loan_gateway::helpers::{Contract as FlashLoanGateway, RequestFlashLoanProps};
+
+use crate::{
+    error::ContractError,
+    market,
+    msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg},
+    state::{Config, Liquidation, CONFIG, LIQUIDATION, SWAP_ROUTES},
+};
+
+// version info for migration info
+const CONTRACT_NAME: &str = "crates.io:cw-flash-loan-liquidator";
+const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn instantiate(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    msg: InstantiateMsg,
+) -> Result<Response, ContractError> {
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    let cw_gateway_contract_addr = deps.api.addr_validate(&msg.cw_gateway_contract_addr)?;
+
+    CONFIG.save(
+        deps.storage,
+        &Config {
+            owner: info.sender,
+            cw_gateway_contract_addr,
+        },
+    )?;
+
+    for swap_route in msg.swap_routes {
+        save_swap_route(
+            deps.storage,
+            deps.api,
+            &swap_route.offer_denom,
+            &swap_route.route,
+        )?;
+    }
+
+    Ok(Response::new().add_attribute("method", "instantiate"))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    match msg {
+        ExecuteMsg::Liquidate {
+            market,
+            position_owner,
+            debt_asset,
+            collateral_denom,
+        } => execute_liquidate(
+            deps,
+            env,
+            info,
+            market,
+            position_owner,
+            debt_asset,
+            collateral_denom,
+        ),
+        ExecuteMsg::OnFlashLoanProvided {} => execute_on_flash_loan_provided(deps, env, info),
+        ExecuteMsg::SwapCollateral {} => execute_swap_collateral(deps, env, info),
+        ExecuteMsg::SetSwapRoute { offer_denom, route } => {
+            execute_set_swap_route(deps, info, offer_denom, route)
+        }
+        ExecuteMsg::Withdraw { amount, recipient } => {
+            execute_withdraw(deps, env, info, amount, recipient)
+        }
+    }
+}
+
+/// Handler initiating a liquidation, funded by a flash loan of the debt asset
+fn execute_liquidate(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    market: String,
+    position_owner: String,
+    debt_asset: Coin,
+    collateral_denom: String,
+) -> Result<Response, ContractError> {
+    println!(
+        "[Liquidator: execute_liquidate]: repaying {:?} of {} on {}",
+        &debt_asset, &position_owner, &market
+    );
+
+    let config = CONFIG.load(deps.storage)?;
+
+    if info.sender != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // Collateral the liquidator has no route for could not be sold to repay the loan
+    if collateral_denom != debt_asset.denom {
+        let route = SWAP_ROUTES
+            .may_load(deps.storage, &collateral_denom)?
+            .ok_or_else(|| ContractError::SwapRouteNotFound {
+                offer_denom: collateral_denom.clone(),
+            })?;
+
+        if route.last().map(|step| step.ask_denom.as_str()) != Some(debt_asset.denom.as_str()) {
+            return Err(ContractError::InvalidSwapRoute {
+                offer_denom: collateral_denom,
+                ask_denom: debt_asset.denom,
+            });
+        }
+    }
+
+    let initial_collateral_balance = deps
+        .querier
+        .query_balance(env.contract.address, collateral_denom.as_str())?
+        .amount;
+
+    LIQUIDATION.save(
+        deps.storage,
+        &Liquidation {
+            market: deps.api.addr_validate(&market)?,
+            position_owner: deps.api.addr_validate(&position_owner)?,
+            debt_asset: debt_asset.clone(),
+            collateral_denom,
+            initial_collateral_balance,
+        },
+    )?;
+
+    let msg = FlashLoanGateway(config.cw_gateway_contract_addr).request_flash_loan(
+        RequestFlashLoanProps {
+            asset: debt_asset,
+            on_flash_loan_provided_hook: &ExecuteMsg::OnFlashLoanProvided {},
+            referrer: None,
+        },
+    )?;
+
+    Ok(Response::new().add_message(msg).add_attributes(vec![
+        ("module", "liquidator"),
+        ("action", "execute_liquidate"),
+    ]))
+}
+
+/// Handler utilising the flash loan: liquidates the position, sells the collateral
+/// and repays the gateway.
+fn execute_on_flash_loan_provided(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    // Anyone else could have the liquidator spend its own funds on the saved liquidation
+    if info.sender != config.cw_gateway_contract_addr {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let liquidation = LIQUIDATION.load(deps.storage)?;
+
+    let liquidate_msg = wasm_execute(
+        liquidation.market.to_string(),
+        &market::ExecuteMsg::Liquidate {
+            position_owner: liquidation.position_owner.to_string(),
+            collateral_denom: liquidation.collateral_denom,
+        },
+        vec![liquidation.debt_asset],
+    )?;
+
+    // The collateral is only known once the market has paid it out
+    let swap_collateral_msg = wasm_execute(
+        env.contract.address.to_string(),
+        &ExecuteMsg::SwapCollateral {},
+        vec![],
+    )?;
+
+    let flash_loan_gateway = FlashLoanGateway(config.cw_gateway_contract_addr);
+
+    let total_repayment =
+        flash_loan_gateway.get_debt_remaining(&deps.querier, env.contract.address)?;
+
+    Ok(Response::new()
+        .add_message(liquidate_msg)
+        .add_message(swap_collateral_msg)
+        .add_message(flash_loan_gateway.repay_flash_loan(total_repayment)?)
+        .add_attributes(vec![
+            ("module", "liquidator"),
+            ("action", "execute_on_flash_loan_provided"),
+        ]))
+}
+
+/// Handler selling the collateral seized during the liquidation for the debt denom.
+fn execute_swap_collateral(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    // This step is only ever scheduled by the contract itself
+    if info.sender != env.contract.address {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let liquidation = LIQUIDATION.load(deps.storage)?;
+    LIQUIDATION.remove(deps.storage);
+
+    let collateral_balance = deps
+        .querier
+        .query_balance(
+            env.contract.address.clone(),
+            liquidation.collateral_denom.as_str(),
+        )?
+        .amount;
+
+    let seized_amount = collateral_balance.saturating_sub(liquidation.initial_collateral_balance);
+    if seized_amount.is_zero() {
+        return Err(ContractError::NoCollateralReceived {});
+    }
+
+    let seized = coin(seized_amount.u128(), liquidation.collateral_denom.as_str());
+
+    println!(
+        "[Liquidator: execute_swap_collateral]: seized {:?}",
+        &seized
+    );
+
+    // Collateral in the debt denom is already what the gateway wants back
+    if liquidation.collateral_denom == liquidation.debt_asset.denom {
+        return Ok(Response::new().add_attributes(vec![
+            ("module", "liquidator"),
+            ("action", "execute_swap_collateral"),
+        ]));
+    }
+
+    let route = SWAP_ROUTES.load(deps.storage, &liquidation.collateral_denom)?;
+
+    let (swap_msgs, swap_return) = swap_route_msgs(
+        deps.api,
+        &deps.querier,
+        &env.contract.address,
+        seized,
+        &route,
+    )?;
+
+    println!(
+        "[Liquidator: execute_swap_collateral]: swap route returns {:?}",
+        swap_return
+    );
+
+    Ok(Response::new().add_messages(swap_msgs).add_attributes(vec![
+        ("module", "liquidator"),
+        ("action", "execute_swap_collateral"),
+        ("swap_return", &swap_return.to_string()),
+    ]))
+}
+
+fn execute_set_swap_route(
+    deps: DepsMut,
+    info: MessageInfo,
+    offer_denom: String,
+    route: Vec<SwapStep>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    if info.sender != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    save_swap_route(deps.storage, deps.api, &offer_denom, &route)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("module", "liquidator"),
+        ("action", "execute_set_swap_route"),
+        ("offer_denom", &offer_denom),
+    ]))
+}
+
+fn save_swap_route(
+    storage: &mut dyn Storage,
+    api: &dyn Api,
+    offer_denom: &str,
+    route: &[SwapStep],
+) -> Result<(), ContractError> {
+    // Catch malformed DEX addresses when configuring rather than mid-liquidation
+    for step in route {
+        step.dex.adapter(api)?;
+    }
+
+    if route.is_empty() {
+        SWAP_ROUTES.remove(storage, offer_denom);
+    } else {
+        SWAP_ROUTES.save(storage, offer_denom, &route.to_vec())?;
+    }
+
+    Ok(())
+}
+
+/// Handler sweeping accumulated profits out of the liquidator contract.
+fn execute_withdraw(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    amount: Coin,
+    recipient: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    if info.sender != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let recipient = deps.api.addr_validate(&recipient)?;
+
+    let msg = BankMsg::Send {
+        to_address: recipient.to_string(),
+        amount: vec![amount],
+    };
+
+    Ok(Response::new().add_message(msg).add_attributes(vec![
+        ("module", "liquidator"),
+        ("action", "execute_withdraw"),
+        ("recipient", recipient.as_str()),
+    ]))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::new().add_attributes(vec![("module", "liquidator"), ("action", "migrate")]))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::SwapRoute { offer_denom } => {
+            to_json_binary(&SWAP_ROUTES.may_load(deps.storage, &offer_denom)?)
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use cosmwasm_std::StdError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ContractError {
+    #[error("{0}")]
+    Std(#[from] StdError),
+
+    #[error("Unauthorized")]
+    Unauthorized {},
+
+    #[error("No swap route configured for {offer_denom}")]
+    SwapRouteNotFound { offer_denom: String },
+
+    #[error("Swap route for {offer_denom} doesn't end in {ask_denom}")]
+    InvalidSwapRoute {
+        offer_denom: String,
+        ask_denom: String,
+    },
+
+    #[error("No collateral was received from the market")]
+    NoCollateralReceived {},
+}
@@ -0,0 +1,345 @@
+#[cfg(test)]
+mod tests {
+    use crate::helpers::Contract as CwLiquidatorContract;
+    use crate::msg::{ExecuteMsg, InstantiateMsg, SwapRoute};
+    use crate::ContractError;
+    use cosmwasm_std::{coin, coins, Addr, Coin, Decimal, Empty, Uint128};
+    use cw_flash_loan_borrower::swap::{Dex, SwapStep};
+    use cw_flash_loan_gateway::helpers::Contract as CwGatewayContract;
+    use cw_flash_loan_mock_amm::msg::{
+        Asset, AssetInfo, InstantiateMsg as PoolInstantiateMsg, QueryMsg as PoolQueryMsg,
+        SimulationResponse,
+    };
+    use cw_flash_loan_mock_money_market::msg::{
+        DenomPrice, ExecuteMsg as MarketExecuteMsg, InstantiateMsg as MarketInstantiateMsg,
+        PositionResponse, QueryMsg as MarketQueryMsg,
+    };
+    use cw_flash_loan_testing::{
+        assert_treasury_balanced, Deployment as FlashLoanDeployment, DeploymentBuilder, ADMIN,
+    };
+    use cw_multi_test::{App, Contract, ContractWrapper, Executor};
+
+    pub fn cw_liquidator_contract() -> Box<dyn Contract<Empty>> {
+        let contract = ContractWrapper::new(
+            crate::contract::execute,
+            crate::contract::instantiate,
+            crate::contract::query,
+        );
+
+        Box::new(contract)
+    }
+
+    pub fn cw_mock_amm_contract() -> Box<dyn Contract<Empty>> {
+        let contract = ContractWrapper::new(
+            cw_flash_loan_mock_amm::contract::execute,
+            cw_flash_loan_mock_amm::contract::instantiate,
+            cw_flash_loan_mock_amm::contract::query,
+        );
+
+        Box::new(contract)
+    }
+
+    pub fn cw_mock_money_market_contract() -> Box<dyn Contract<Empty>> {
+        let contract = ContractWrapper::new(
+            cw_flash_loan_mock_money_market::contract::execute,
+            cw_flash_loan_mock_money_market::contract::instantiate,
+            cw_flash_loan_mock_money_market::contract::query,
+        );
+
+        Box::new(contract)
+    }
+
+    /// Owner of the position getting liquidated, validated by the market
+    const POSITION_OWNER: &str = "position_owner";
+
+    struct Deployment {
+        app: App,
+        liquidator: CwLiquidatorContract,
+        gateway: CwGatewayContract,
+        market: Addr,
+        pool: Addr,
+    }
+
+    /// Deploys the flash loan contracts, a uluna/uusd pool priced at 2 uusd per uluna
+    /// and a market where the position owner borrowed 2_000 uusd against 1_000 uluna
+    /// while uluna was still worth 3 uusd.
+    fn proper_instantiate() -> Deployment {
+        let FlashLoanDeployment {
+            mut app,
+            gateway: cw_gateway_contract,
+            ..
+        } = DeploymentBuilder::new()
+            .with_denom("uusd")
+            .with_balance(ADMIN, coins(1_000_000_000_000_000_000, "uluna"))
+            .with_balance(POSITION_OWNER, coins(1_000, "uluna"))
+            .build();
+
+        let cw_mock_amm_id = app.store_code(cw_mock_amm_contract());
+        let pool = app
+            .instantiate_contract(
+                cw_mock_amm_id,
+                Addr::unchecked(ADMIN),
+                &PoolInstantiateMsg {
+                    denoms: ["uluna".to_string(), "uusd".to_string()],
+                    commission_rate: Decimal::permille(3),
+                },
+                &[coin(1_000_000, "uluna"), coin(2_000_000, "uusd")],
+                "pool",
+                None,
+            )
+            .unwrap();
+
+        let cw_mock_money_market_id = app.store_code(cw_mock_money_market_contract());
+        let market = app
+            .instantiate_contract(
+                cw_mock_money_market_id,
+                Addr::unchecked(ADMIN),
+                &MarketInstantiateMsg {
+                    liquidation_threshold: Decimal::percent(80),
+                    liquidation_bonus: Decimal::percent(10),
+                    prices: vec![
+                        DenomPrice {
+                            denom: "uluna".to_string(),
+                            price: Decimal::percent(300),
+                        },
+                        DenomPrice {
+                            denom: "uusd".to_string(),
+                            price: Decimal::one(),
+                        },
+                    ],
+                },
+                &coins(1_000_000, "uusd"),
+                "market",
+                None,
+            )
+            .unwrap();
+
+        app.execute_contract(
+            Addr::unchecked(POSITION_OWNER),
+            market.clone(),
+            &MarketExecuteMsg::Deposit {},
+            &coins(1_000, "uluna"),
+        )
+        .unwrap();
+
+        app.execute_contract(
+            Addr::unchecked(POSITION_OWNER),
+            market.clone(),
+            &MarketExecuteMsg::Borrow {
+                asset: coin(2_000, "uusd"),
+            },
+            &[],
+        )
+        .unwrap();
+
+        let cw_liquidator_id = app.store_code(cw_liquidator_contract());
+        let cw_liquidator_contract_addr = app
+            .instantiate_contract(
+                cw_liquidator_id,
+                Addr::unchecked(ADMIN),
+                &InstantiateMsg {
+                    cw_gateway_contract_addr: cw_gateway_contract.addr().to_string(),
+                    swap_routes: vec![SwapRoute {
+                        offer_denom: "uluna".to_string(),
+                        route: vec![SwapStep {
+                            dex: Dex::Astroport {
+                                pair_addr: pool.to_string(),
+                            },
+                            ask_denom: "uusd".to_string(),
+                        }],
+                    }],
+                },
+                &[],
+                "liquidator",
+                None,
+            )
+            .unwrap();
+
+        Deployment {
+            app,
+            gateway: cw_gateway_contract,
+            liquidator: CwLiquidatorContract(cw_liquidator_contract_addr),
+            market,
+            pool,
+        }
+    }
+
+    fn set_uluna_price(deployment: &mut Deployment, price: Decimal) {
+        deployment
+            .app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                deployment.market.clone(),
+                &MarketExecuteMsg::SetPrice {
+                    denom: "uluna".to_string(),
+                    price,
+                },
+                &[],
+            )
+            .unwrap();
+    }
+
+    fn liquidate_msg(deployment: &Deployment, debt_asset: Coin) -> ExecuteMsg {
+        ExecuteMsg::Liquidate {
+            market: deployment.market.to_string(),
+            position_owner: POSITION_OWNER.to_string(),
+            debt_asset,
+            collateral_denom: "uluna".to_string(),
+        }
+    }
+
+    fn balance(app: &App, addr: &Addr, denom: &str) -> Uint128 {
+        app.wrap().query_balance(addr, denom).unwrap().amount
+    }
+
+    #[test]
+    fn liquidates_unhealthy_position() {
+        let mut deployment = proper_instantiate();
+
+        // The position is now worth 2_000 uusd with a borrow limit of 1_600 uusd
+        set_uluna_price(&mut deployment, Decimal::percent(200));
+
+        // Repaying 1_000 uusd seizes 1_100 uusd worth of uluna
+        let SimulationResponse { return_amount, .. } = deployment
+            .app
+            .wrap()
+            .query_wasm_smart(
+                &deployment.pool,
+                &PoolQueryMsg::Simulation {
+                    offer_asset: Asset {
+                        info: AssetInfo::NativeToken {
+                            denom: "uluna".to_string(),
+                        },
+                        amount: Uint128::new(550),
+                    },
+                    ask_asset_info: None,
+                },
+            )
+            .unwrap();
+
+        let msg = liquidate_msg(&deployment, coin(1_000, "uusd"));
+        deployment
+            .app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                deployment.liquidator.addr(),
+                &msg,
+                &[],
+            )
+            .unwrap();
+
+        let position: PositionResponse = deployment
+            .app
+            .wrap()
+            .query_wasm_smart(
+                &deployment.market,
+                &MarketQueryMsg::Position {
+                    owner: POSITION_OWNER.to_string(),
+                },
+            )
+            .unwrap();
+        assert_eq!(position.collateral, coins(450, "uluna"));
+        assert_eq!(position.debt, coins(1_000, "uusd"));
+
+        // Whatever the collateral sold for above the 1_030 uusd owed to the gateway
+        let liquidator = deployment.liquidator.addr();
+        assert_eq!(
+            balance(&deployment.app, &liquidator, "uusd"),
+            return_amount - Uint128::new(1_030)
+        );
+        assert!(balance(&deployment.app, &liquidator, "uluna").is_zero());
+
+        deployment
+            .app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                liquidator.clone(),
+                &ExecuteMsg::Withdraw {
+                    amount: coin(return_amount.u128() - 1_030, "uusd"),
+                    recipient: "recipient".to_string(),
+                },
+                &[],
+            )
+            .unwrap();
+        assert!(balance(&deployment.app, &liquidator, "uusd").is_zero());
+
+        assert_treasury_balanced(&deployment.app.wrap(), &deployment.gateway);
+    }
+
+    #[test]
+    fn healthy_position_is_not_liquidated() {
+        let mut deployment = proper_instantiate();
+
+        let msg = liquidate_msg(&deployment, coin(1_000, "uusd"));
+        let err = deployment
+            .app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                deployment.liquidator.addr(),
+                &msg,
+                &[],
+            )
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<cw_flash_loan_gateway::ContractError>(),
+            Some(cw_flash_loan_gateway::ContractError::BorrowerHookFailed { reason, .. })
+                if reason.contains("Position is healthy")
+        ));
+
+        assert_treasury_balanced(&deployment.app.wrap(), &deployment.gateway);
+    }
+
+    #[test]
+    fn liquidation_requires_swap_route_to_debt_denom() {
+        let mut deployment = proper_instantiate();
+        set_uluna_price(&mut deployment, Decimal::percent(200));
+
+        deployment
+            .app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                deployment.liquidator.addr(),
+                &ExecuteMsg::SetSwapRoute {
+                    offer_denom: "uluna".to_string(),
+                    route: vec![],
+                },
+                &[],
+            )
+            .unwrap();
+
+        let msg = liquidate_msg(&deployment, coin(1_000, "uusd"));
+        let err = deployment
+            .app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                deployment.liquidator.addr(),
+                &msg,
+                &[],
+            )
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<ContractError>(),
+            Some(ContractError::SwapRouteNotFound { .. })
+        ));
+
+        // Only the owner runs liquidations
+        let err = deployment
+            .app
+            .execute_contract(
+                Addr::unchecked(POSITION_OWNER),
+                deployment.liquidator.addr(),
+                &msg,
+                &[],
+            )
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<ContractError>(),
+            Some(ContractError::Unauthorized {})
+        ));
+
+        assert_treasury_balanced(&deployment.app.wrap(), &deployment.gateway);
+    }
+}
@@ -0,0 +1,14 @@
+//! Flash loan borrower liquidating unhealthy money market positions.
+//!
+//! The debt is repaid with flash-loaned funds, the seized collateral is swapped back
+//! to the debt denom through the configured DEX route and the loan is repaid from it.
+
+pub mod contract;
+mod error;
+pub mod helpers;
+pub mod integration_tests;
+pub mod market;
+pub mod msg;
+pub mod state;
+
+pub use crate::error::ContractError;
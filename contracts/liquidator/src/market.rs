@@ -0,0 +1,15 @@
+//! Subset of the money market interface used by the liquidator.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    /// Repays the attached funds on behalf of `position_owner`,
+    /// collateral of `collateral_denom` is sent back in return
+    Liquidate {
+        position_owner: String,
+        collateral_denom: String,
+    },
+}
@@ -0,0 +1,56 @@
+use cosmwasm_schema::QueryResponses;
+use cosmwasm_std::Coin;
+use cw_flash_loan_borrower::swap::SwapStep;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InstantiateMsg {
+    pub cw_gateway_contract_addr: String,
+    #[serde(default)]
+    pub swap_routes: Vec<SwapRoute>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateMsg {}
+
+/// Swaps selling `offer_denom`, the last step asking for the debt denom
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SwapRoute {
+    pub offer_denom: String,
+    pub route: Vec<SwapStep>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    /// Flash-borrows `debt_asset` to repay it on `market` on behalf of `position_owner`,
+    /// then sells the seized collateral to repay the loan. Owner only
+    Liquidate {
+        market: String,
+        position_owner: String,
+        debt_asset: Coin,
+        collateral_denom: String,
+    },
+    OnFlashLoanProvided {},
+    /// Sells the seized collateral, only callable by the contract itself
+    SwapCollateral {},
+    /// Sets how seized `offer_denom` collateral gets sold, owner only
+    SetSwapRoute {
+        offer_denom: String,
+        route: Vec<SwapStep>,
+    },
+    /// Sends accumulated profits out of the contract, owner only
+    Withdraw {
+        amount: Coin,
+        recipient: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, QueryResponses)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    /// Returns the route selling `offer_denom`, if any
+    #[returns(Option<Vec<SwapStep>>)]
+    SwapRoute { offer_denom: String },
+}
@@ -0,0 +1,32 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{Addr, Coin, Uint128};
+use cw_flash_loan_borrower::swap::SwapStep;
+use cw_storage_plus::{Item, Map};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Config {
+    /// The only address allowed to liquidate, configure routes and withdraw profits
+    pub owner: Addr,
+    pub cw_gateway_contract_addr: Addr,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Liquidation {
+    pub market: Addr,
+    pub position_owner: Addr,
+    /// Debt repaid on behalf of the position owner, borrowed from the gateway
+    pub debt_asset: Coin,
+    pub collateral_denom: String,
+    /// Balance of the collateral denom before the liquidation,
+    /// anything above it once the market paid out is the seized collateral
+    pub initial_collateral_balance: Uint128,
+}
+
+pub const CONFIG: Item<Config> = Item::new("config");
+
+/// Swap route selling seized collateral, keyed by collateral denom
+pub const SWAP_ROUTES: Map<&str, Vec<SwapStep>> = Map::new("swap_routes");
+
+pub const LIQUIDATION: Item<Liquidation> = Item::new("liquidation");
@@ -0,0 +1,427 @@
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
+use cosmwasm_std::{
+    coin, to_json_binary, BankMsg, Binary, Decimal, Deps, DepsMut, Env, MessageInfo, Response,
+    StdError, StdResult, Uint128,
+};
+use cw2::set_contract_version;
+
+use crate::error::ContractError;
+use crate::msg::{
+    Asset, AssetInfo, ExecuteMsg, InstantiateMsg, PoolResponse, QueryMsg, SimulationResponse,
+};
+use crate::state::{Config, CONFIG};
+
+// version info for migration info
+const CONTRACT_NAME: &str = "crates.io:cw-flash-loan-mock-amm";
+const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn instantiate(
+    deps: DepsMut,
+    _env: Env,
+    _info: MessageInfo,
+    msg: InstantiateMsg,
+) -> Result<Response, ContractError> {
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    if msg.denoms[0] == msg.denoms[1] {
+        return Err(ContractError::InvalidPair {});
+    }
+
+    CONFIG.save(
+        deps.storage,
+        &Config {
+            denoms: msg.denoms,
+            commission_rate: msg.commission_rate,
+        },
+    )?;
+
+    Ok(Response::new().add_attribute("method", "instantiate"))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    match msg {
+        ExecuteMsg::ProvideLiquidity {} => execute_provide_liquidity(deps, info),
+        ExecuteMsg::Swap {
+            offer_asset,
+            ask_asset_info,
+            belief_price,
+            max_spread,
+            to,
+        } => execute_swap(
+            deps,
+            env,
+            info,
+            offer_asset,
+            ask_asset_info,
+            belief_price,
+            max_spread,
+            to,
+        ),
+    }
+}
+
+fn execute_provide_liquidity(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    // the funds are already part of the reserves, just make sure they belong to the pair
+    for fund in info.funds.iter() {
+        if !config.denoms.contains(&fund.denom) {
+            return Err(ContractError::AssetNotInPool {
+                denom: fund.denom.clone(),
+            });
+        }
+    }
+
+    Ok(Response::new().add_attributes(vec![
+        ("module", "mock_amm"),
+        ("action", "execute_provide_liquidity"),
+    ]))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn execute_swap(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    offer_asset: Asset,
+    ask_asset_info: Option<AssetInfo>,
+    belief_price: Option<Decimal>,
+    max_spread: Option<Decimal>,
+    to: Option<String>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    let AssetInfo::NativeToken { denom: offer_denom } = offer_asset.info.clone();
+    let ask_denom = ask_denom(&config, &offer_denom, ask_asset_info)?;
+
+    let offer_sent = info
+        .funds
+        .iter()
+        .any(|fund| fund.denom == offer_denom && fund.amount == offer_asset.amount);
+
+    if !offer_sent || offer_asset.amount.is_zero() {
+        return Err(ContractError::OfferedAssetNotSent {});
+    }
+
+    // offered funds have been credited to the pool already
+    let offer_pool = deps
+        .querier
+        .query_balance(env.contract.address.clone(), offer_denom.as_str())?
+        .amount
+        .checked_sub(offer_asset.amount)
+        .map_err(StdError::from)?;
+    let ask_pool = deps
+        .querier
+        .query_balance(env.contract.address, ask_denom.as_str())?
+        .amount;
+
+    let simulation = compute_swap(
+        offer_pool,
+        ask_pool,
+        offer_asset.amount,
+        config.commission_rate,
+    )?;
+
+    assert_max_spread(
+        belief_price,
+        max_spread,
+        offer_asset.amount,
+        simulation.return_amount,
+        simulation.spread_amount,
+    )?;
+
+    let receiver = match to {
+        Some(to) => deps.api.addr_validate(&to)?,
+        None => info.sender,
+    };
+
+    let return_asset = coin(simulation.return_amount.u128(), ask_denom.as_str());
+
+    println!(
+        "[Mock AMM: execute_swap] offer = {}{} | return = {:?}",
+        offer_asset.amount, &offer_denom, &return_asset
+    );
+
+    let msg = BankMsg::Send {
+        to_address: receiver.to_string(),
+        amount: vec![return_asset],
+    };
+
+    Ok(Response::new().add_message(msg).add_attributes(vec![
+        ("module", "mock_amm"),
+        ("action", "execute_swap"),
+        ("offer_asset", offer_denom.as_str()),
+        ("ask_asset", ask_denom.as_str()),
+        ("offer_amount", &offer_asset.amount.to_string()),
+        ("return_amount", &simulation.return_amount.to_string()),
+    ]))
+}
+
+/// Finds the other side of the pair for the offered denom.
+fn ask_denom(
+    config: &Config,
+    offer_denom: &str,
+    ask_asset_info: Option<AssetInfo>,
+) -> Result<String, ContractError> {
+    let ask_denom = if offer_denom == config.denoms[0] {
+        config.denoms[1].clone()
+    } else if offer_denom == config.denoms[1] {
+        config.denoms[0].clone()
+    } else {
+        return Err(ContractError::AssetNotInPool {
+            denom: offer_denom.to_string(),
+        });
+    };
+
+    match ask_asset_info {
+        Some(AssetInfo::NativeToken { denom }) if denom != ask_denom => {
+            Err(ContractError::AssetNotInPool { denom })
+        }
+        _ => Ok(ask_denom),
+    }
+}
+
+/// Constant product swap, the commission is taken from the return amount.
+fn compute_swap(
+    offer_pool: Uint128,
+    ask_pool: Uint128,
+    offer_amount: Uint128,
+    commission_rate: Decimal,
+) -> Result<SimulationResponse, ContractError> {
+    if offer_pool.is_zero() || ask_pool.is_zero() {
+        return Err(ContractError::EmptyPool {});
+    }
+
+    let return_amount = ask_pool.multiply_ratio(offer_amount, offer_pool + offer_amount);
+    let spread_amount = ask_pool
+        .multiply_ratio(offer_amount, offer_pool)
+        .saturating_sub(return_amount);
+    let commission_amount = return_amount * commission_rate;
+
+    Ok(SimulationResponse {
+        return_amount: return_amount - commission_amount,
+        spread_amount,
+        commission_amount,
+    })
+}
+
+/// Same semantics as Astroport: the spread is only checked when `max_spread` is given.
+fn assert_max_spread(
+    belief_price: Option<Decimal>,
+    max_spread: Option<Decimal>,
+    offer_amount: Uint128,
+    return_amount: Uint128,
+    spread_amount: Uint128,
+) -> Result<(), ContractError> {
+    let max_spread = match max_spread {
+        Some(max_spread) => max_spread,
+        None => return Ok(()),
+    };
+
+    let spread = match belief_price.filter(|belief_price| !belief_price.is_zero()) {
+        Some(belief_price) => {
+            let expected_return = offer_amount * (Decimal::one() / belief_price);
+            if expected_return.is_zero() {
+                return Ok(());
+            }
+            Decimal::from_ratio(
+                expected_return.saturating_sub(return_amount),
+                expected_return,
+            )
+        }
+        None => Decimal::from_ratio(spread_amount, return_amount + spread_amount),
+    };
+
+    if spread > max_spread {
+        return Err(ContractError::MaxSpreadExceeded { spread, max_spread });
+    }
+
+    Ok(())
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Pool {} => to_json_binary(&query_pool(deps, env)?),
+        QueryMsg::Simulation {
+            offer_asset,
+            ask_asset_info,
+        } => to_json_binary(&query_simulation(deps, env, offer_asset, ask_asset_info)?),
+    }
+}
+
+fn query_pool(deps: Deps, env: Env) -> StdResult<PoolResponse> {
+    let config = CONFIG.load(deps.storage)?;
+
+    let assets = config
+        .denoms
+        .iter()
+        .map(|denom| {
+            let balance = deps
+                .querier
+                .query_balance(env.contract.address.clone(), denom.as_str())?;
+
+            Ok(Asset {
+                info: AssetInfo::NativeToken {
+                    denom: balance.denom,
+                },
+                amount: balance.amount,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(PoolResponse { assets })
+}
+
+fn query_simulation(
+    deps: Deps,
+    env: Env,
+    offer_asset: Asset,
+    ask_asset_info: Option<AssetInfo>,
+) -> StdResult<SimulationResponse> {
+    let config = CONFIG.load(deps.storage)?;
+
+    let AssetInfo::NativeToken { denom: offer_denom } = offer_asset.info;
+    let ask_denom = ask_denom(&config, &offer_denom, ask_asset_info)
+        .map_err(|err| StdError::generic_err(err.to_string()))?;
+
+    let offer_pool = deps
+        .querier
+        .query_balance(env.contract.address.clone(), offer_denom.as_str())?
+        .amount;
+    let ask_pool = deps
+        .querier
+        .query_balance(env.contract.address, ask_denom.as_str())?
+        .amount;
+
+    compute_swap(
+        offer_pool,
+        ask_pool,
+        offer_asset.amount,
+        config.commission_rate,
+    )
+    .map_err(|err| StdError::generic_err(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::{
+        coins, from_json,
+        testing::{mock_dependencies_with_balance, mock_env, mock_info},
+        CosmosMsg, SubMsg,
+    };
+
+    fn native(denom: &str, amount: u128) -> Asset {
+        Asset {
+            info: AssetInfo::NativeToken {
+                denom: denom.to_string(),
+            },
+            amount: Uint128::new(amount),
+        }
+    }
+
+    #[test]
+    fn swap_follows_constant_product() {
+        let mut deps =
+            mock_dependencies_with_balance(&[coin(100_000, "uluna"), coin(200_000, "uusd")]);
+
+        let msg = InstantiateMsg {
+            denoms: ["uluna".to_string(), "uusd".to_string()],
+            commission_rate: Decimal::zero(),
+        };
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let simulation: SimulationResponse = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::Simulation {
+                    offer_asset: native("uluna", 1_000),
+                    ask_asset_info: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        // 200_000 * 1_000 / 101_000
+        assert_eq!(simulation.return_amount, Uint128::new(1_980));
+        assert_eq!(simulation.spread_amount, Uint128::new(20));
+
+        // the offered funds are credited before the contract gets executed
+        deps.querier.update_balance(
+            mock_env().contract.address,
+            vec![coin(101_000, "uluna"), coin(200_000, "uusd")],
+        );
+
+        let response = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("trader", &coins(1_000, "uluna")),
+            ExecuteMsg::Swap {
+                offer_asset: native("uluna", 1_000),
+                ask_asset_info: None,
+                belief_price: None,
+                max_spread: None,
+                to: None,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            response.messages,
+            vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+                to_address: "trader".to_string(),
+                amount: coins(1_980, "uusd"),
+            }))]
+        );
+    }
+
+    #[test]
+    fn swap_rejects_unknown_denoms_and_missing_funds() {
+        let mut deps =
+            mock_dependencies_with_balance(&[coin(100_000, "uluna"), coin(200_000, "uusd")]);
+
+        let msg = InstantiateMsg {
+            denoms: ["uluna".to_string(), "uusd".to_string()],
+            commission_rate: Decimal::permille(3),
+        };
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let swap = |asset: Asset| ExecuteMsg::Swap {
+            offer_asset: asset,
+            ask_asset_info: None,
+            belief_price: None,
+            max_spread: None,
+            to: None,
+        };
+
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("trader", &coins(1_000, "uatom")),
+            swap(native("uatom", 1_000)),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::AssetNotInPool { .. }));
+
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("trader", &[]),
+            swap(native("uluna", 1_000)),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::OfferedAssetNotSent {}));
+    }
+}
@@ -0,0 +1,26 @@
+use cosmwasm_std::{Decimal, StdError};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ContractError {
+    #[error("{0}")]
+    Std(#[from] StdError),
+
+    #[error("Pool must be made of two different native denoms")]
+    InvalidPair {},
+
+    #[error("Asset {denom} is not part of the pool")]
+    AssetNotInPool { denom: String },
+
+    #[error("Offered asset must be sent along with the swap message")]
+    OfferedAssetNotSent {},
+
+    #[error("Pool has no liquidity")]
+    EmptyPool {},
+
+    #[error("Spread of {spread} exceeds the maximum of {max_spread}")]
+    MaxSpreadExceeded {
+        spread: Decimal,
+        max_spread: Decimal,
+    },
+}
@@ -0,0 +1,11 @@
+//! A minimal constant-product pool used to exercise flash loan strategies in tests.
+//!
+//! It only supports native pairs and speaks a subset of the Astroport pair interface,
+//! so strategies built against it can be pointed at a real DEX later on.
+
+pub mod contract;
+mod error;
+pub mod msg;
+pub mod state;
+
+pub use crate::error::ContractError;
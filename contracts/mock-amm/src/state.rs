@@ -0,0 +1,14 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::Decimal;
+use cw_storage_plus::Item;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Config {
+    pub denoms: [String; 2],
+    /// Share of the return amount kept by the pool on every swap
+    pub commission_rate: Decimal,
+}
+
+pub const CONFIG: Item<Config> = Item::new("config");
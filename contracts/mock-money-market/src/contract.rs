@@ -0,0 +1,443 @@
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
+use cosmwasm_std::{
+    coin, to_json_binary, Addr, BankMsg, Binary, Coin, Decimal, Deps, DepsMut, Env, MessageInfo,
+    Order, Response, StdResult, Storage, Uint128,
+};
+use cw2::set_contract_version;
+
+use crate::error::ContractError;
+use crate::msg::{ExecuteMsg, InstantiateMsg, PositionResponse, QueryMsg};
+use crate::state::{Config, COLLATERAL, CONFIG, DEBT, PRICES};
+
+// version info for migration info
+const CONTRACT_NAME: &str = "crates.io:cw-flash-loan-mock-money-market";
+const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn instantiate(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    msg: InstantiateMsg,
+) -> Result<Response, ContractError> {
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    CONFIG.save(
+        deps.storage,
+        &Config {
+            owner: info.sender,
+            liquidation_threshold: msg.liquidation_threshold,
+            liquidation_bonus: msg.liquidation_bonus,
+        },
+    )?;
+
+    for denom_price in msg.prices {
+        PRICES.save(deps.storage, &denom_price.denom, &denom_price.price)?;
+    }
+
+    // funds sent along are the liquidity borrowers can draw from
+    Ok(Response::new().add_attribute("method", "instantiate"))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn execute(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    match msg {
+        ExecuteMsg::Deposit {} => execute_deposit(deps, info),
+        ExecuteMsg::Withdraw { asset } => execute_withdraw(deps, info, asset),
+        ExecuteMsg::Borrow { asset } => execute_borrow(deps, info, asset),
+        ExecuteMsg::Repay { on_behalf_of } => execute_repay(deps, info, on_behalf_of),
+        ExecuteMsg::Liquidate {
+            position_owner,
+            collateral_denom,
+        } => execute_liquidate(deps, info, position_owner, collateral_denom),
+        ExecuteMsg::SetPrice { denom, price } => execute_set_price(deps, info, denom, price),
+    }
+}
+
+fn execute_deposit(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    let asset = one_coin(&info)?;
+
+    COLLATERAL.update(
+        deps.storage,
+        (&info.sender, &asset.denom),
+        |collateral| -> StdResult<_> { Ok(collateral.unwrap_or_default() + asset.amount) },
+    )?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("module", "mock_money_market"),
+        ("action", "execute_deposit"),
+    ]))
+}
+
+fn execute_withdraw(
+    deps: DepsMut,
+    info: MessageInfo,
+    asset: Coin,
+) -> Result<Response, ContractError> {
+    let collateral = COLLATERAL
+        .may_load(deps.storage, (&info.sender, &asset.denom))?
+        .unwrap_or_default();
+
+    let collateral = collateral.checked_sub(asset.amount).map_err(|_| {
+        ContractError::InsufficientCollateral {
+            denom: asset.denom.clone(),
+        }
+    })?;
+    COLLATERAL.save(deps.storage, (&info.sender, &asset.denom), &collateral)?;
+
+    if !is_healthy(deps.storage, &info.sender)? {
+        return Err(ContractError::UnhealthyPosition {});
+    }
+
+    Ok(Response::new()
+        .add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: vec![asset],
+        })
+        .add_attributes(vec![
+            ("module", "mock_money_market"),
+            ("action", "execute_withdraw"),
+        ]))
+}
+
+fn execute_borrow(
+    deps: DepsMut,
+    info: MessageInfo,
+    asset: Coin,
+) -> Result<Response, ContractError> {
+    DEBT.update(
+        deps.storage,
+        (&info.sender, &asset.denom),
+        |debt| -> StdResult<_> { Ok(debt.unwrap_or_default() + asset.amount) },
+    )?;
+
+    if !is_healthy(deps.storage, &info.sender)? {
+        return Err(ContractError::UnhealthyPosition {});
+    }
+
+    Ok(Response::new()
+        .add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: vec![asset],
+        })
+        .add_attributes(vec![
+            ("module", "mock_money_market"),
+            ("action", "execute_borrow"),
+        ]))
+}
+
+fn execute_repay(
+    deps: DepsMut,
+    info: MessageInfo,
+    on_behalf_of: Option<String>,
+) -> Result<Response, ContractError> {
+    let asset = one_coin(&info)?;
+
+    let position_owner = match on_behalf_of {
+        Some(owner) => deps.api.addr_validate(&owner)?,
+        None => info.sender,
+    };
+
+    reduce_debt(deps.storage, &position_owner, &asset)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("module", "mock_money_market"),
+        ("action", "execute_repay"),
+    ]))
+}
+
+fn execute_liquidate(
+    deps: DepsMut,
+    info: MessageInfo,
+    position_owner: String,
+    collateral_denom: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let repaid = one_coin(&info)?;
+    let position_owner = deps.api.addr_validate(&position_owner)?;
+
+    if is_healthy(deps.storage, &position_owner)? {
+        return Err(ContractError::HealthyPosition {});
+    }
+
+    reduce_debt(deps.storage, &position_owner, &repaid)?;
+
+    // the liquidator gets the repaid value back in collateral, plus the bonus
+    let repaid_value = value(deps.storage, &repaid)?;
+    let seized_value = repaid_value * (Decimal::one() + config.liquidation_bonus);
+    let collateral_price = price(deps.storage, &collateral_denom)?;
+    let seized_amount = (seized_value / collateral_price).to_uint_floor();
+
+    let collateral = COLLATERAL
+        .may_load(deps.storage, (&position_owner, &collateral_denom))?
+        .unwrap_or_default();
+    let seized_amount = seized_amount.min(collateral);
+
+    COLLATERAL.save(
+        deps.storage,
+        (&position_owner, &collateral_denom),
+        &(collateral - seized_amount),
+    )?;
+
+    let seized = coin(seized_amount.u128(), collateral_denom);
+
+    println!(
+        "[Mock Money Market: execute_liquidate] repaid = {:?} | seized = {:?}",
+        &repaid, &seized
+    );
+
+    Ok(Response::new()
+        .add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: vec![seized],
+        })
+        .add_attributes(vec![
+            ("module", "mock_money_market"),
+            ("action", "execute_liquidate"),
+            ("position_owner", position_owner.as_str()),
+        ]))
+}
+
+fn execute_set_price(
+    deps: DepsMut,
+    info: MessageInfo,
+    denom: String,
+    price: Decimal,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    if info.sender != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    PRICES.save(deps.storage, &denom, &price)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("module", "mock_money_market"),
+        ("action", "execute_set_price"),
+    ]))
+}
+
+fn one_coin(info: &MessageInfo) -> Result<Coin, ContractError> {
+    match info.funds.as_slice() {
+        [asset] if !asset.amount.is_zero() => Ok(asset.clone()),
+        _ => Err(ContractError::InvalidFunds {}),
+    }
+}
+
+fn reduce_debt(
+    storage: &mut dyn Storage,
+    position_owner: &Addr,
+    repaid: &Coin,
+) -> Result<(), ContractError> {
+    let debt = DEBT
+        .may_load(storage, (position_owner, &repaid.denom))?
+        .unwrap_or_default();
+
+    let debt = debt
+        .checked_sub(repaid.amount)
+        .map_err(|_| ContractError::RepayExceedsDebt {
+            debt: coin(debt.u128(), repaid.denom.as_str()).to_string(),
+        })?;
+
+    DEBT.save(storage, (position_owner, &repaid.denom), &debt)?;
+
+    Ok(())
+}
+
+fn price(storage: &dyn Storage, denom: &str) -> Result<Decimal, ContractError> {
+    PRICES
+        .may_load(storage, denom)?
+        .ok_or_else(|| ContractError::PriceNotFound {
+            denom: denom.to_string(),
+        })
+}
+
+fn value(storage: &dyn Storage, asset: &Coin) -> Result<Decimal, ContractError> {
+    Ok(Decimal::from_ratio(asset.amount, 1u128) * price(storage, &asset.denom)?)
+}
+
+fn coins_of(
+    storage: &dyn Storage,
+    map: &cw_storage_plus::Map<(&Addr, &str), Uint128>,
+    owner: &Addr,
+) -> StdResult<Vec<Coin>> {
+    map.prefix(owner)
+        .range(storage, None, None, Order::Ascending)
+        .filter(|item| !matches!(item, Ok((_, amount)) if amount.is_zero()))
+        .map(|item| item.map(|(denom, amount)| coin(amount.u128(), denom)))
+        .collect()
+}
+
+/// Borrow limit and debt value of a position.
+fn position_values(
+    storage: &dyn Storage,
+    owner: &Addr,
+) -> Result<(Decimal, Decimal), ContractError> {
+    let config = CONFIG.load(storage)?;
+
+    let mut collateral_value = Decimal::zero();
+    for asset in coins_of(storage, &COLLATERAL, owner)? {
+        collateral_value += value(storage, &asset)?;
+    }
+
+    let mut debt_value = Decimal::zero();
+    for asset in coins_of(storage, &DEBT, owner)? {
+        debt_value += value(storage, &asset)?;
+    }
+
+    Ok((collateral_value * config.liquidation_threshold, debt_value))
+}
+
+fn is_healthy(storage: &dyn Storage, owner: &Addr) -> Result<bool, ContractError> {
+    let (borrow_limit, debt_value) = position_values(storage, owner)?;
+
+    Ok(debt_value <= borrow_limit)
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Position { owner } => to_json_binary(&query_position(deps, owner)?),
+    }
+}
+
+fn query_position(deps: Deps, owner: String) -> StdResult<PositionResponse> {
+    let owner = deps.api.addr_validate(&owner)?;
+
+    let (borrow_limit, debt_value) = position_values(deps.storage, &owner)
+        .map_err(|err| cosmwasm_std::StdError::generic_err(err.to_string()))?;
+
+    Ok(PositionResponse {
+        collateral: coins_of(deps.storage, &COLLATERAL, &owner)?,
+        debt: coins_of(deps.storage, &DEBT, &owner)?,
+        borrow_limit,
+        debt_value,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::msg::DenomPrice;
+    use cosmwasm_std::{
+        coins,
+        testing::{mock_dependencies, mock_env, mock_info},
+    };
+
+    fn instantiate_market(deps: DepsMut) {
+        let msg = InstantiateMsg {
+            liquidation_threshold: Decimal::percent(80),
+            liquidation_bonus: Decimal::percent(10),
+            prices: vec![
+                DenomPrice {
+                    denom: "uluna".to_string(),
+                    price: Decimal::percent(300),
+                },
+                DenomPrice {
+                    denom: "uusd".to_string(),
+                    price: Decimal::one(),
+                },
+            ],
+        };
+        instantiate(deps, mock_env(), mock_info("owner", &[]), msg).unwrap();
+    }
+
+    #[test]
+    fn position_becomes_liquidatable_when_collateral_price_drops() {
+        let mut deps = mock_dependencies();
+        instantiate_market(deps.as_mut());
+
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("borrower", &coins(1_000, "uluna")),
+            ExecuteMsg::Deposit {},
+        )
+        .unwrap();
+
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("greedy", &coins(1_000, "uluna")),
+            ExecuteMsg::Deposit {},
+        )
+        .unwrap();
+
+        // 1_000 uluna are worth 3_000 uusd, 2_400 of which can be borrowed
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("greedy", &[]),
+            ExecuteMsg::Borrow {
+                asset: coin(2_401, "uusd"),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::UnhealthyPosition {}));
+
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("borrower", &[]),
+            ExecuteMsg::Borrow {
+                asset: coin(2_000, "uusd"),
+            },
+        )
+        .unwrap();
+
+        let liquidate = ExecuteMsg::Liquidate {
+            position_owner: "borrower".to_string(),
+            collateral_denom: "uluna".to_string(),
+        };
+
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("liquidator", &coins(1_000, "uusd")),
+            liquidate.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::HealthyPosition {}));
+
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            ExecuteMsg::SetPrice {
+                denom: "uluna".to_string(),
+                price: Decimal::percent(200),
+            },
+        )
+        .unwrap();
+
+        let response = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("liquidator", &coins(1_000, "uusd")),
+            liquidate,
+        )
+        .unwrap();
+
+        // 1_000 uusd repaid, 1_100 uusd worth of uluna seized
+        assert_eq!(
+            response.messages[0].msg,
+            BankMsg::Send {
+                to_address: "liquidator".to_string(),
+                amount: coins(550, "uluna"),
+            }
+            .into()
+        );
+
+        let position = query_position(deps.as_ref(), "borrower".to_string()).unwrap();
+        assert_eq!(position.collateral, coins(450, "uluna"));
+        assert_eq!(position.debt, coins(1_000, "uusd"));
+    }
+}
@@ -0,0 +1,29 @@
+use cosmwasm_std::StdError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ContractError {
+    #[error("{0}")]
+    Std(#[from] StdError),
+
+    #[error("Unauthorized")]
+    Unauthorized {},
+
+    #[error("No price set for {denom}")]
+    PriceNotFound { denom: String },
+
+    #[error("Exactly one coin must be sent")]
+    InvalidFunds {},
+
+    #[error("Position would become unhealthy")]
+    UnhealthyPosition {},
+
+    #[error("Position is healthy and cannot be liquidated")]
+    HealthyPosition {},
+
+    #[error("Repaid amount exceeds the debt of {debt}")]
+    RepayExceedsDebt { debt: String },
+
+    #[error("Not enough {denom} in the position")]
+    InsufficientCollateral { denom: String },
+}
@@ -0,0 +1,11 @@
+//! A minimal money market used to exercise liquidation and refinancing strategies in tests.
+//!
+//! Positions are kept per owner and valued with prices set by the market owner,
+//! so tests can make a position unhealthy by simply moving a price.
+
+pub mod contract;
+mod error;
+pub mod msg;
+pub mod state;
+
+pub use crate::error::ContractError;
@@ -0,0 +1,52 @@
+use cosmwasm_std::{Coin, Decimal};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InstantiateMsg {
+    pub liquidation_threshold: Decimal,
+    pub liquidation_bonus: Decimal,
+    pub prices: Vec<DenomPrice>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DenomPrice {
+    pub denom: String,
+    pub price: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    /// Adds the attached funds to the sender's collateral
+    Deposit {},
+    /// Takes collateral out of the sender's position
+    Withdraw { asset: Coin },
+    /// Borrows against the sender's collateral
+    Borrow { asset: Coin },
+    /// Repays the attached funds, on behalf of the sender unless told otherwise
+    Repay { on_behalf_of: Option<String> },
+    /// Repays the attached funds on behalf of an unhealthy position,
+    /// the sender gets its collateral with a bonus in return
+    Liquidate {
+        position_owner: String,
+        collateral_denom: String,
+    },
+    /// Owner only
+    SetPrice { denom: String, price: Decimal },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    Position { owner: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PositionResponse {
+    pub collateral: Vec<Coin>,
+    pub debt: Vec<Coin>,
+    /// Collateral value weighted by the liquidation threshold
+    pub borrow_limit: Decimal,
+    pub debt_value: Decimal,
+}
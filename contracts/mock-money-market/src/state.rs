@@ -0,0 +1,26 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{Addr, Decimal, Uint128};
+use cw_storage_plus::{Item, Map};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Config {
+    /// Allowed to move prices around
+    pub owner: Addr,
+    /// Share of the collateral value that can be borrowed against
+    pub liquidation_threshold: Decimal,
+    /// Extra collateral given to liquidators on top of the repaid debt value
+    pub liquidation_bonus: Decimal,
+}
+
+pub const CONFIG: Item<Config> = Item::new("config");
+
+/// Price of a denom, expressed in a common quote unit
+pub const PRICES: Map<&str, Decimal> = Map::new("prices");
+
+/// Collateral deposited by `(owner, denom)`
+pub const COLLATERAL: Map<(&Addr, &str), Uint128> = Map::new("collateral");
+
+/// Debt owed by `(owner, denom)`
+pub const DEBT: Map<(&Addr, &str), Uint128> = Map::new("debt");
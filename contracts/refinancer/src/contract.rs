@@ -0,0 +1,306 @@
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
+use cosmwasm_std::{
+    to_json_binary, wasm_execute, BankMsg, Binary, Coin, CosmosMsg, Deps, DepsMut, Env,
+    MessageInfo, Response, StdResult,
+};
+
+use cw2::set_contract_version;
+use cw_flash_loan_borrower::swap::{swap_route_msgs, SwapStep};
+use cw_flash_loan_gateway::helpers::{Contract as FlashLoanGateway, RequestFlashLoanProps};
+
+use crate::{
+    error::ContractError,
+    market,
+    msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg},
+    state::{Config, Refinancing, CONFIG, REFINANCING},
+};
+
+// version info for migration info
+const CONTRACT_NAME: &str = "crates.io:cw-flash-loan-refinancer";
+const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn instantiate(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    msg: InstantiateMsg,
+) -> Result<Response, ContractError> {
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    let cw_gateway_contract_addr = deps.api.addr_validate(&msg.cw_gateway_contract_addr)?;
+
+    CONFIG.save(
+        deps.storage,
+        &Config {
+            owner: info.sender,
+            cw_gateway_contract_addr,
+        },
+    )?;
+
+    Ok(Response::new().add_attribute("method", "instantiate"))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    match msg {
+        ExecuteMsg::Refinance {
+            from_market,
+            to_market,
+            debt,
+            collateral,
+            collateral_swap_route,
+        } => execute_refinance(
+            deps,
+            info,
+            from_market,
+            to_market,
+            debt,
+            collateral,
+            collateral_swap_route,
+        ),
+        ExecuteMsg::OnFlashLoanProvided {} => execute_on_flash_loan_provided(deps, env, info),
+        ExecuteMsg::Deposit { market } => execute_deposit(deps, info, market),
+        ExecuteMsg::Borrow { market, asset } => execute_borrow(deps, info, market, asset),
+        ExecuteMsg::Withdraw { amount, recipient } => {
+            execute_withdraw(deps, info, amount, recipient)
+        }
+    }
+}
+
+/// Handler initiating a refinancing, funded by a flash loan of the debt
+fn execute_refinance(
+    deps: DepsMut,
+    info: MessageInfo,
+    from_market: String,
+    to_market: String,
+    debt: Coin,
+    collateral: Coin,
+    collateral_swap_route: Vec<SwapStep>,
+) -> Result<Response, ContractError> {
+    println!(
+        "[Refinancer: execute_refinance]: moving {:?} of debt and {:?} of collateral from {} to {}",
+        &debt, &collateral, &from_market, &to_market
+    );
+
+    let config = CONFIG.load(deps.storage)?;
+
+    if info.sender != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // An unknown dex would only be caught swapping the collateral, with the debt already repaid
+    for step in &collateral_swap_route {
+        step.dex.adapter(deps.api)?;
+    }
+
+    REFINANCING.save(
+        deps.storage,
+        &Refinancing {
+            from_market: deps.api.addr_validate(&from_market)?,
+            to_market: deps.api.addr_validate(&to_market)?,
+            debt: debt.clone(),
+            collateral,
+            collateral_swap_route,
+        },
+    )?;
+
+    let msg = FlashLoanGateway(config.cw_gateway_contract_addr).request_flash_loan(
+        RequestFlashLoanProps {
+            asset: debt,
+            on_flash_loan_provided_hook: &ExecuteMsg::OnFlashLoanProvided {},
+            referrer: None,
+        },
+    )?;
+
+    Ok(Response::new().add_message(msg).add_attributes(vec![
+        ("module", "refinancer"),
+        ("action", "execute_refinance"),
+    ]))
+}
+
+/// Handler utilising the flash loan: runs the refinancing plan and repays the gateway.
+fn execute_on_flash_loan_provided(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    // Anyone else could replay the saved plan, moving the position with the refinancer's own funds
+    if info.sender != config.cw_gateway_contract_addr {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let Refinancing {
+        from_market,
+        to_market,
+        debt,
+        collateral,
+        collateral_swap_route,
+    } = REFINANCING.load(deps.storage)?;
+    REFINANCING.remove(deps.storage);
+
+    let mut msgs: Vec<CosmosMsg> = vec![
+        wasm_execute(
+            from_market.to_string(),
+            &market::ExecuteMsg::Repay { on_behalf_of: None },
+            vec![debt],
+        )?
+        .into(),
+        wasm_execute(
+            from_market.to_string(),
+            &market::ExecuteMsg::Withdraw {
+                asset: collateral.clone(),
+            },
+            vec![],
+        )?
+        .into(),
+    ];
+
+    let new_collateral = if collateral_swap_route.is_empty() {
+        collateral
+    } else {
+        let (swap_msgs, swap_return) = swap_route_msgs(
+            deps.api,
+            &deps.querier,
+            &env.contract.address,
+            collateral,
+            &collateral_swap_route,
+        )?;
+        msgs.extend(swap_msgs);
+
+        swap_return
+    };
+
+    println!(
+        "[Refinancer: execute_on_flash_loan_provided]: depositing {:?}",
+        &new_collateral
+    );
+
+    let flash_loan_gateway = FlashLoanGateway(config.cw_gateway_contract_addr);
+
+    // The new debt also covers the flash loan fees
+    let total_repayment =
+        flash_loan_gateway.get_debt_remaining(&deps.querier, env.contract.address)?;
+
+    msgs.push(
+        wasm_execute(
+            to_market.to_string(),
+            &market::ExecuteMsg::Deposit {},
+            vec![new_collateral],
+        )?
+        .into(),
+    );
+    msgs.push(
+        wasm_execute(
+            to_market.to_string(),
+            &market::ExecuteMsg::Borrow {
+                asset: total_repayment.clone(),
+            },
+            vec![],
+        )?
+        .into(),
+    );
+    msgs.push(flash_loan_gateway.repay_flash_loan(total_repayment)?);
+
+    Ok(Response::new().add_messages(msgs).add_attributes(vec![
+        ("module", "refinancer"),
+        ("action", "execute_on_flash_loan_provided"),
+        ("from_market", from_market.as_str()),
+        ("to_market", to_market.as_str()),
+    ]))
+}
+
+fn execute_deposit(
+    deps: DepsMut,
+    info: MessageInfo,
+    market: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    if info.sender != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let msg = wasm_execute(
+        deps.api.addr_validate(&market)?,
+        &market::ExecuteMsg::Deposit {},
+        info.funds,
+    )?;
+
+    Ok(Response::new().add_message(msg).add_attributes(vec![
+        ("module", "refinancer"),
+        ("action", "execute_deposit"),
+    ]))
+}
+
+fn execute_borrow(
+    deps: DepsMut,
+    info: MessageInfo,
+    market: String,
+    asset: Coin,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    if info.sender != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let msg = wasm_execute(
+        deps.api.addr_validate(&market)?,
+        &market::ExecuteMsg::Borrow { asset },
+        vec![],
+    )?;
+
+    Ok(Response::new()
+        .add_message(msg)
+        .add_attributes(vec![("module", "refinancer"), ("action", "execute_borrow")]))
+}
+
+/// Handler sending funds, e.g. borrowed ones, out of the refinancer contract.
+fn execute_withdraw(
+    deps: DepsMut,
+    info: MessageInfo,
+    amount: Coin,
+    recipient: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    if info.sender != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let recipient = deps.api.addr_validate(&recipient)?;
+
+    let msg = BankMsg::Send {
+        to_address: recipient.to_string(),
+        amount: vec![amount],
+    };
+
+    Ok(Response::new().add_message(msg).add_attributes(vec![
+        ("module", "refinancer"),
+        ("action", "execute_withdraw"),
+        ("recipient", recipient.as_str()),
+    ]))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::new().add_attributes(vec![("module", "refinancer"), ("action", "migrate")]))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Config {} => to_json_binary(&CONFIG.load(deps.storage)?),
+    }
+}
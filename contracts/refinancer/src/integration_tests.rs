@@ -0,0 +1,307 @@
+#[cfg(test)]
+mod tests {
+    use crate::helpers::Contract as CwRefinancerContract;
+    use crate::msg::{ExecuteMsg, InstantiateMsg};
+    use cosmwasm_std::{coin, coins, Addr, Coin, Decimal, Empty, Uint128};
+    use cw_flash_loan_borrower::swap::{Dex, SwapStep};
+    use cw_flash_loan_gateway::helpers::Contract as CwGatewayContract;
+    use cw_flash_loan_mock_amm::msg::{
+        Asset, AssetInfo, InstantiateMsg as PoolInstantiateMsg, QueryMsg as PoolQueryMsg,
+        SimulationResponse,
+    };
+    use cw_flash_loan_mock_money_market::msg::{
+        DenomPrice, InstantiateMsg as MarketInstantiateMsg, PositionResponse,
+        QueryMsg as MarketQueryMsg,
+    };
+    use cw_flash_loan_testing::{
+        assert_treasury_balanced, Deployment as FlashLoanDeployment, DeploymentBuilder, ADMIN,
+    };
+    use cw_multi_test::{App, Contract, ContractWrapper, Executor};
+
+    pub fn cw_refinancer_contract() -> Box<dyn Contract<Empty>> {
+        let contract = ContractWrapper::new(
+            crate::contract::execute,
+            crate::contract::instantiate,
+            crate::contract::query,
+        );
+
+        Box::new(contract)
+    }
+
+    pub fn cw_mock_amm_contract() -> Box<dyn Contract<Empty>> {
+        let contract = ContractWrapper::new(
+            cw_flash_loan_mock_amm::contract::execute,
+            cw_flash_loan_mock_amm::contract::instantiate,
+            cw_flash_loan_mock_amm::contract::query,
+        );
+
+        Box::new(contract)
+    }
+
+    pub fn cw_mock_money_market_contract() -> Box<dyn Contract<Empty>> {
+        let contract = ContractWrapper::new(
+            cw_flash_loan_mock_money_market::contract::execute,
+            cw_flash_loan_mock_money_market::contract::instantiate,
+            cw_flash_loan_mock_money_market::contract::query,
+        );
+
+        Box::new(contract)
+    }
+
+    struct Deployment {
+        app: App,
+        refinancer: CwRefinancerContract,
+        gateway: CwGatewayContract,
+        pool: Addr,
+    }
+
+    /// Deploys the flash loan contracts, a uluna/uusd pool priced at 2 uusd per uluna
+    /// and the refinancer.
+    fn proper_instantiate() -> Deployment {
+        let FlashLoanDeployment {
+            mut app,
+            gateway: cw_gateway_contract,
+            ..
+        } = DeploymentBuilder::new()
+            .with_denom("uusd")
+            .with_balance(ADMIN, coins(1_000_000_000_000_000_000, "uluna"))
+            .build();
+
+        let cw_mock_amm_id = app.store_code(cw_mock_amm_contract());
+        let pool = app
+            .instantiate_contract(
+                cw_mock_amm_id,
+                Addr::unchecked(ADMIN),
+                &PoolInstantiateMsg {
+                    denoms: ["uluna".to_string(), "uusd".to_string()],
+                    commission_rate: Decimal::permille(3),
+                },
+                &[coin(1_000_000, "uluna"), coin(2_000_000, "uusd")],
+                "pool",
+                None,
+            )
+            .unwrap();
+
+        let cw_refinancer_id = app.store_code(cw_refinancer_contract());
+        let cw_refinancer_contract_addr = app
+            .instantiate_contract(
+                cw_refinancer_id,
+                Addr::unchecked(ADMIN),
+                &InstantiateMsg {
+                    cw_gateway_contract_addr: cw_gateway_contract.addr().to_string(),
+                },
+                &[],
+                "refinancer",
+                None,
+            )
+            .unwrap();
+
+        Deployment {
+            app,
+            gateway: cw_gateway_contract,
+            refinancer: CwRefinancerContract(cw_refinancer_contract_addr),
+            pool,
+        }
+    }
+
+    /// Market with plenty of uusd to lend, valuing uluna at 3 uusd
+    fn instantiate_market(app: &mut App, liquidation_threshold: Decimal) -> Addr {
+        let cw_mock_money_market_id = app.store_code(cw_mock_money_market_contract());
+
+        app.instantiate_contract(
+            cw_mock_money_market_id,
+            Addr::unchecked(ADMIN),
+            &MarketInstantiateMsg {
+                liquidation_threshold,
+                liquidation_bonus: Decimal::percent(10),
+                prices: vec![
+                    DenomPrice {
+                        denom: "uluna".to_string(),
+                        price: Decimal::percent(300),
+                    },
+                    DenomPrice {
+                        denom: "uusd".to_string(),
+                        price: Decimal::one(),
+                    },
+                ],
+            },
+            &coins(1_000_000, "uusd"),
+            "market",
+            None,
+        )
+        .unwrap()
+    }
+
+    /// Has the refinancer deposit `collateral` and borrow `debt` on `market`
+    fn open_position(deployment: &mut Deployment, market: &Addr, collateral: Coin, debt: Coin) {
+        deployment
+            .app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                deployment.refinancer.addr(),
+                &ExecuteMsg::Deposit {
+                    market: market.to_string(),
+                },
+                &[collateral],
+            )
+            .unwrap();
+
+        deployment
+            .app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                deployment.refinancer.addr(),
+                &ExecuteMsg::Borrow {
+                    market: market.to_string(),
+                    asset: debt,
+                },
+                &[],
+            )
+            .unwrap();
+    }
+
+    fn query_position(deployment: &Deployment, market: &Addr) -> PositionResponse {
+        deployment
+            .app
+            .wrap()
+            .query_wasm_smart(
+                market,
+                &MarketQueryMsg::Position {
+                    owner: deployment.refinancer.addr().to_string(),
+                },
+            )
+            .unwrap()
+    }
+
+    fn refinance(
+        deployment: &mut Deployment,
+        from_market: &Addr,
+        to_market: &Addr,
+        collateral_swap_route: Vec<SwapStep>,
+    ) -> anyhow::Result<()> {
+        deployment.app.execute_contract(
+            Addr::unchecked(ADMIN),
+            deployment.refinancer.addr(),
+            &ExecuteMsg::Refinance {
+                from_market: from_market.to_string(),
+                to_market: to_market.to_string(),
+                debt: coin(1_000, "uusd"),
+                collateral: coin(1_000, "uluna"),
+                collateral_swap_route,
+            },
+            &[],
+        )?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn moves_position_to_another_market() {
+        let mut deployment = proper_instantiate();
+        let from_market = instantiate_market(&mut deployment.app, Decimal::percent(80));
+        let to_market = instantiate_market(&mut deployment.app, Decimal::percent(80));
+
+        open_position(
+            &mut deployment,
+            &from_market,
+            coin(1_000, "uluna"),
+            coin(1_000, "uusd"),
+        );
+
+        refinance(&mut deployment, &from_market, &to_market, vec![]).unwrap();
+
+        let position = query_position(&deployment, &from_market);
+        assert!(position.collateral.is_empty());
+        assert!(position.debt.is_empty());
+
+        // The flash loan fees end up on the new debt
+        let position = query_position(&deployment, &to_market);
+        assert_eq!(position.collateral, coins(1_000, "uluna"));
+        assert_eq!(position.debt, coins(1_030, "uusd"));
+
+        // Nothing is left behind besides what was borrowed when opening the position
+        assert_eq!(
+            deployment
+                .app
+                .wrap()
+                .query_all_balances(deployment.refinancer.addr())
+                .unwrap(),
+            coins(1_000, "uusd")
+        );
+
+        assert_treasury_balanced(&deployment.app.wrap(), &deployment.gateway);
+    }
+
+    #[test]
+    fn swaps_collateral_in_place() {
+        let mut deployment = proper_instantiate();
+        let market = instantiate_market(&mut deployment.app, Decimal::percent(80));
+
+        open_position(
+            &mut deployment,
+            &market,
+            coin(1_000, "uluna"),
+            coin(1_000, "uusd"),
+        );
+
+        let SimulationResponse { return_amount, .. } = deployment
+            .app
+            .wrap()
+            .query_wasm_smart(
+                &deployment.pool,
+                &PoolQueryMsg::Simulation {
+                    offer_asset: Asset {
+                        info: AssetInfo::NativeToken {
+                            denom: "uluna".to_string(),
+                        },
+                        amount: Uint128::new(1_000),
+                    },
+                    ask_asset_info: None,
+                },
+            )
+            .unwrap();
+
+        let collateral_swap_route = vec![SwapStep {
+            dex: Dex::Astroport {
+                pair_addr: deployment.pool.to_string(),
+            },
+            ask_denom: "uusd".to_string(),
+        }];
+        refinance(&mut deployment, &market, &market, collateral_swap_route).unwrap();
+
+        let position = query_position(&deployment, &market);
+        assert_eq!(position.collateral, coins(return_amount.u128(), "uusd"));
+        assert_eq!(position.debt, coins(1_030, "uusd"));
+
+        assert_treasury_balanced(&deployment.app.wrap(), &deployment.gateway);
+    }
+
+    #[test]
+    fn refinancing_refused_by_new_market_is_reverted() {
+        let mut deployment = proper_instantiate();
+        let from_market = instantiate_market(&mut deployment.app, Decimal::percent(80));
+        // 1_000 uluna only allow borrowing 900 uusd there
+        let to_market = instantiate_market(&mut deployment.app, Decimal::percent(30));
+
+        open_position(
+            &mut deployment,
+            &from_market,
+            coin(1_000, "uluna"),
+            coin(1_000, "uusd"),
+        );
+
+        let err = refinance(&mut deployment, &from_market, &to_market, vec![]).unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<cw_flash_loan_gateway::ContractError>(),
+            Some(cw_flash_loan_gateway::ContractError::BorrowerHookFailed { reason, .. })
+                if reason.contains("Position would become unhealthy")
+        ));
+
+        let position = query_position(&deployment, &from_market);
+        assert_eq!(position.collateral, coins(1_000, "uluna"));
+        assert_eq!(position.debt, coins(1_000, "uusd"));
+
+        assert_treasury_balanced(&deployment.app.wrap(), &deployment.gateway);
+    }
+}
@@ -0,0 +1,15 @@
+//! Flash loan borrower template moving a lending position in a single transaction.
+//!
+//! The contract owns its positions. A [`msg::ExecuteMsg::Refinance`] plan repays the debt
+//! on one market with flash-loaned funds, takes the collateral out, optionally swaps it,
+//! puts it into another market and borrows there again to repay the flash loan.
+
+pub mod contract;
+mod error;
+pub mod helpers;
+pub mod integration_tests;
+pub mod market;
+pub mod msg;
+pub mod state;
+
+pub use crate::error::ContractError;
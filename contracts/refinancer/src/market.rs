@@ -0,0 +1,18 @@
+//! Subset of the money market interface used by the refinancer.
+
+use cosmwasm_std::Coin;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    /// Adds the attached funds to the sender's collateral
+    Deposit {},
+    /// Takes collateral out of the sender's position
+    Withdraw { asset: Coin },
+    /// Borrows against the sender's collateral
+    Borrow { asset: Coin },
+    /// Repays the attached funds
+    Repay { on_behalf_of: Option<String> },
+}
@@ -0,0 +1,54 @@
+use cosmwasm_schema::QueryResponses;
+use cosmwasm_std::Coin;
+use cw_flash_loan_borrower::swap::SwapStep;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InstantiateMsg {
+    pub cw_gateway_contract_addr: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateMsg {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    /// Moves the contract's position from `from_market` to `to_market`, owner only.
+    ///
+    /// Runs repay → withdraw → deposit → borrow → repay flash loan, where `debt` is
+    /// flash-borrowed and the amount borrowed on `to_market` also covers the loan fees.
+    /// Both markets may be the same one, which only swaps the collateral.
+    Refinance {
+        from_market: String,
+        to_market: String,
+        debt: Coin,
+        collateral: Coin,
+        /// Swaps the withdrawn collateral before depositing it, kept as is when empty
+        #[serde(default)]
+        collateral_swap_route: Vec<SwapStep>,
+    },
+    OnFlashLoanProvided {},
+    /// Deposits the attached funds as collateral on `market`, owner only
+    Deposit {
+        market: String,
+    },
+    /// Borrows on `market` against the contract's collateral, owner only
+    Borrow {
+        market: String,
+        asset: Coin,
+    },
+    /// Sends funds out of the contract, owner only
+    Withdraw {
+        amount: Coin,
+        recipient: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, QueryResponses)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    #[returns(crate::state::Config)]
+    Config {},
+}
@@ -0,0 +1,30 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{Addr, Coin};
+use cw_flash_loan_borrower::swap::SwapStep;
+use cw_storage_plus::Item;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Config {
+    /// The only address allowed to manage positions and withdraw funds
+    pub owner: Addr,
+    pub cw_gateway_contract_addr: Addr,
+}
+
+/// Refinancing plan being executed with the flash loan
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Refinancing {
+    pub from_market: Addr,
+    pub to_market: Addr,
+    /// Debt repaid on `from_market`, borrowed from the gateway
+    pub debt: Coin,
+    /// Collateral moved from `from_market` to `to_market`
+    pub collateral: Coin,
+    /// Swaps turning the collateral into the one deposited on `to_market`
+    pub collateral_swap_route: Vec<SwapStep>,
+}
+
+pub const CONFIG: Item<Config> = Item::new("config");
+
+pub const REFINANCING: Item<Refinancing> = Item::new("refinancing");
@@ -0,0 +1,314 @@
+//! Typed client for using the vault from off-chain code.
+//!
+//! Execute messages come out as `MsgExecuteContract`s, ready to be put in a transaction
+//! and signed, or to be run as a `CosmosMsg` against a local `cw-multi-test` app.
+//! Queries go through a `ContractQuerier`, which can be backed by a chain's gRPC or LCD
+//! endpoint as well as by `QuerierWrapper`.
+
+use cosmwasm_std::{
+    to_json_binary, Binary, Coin, CosmosMsg, CustomQuery, QuerierWrapper, StdError, StdResult,
+    Uint128, WasmMsg, WasmQuery,
+};
+use cw20::Cw20ExecuteMsg;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::msg::{
+    ExecuteMsg, FeeQuoteResponse, PendingWithdrawalsResponse, QueryMsg, ReceiveMsg,
+    RemoteVaultsResponse, SharesResponse, TotalAssetsResponse,
+};
+use crate::state::{Config, FeeCurve, StakingConfig};
+
+pub const MSG_EXECUTE_CONTRACT_TYPE_URL: &str = "/cosmwasm.wasm.v1.MsgExecuteContract";
+
+/// Runs smart queries against contracts
+pub trait ContractQuerier {
+    fn query_smart<T: DeserializeOwned>(&self, contract: &str, msg: &Binary) -> StdResult<T>;
+}
+
+impl<C: CustomQuery> ContractQuerier for QuerierWrapper<'_, C> {
+    fn query_smart<T: DeserializeOwned>(&self, contract: &str, msg: &Binary) -> StdResult<T> {
+        self.query(
+            &WasmQuery::Smart {
+                contract_addr: contract.to_string(),
+                msg: msg.clone(),
+            }
+            .into(),
+        )
+    }
+}
+
+/// `MsgExecuteContract` as it appears in the JSON of a transaction
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MsgExecuteContract {
+    #[serde(rename = "@type")]
+    pub type_url: String,
+    pub sender: String,
+    pub contract: String,
+    pub msg: serde_json::Value,
+    pub funds: Vec<Coin>,
+}
+
+impl MsgExecuteContract {
+    pub fn new<M: Serialize>(
+        sender: &str,
+        contract: &str,
+        msg: &M,
+        funds: Vec<Coin>,
+    ) -> StdResult<Self> {
+        Ok(MsgExecuteContract {
+            type_url: MSG_EXECUTE_CONTRACT_TYPE_URL.to_string(),
+            sender: sender.to_string(),
+            contract: contract.to_string(),
+            msg: serde_json::to_value(msg).map_err(|err| StdError::serialize_err("msg", err))?,
+            funds,
+        })
+    }
+
+    pub fn to_json(&self) -> StdResult<String> {
+        serde_json::to_string(self)
+            .map_err(|err| StdError::serialize_err("MsgExecuteContract", err))
+    }
+
+    /// Same message for executing on behalf of `sender`, e.g. with `cw-multi-test`
+    pub fn to_cosmos_msg(&self) -> StdResult<CosmosMsg> {
+        Ok(WasmMsg::Execute {
+            contract_addr: self.contract.clone(),
+            msg: to_json_binary(&self.msg)?,
+            funds: self.funds.clone(),
+        }
+        .into())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VaultClient {
+    pub contract: String,
+}
+
+impl VaultClient {
+    pub fn new(contract: impl Into<String>) -> Self {
+        VaultClient {
+            contract: contract.into(),
+        }
+    }
+
+    pub fn execute(
+        &self,
+        sender: &str,
+        msg: &ExecuteMsg,
+        funds: Vec<Coin>,
+    ) -> StdResult<MsgExecuteContract> {
+        MsgExecuteContract::new(sender, &self.contract, msg, funds)
+    }
+
+    /// Only the gateway can have the vault provide assets
+    pub fn provide_asset(
+        &self,
+        sender: &str,
+        asset: Coin,
+        borrower_addr: &str,
+    ) -> StdResult<MsgExecuteContract> {
+        let msg = ExecuteMsg::ProvideAsset {
+            asset,
+            borrower_addr: borrower_addr.to_string(),
+        };
+
+        self.execute(sender, &msg, vec![])
+    }
+
+    pub fn deposit(&self, sender: &str, funds: Vec<Coin>) -> StdResult<MsgExecuteContract> {
+        self.execute(sender, &ExecuteMsg::Deposit {}, funds)
+    }
+
+    /// TokenFactory shares must be attached as `funds`, internal ones are burnt by the vault
+    pub fn withdraw(
+        &self,
+        sender: &str,
+        shares: Uint128,
+        funds: Vec<Coin>,
+    ) -> StdResult<MsgExecuteContract> {
+        self.execute(sender, &ExecuteMsg::Withdraw { shares }, funds)
+    }
+
+    /// Withdraws CW20 shares by sending them to the vault through `share_token`
+    pub fn withdraw_cw20(
+        &self,
+        sender: &str,
+        share_token: &str,
+        shares: Uint128,
+    ) -> StdResult<MsgExecuteContract> {
+        let msg = Cw20ExecuteMsg::Send {
+            contract: self.contract.clone(),
+            amount: shares,
+            msg: to_json_binary(&ReceiveMsg::Withdraw {})?,
+        };
+
+        MsgExecuteContract::new(sender, share_token, &msg, vec![])
+    }
+
+    pub fn claim(&self, sender: &str) -> StdResult<MsgExecuteContract> {
+        self.execute(sender, &ExecuteMsg::Claim {}, vec![])
+    }
+
+    pub fn rebalance(&self, sender: &str) -> StdResult<MsgExecuteContract> {
+        self.execute(sender, &ExecuteMsg::Rebalance {}, vec![])
+    }
+
+    pub fn update_staking(
+        &self,
+        sender: &str,
+        staking: StakingConfig,
+    ) -> StdResult<MsgExecuteContract> {
+        self.execute(sender, &ExecuteMsg::UpdateStaking { staking }, vec![])
+    }
+
+    pub fn update_fee_curve(
+        &self,
+        sender: &str,
+        fee_curve: FeeCurve,
+    ) -> StdResult<MsgExecuteContract> {
+        self.execute(sender, &ExecuteMsg::UpdateFeeCurve { fee_curve }, vec![])
+    }
+
+    pub fn update_gateway(&self, sender: &str, gateway: &str) -> StdResult<MsgExecuteContract> {
+        let msg = ExecuteMsg::UpdateGateway {
+            gateway: gateway.to_string(),
+        };
+
+        self.execute(sender, &msg, vec![])
+    }
+
+    #[cfg(feature = "ibc")]
+    pub fn request_liquidity(
+        &self,
+        sender: &str,
+        channel_id: &str,
+        amount: Uint128,
+    ) -> StdResult<MsgExecuteContract> {
+        let msg = ExecuteMsg::RequestLiquidity {
+            channel_id: channel_id.to_string(),
+            amount,
+        };
+
+        self.execute(sender, &msg, vec![])
+    }
+
+    #[cfg(feature = "ibc")]
+    pub fn update_allowed_remote_vaults(
+        &self,
+        sender: &str,
+        add: Vec<String>,
+        remove: Vec<String>,
+    ) -> StdResult<MsgExecuteContract> {
+        self.execute(
+            sender,
+            &ExecuteMsg::UpdateAllowedRemoteVaults { add, remove },
+            vec![],
+        )
+    }
+
+    #[cfg(feature = "ibc")]
+    pub fn update_remote_vault(
+        &self,
+        sender: &str,
+        channel_id: &str,
+        transfer_channel: &str,
+        min_liquidity: Uint128,
+    ) -> StdResult<MsgExecuteContract> {
+        let msg = ExecuteMsg::UpdateRemoteVault {
+            channel_id: channel_id.to_string(),
+            transfer_channel: transfer_channel.to_string(),
+            min_liquidity,
+        };
+
+        self.execute(sender, &msg, vec![])
+    }
+
+    pub fn query<T: DeserializeOwned>(
+        &self,
+        querier: &impl ContractQuerier,
+        msg: &QueryMsg,
+    ) -> StdResult<T> {
+        querier.query_smart(&self.contract, &to_json_binary(msg)?)
+    }
+
+    pub fn config(&self, querier: &impl ContractQuerier) -> StdResult<Config> {
+        self.query(querier, &QueryMsg::Config {})
+    }
+
+    pub fn total_assets(&self, querier: &impl ContractQuerier) -> StdResult<TotalAssetsResponse> {
+        self.query(querier, &QueryMsg::TotalAssets {})
+    }
+
+    pub fn shares(
+        &self,
+        querier: &impl ContractQuerier,
+        provider: &str,
+    ) -> StdResult<SharesResponse> {
+        let msg = QueryMsg::Shares {
+            provider: provider.to_string(),
+        };
+
+        self.query(querier, &msg)
+    }
+
+    pub fn pending_withdrawals(
+        &self,
+        querier: &impl ContractQuerier,
+        provider: &str,
+    ) -> StdResult<PendingWithdrawalsResponse> {
+        let msg = QueryMsg::PendingWithdrawals {
+            provider: provider.to_string(),
+        };
+
+        self.query(querier, &msg)
+    }
+
+    pub fn fee_quote(
+        &self,
+        querier: &impl ContractQuerier,
+        asset: Coin,
+    ) -> StdResult<FeeQuoteResponse> {
+        self.query(querier, &QueryMsg::FeeQuote { asset })
+    }
+
+    pub fn remote_vaults(&self, querier: &impl ContractQuerier) -> StdResult<RemoteVaultsResponse> {
+        self.query(querier, &QueryMsg::RemoteVaults {})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::coins;
+
+    #[test]
+    fn execute_msgs_are_transaction_json() {
+        let msg = VaultClient::new("vault")
+            .deposit("provider", coins(1_000, "uluna"))
+            .unwrap();
+
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&msg.to_json().unwrap()).unwrap(),
+            serde_json::json!({
+                "@type": "/cosmwasm.wasm.v1.MsgExecuteContract",
+                "sender": "provider",
+                "contract": "vault",
+                "msg": { "deposit": {} },
+                "funds": [{ "denom": "uluna", "amount": "1000" }],
+            })
+        );
+
+        assert_eq!(
+            msg.to_cosmos_msg().unwrap(),
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "vault".to_string(),
+                msg: to_json_binary(&ExecuteMsg::Deposit {}).unwrap(),
+                funds: coins(1_000, "uluna"),
+            })
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{CheckedMultiplyRatioError, Coin, OverflowError, StdError, Uint128};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -6,15 +6,84 @@ pub enum ContractError {
     #[error("{0}")]
     Std(#[from] StdError),
 
+    #[error("{0}")]
+    Overflow(#[from] OverflowError),
+
+    #[error("{0}")]
+    CheckedMultiplyRatio(#[from] CheckedMultiplyRatioError),
+
     #[error("Unauthorized")]
     Unauthorized {},
 
     #[error("Asset Unavailable")]
     AssetUnavailable {},
 
+    #[error("Cannot lend nothing")]
+    EmptyLoan {},
+
     #[error("Custom Error val: {val:?}")]
     CustomError{val: String},
 
+    #[error("Exactly one coin of {denom} must be sent")]
+    InvalidDeposit { denom: String },
+
+    #[error("Deposit of {deposit} is worth less than a share")]
+    DepositTooSmall { deposit: Coin },
+
+    #[error("Cannot withdraw zero shares")]
+    EmptyWithdrawal {},
+
+    #[error("Cannot withdraw more shares than owned: {shares}")]
+    InsufficientShares { shares: Uint128 },
+
+    #[error("Not enough liquidity in the vault, only {available} available")]
+    InsufficientLiquidity { available: Coin },
+
+    #[error("Only the bonded denom {bonded_denom} can be staked")]
+    NotStakeDenom { bonded_denom: String },
+
+    #[error("A flash loan is in progress")]
+    LoanInProgress {},
+
+    #[error("No withdrawal can be claimed yet")]
+    NothingToClaim {},
+
+    #[error("CW20 shares are withdrawn by sending them to the vault")]
+    SharesAreCw20 {},
+
+    #[error("Fee curve kink must be within (0, 1]")]
+    InvalidFeeCurve {},
+
+    #[error("Loan wasn't repaid with its fee, expected a balance of {expected} but got {balance}")]
+    InsufficientRepayment { expected: Coin, balance: Coin },
+
+    #[error("Fee on a loan of {asset} overflows")]
+    FeeOverflow { asset: Coin },
+
+    #[error("Only unordered channels to another vault's `wasm` port on {version} are supported")]
+    InvalidIbcChannel { version: String },
+
+    #[error("Remote vault on port {port_id} isn't allowed to connect")]
+    RemoteVaultNotAllowed { port_id: String },
+
+    #[error("No remote vault on channel {channel_id}")]
+    UnknownRemoteVault { channel_id: String },
+
+    #[error("No transfer channel set to lend to the remote vault on {channel_id}")]
+    NoTransferChannel { channel_id: String },
+
+    #[error("No transfer {transfer_id} in flight on {channel_id}")]
+    UnknownTransfer {
+        channel_id: String,
+        transfer_id: u64,
+    },
+
+    #[error("Deposits and withdrawals wait for the transfers on {channel_id} to be settled")]
+    TransferInFlight { channel_id: String },
+
+    #[error("No transfer lent as packet {sequence} on {channel}")]
+    UnknownTransferPacket { channel: String, sequence: u64 },
+
     // Add any other custom errors you like here.
     // Look at https://docs.rs/thiserror/1.0.21/thiserror/ for details.
 }
@@ -0,0 +1,168 @@
+//! Utilization based vault fees.
+//!
+//! The more of the vault's available liquidity a loan takes, the more it costs.
+//! Below the kink the rate grows slowly from the base rate, past it steeply,
+//! so loans draining the vault pay for the risk they put on it.
+
+use cosmwasm_std::{coin, Coin, Decimal, StdResult, Uint128};
+
+use crate::error::ContractError;
+use crate::state::FeeCurve;
+
+impl Default for FeeCurve {
+    /// Flat 2% fee
+    fn default() -> Self {
+        FeeCurve {
+            base_rate: Decimal::percent(2),
+            kink: Decimal::percent(80),
+            slope_below_kink: Decimal::zero(),
+            slope_above_kink: Decimal::zero(),
+        }
+    }
+}
+
+impl FeeCurve {
+    pub fn validate(&self) -> Result<(), ContractError> {
+        if self.kink.is_zero() || self.kink > Decimal::one() {
+            return Err(ContractError::InvalidFeeCurve {});
+        }
+
+        Ok(())
+    }
+
+    /// Fee rate for a loan taking `utilization` of the available liquidity
+    pub fn rate(&self, utilization: Decimal) -> StdResult<Decimal> {
+        // rates aren't capped, so steep slopes may not fit in a decimal
+        if utilization <= self.kink {
+            let below_kink = self.slope_below_kink * (utilization / self.kink);
+
+            Ok(self.base_rate.checked_add(below_kink)?)
+        } else {
+            let above_kink = (utilization - self.kink) / (Decimal::one() - self.kink);
+
+            Ok(self
+                .base_rate
+                .checked_add(self.slope_below_kink)?
+                .checked_add(self.slope_above_kink * above_kink)?)
+        }
+    }
+}
+
+/// Fee charged for lending `asset` out of `liquid` funds, along with its rate and utilization.
+/// Fees are rounded up, so no loan is ever cheaper than its rate.
+pub fn quote(
+    curve: &FeeCurve,
+    asset: &Coin,
+    liquid: Uint128,
+) -> Result<(Coin, Decimal, Decimal), ContractError> {
+    // utilization is undefined for an empty vault, which has nothing to lend anyway
+    if asset.amount > liquid || liquid.is_zero() {
+        return Err(ContractError::InsufficientLiquidity {
+            available: coin(liquid.u128(), asset.denom.as_str()),
+        });
+    }
+
+    let utilization = Decimal::from_ratio(asset.amount, liquid);
+    let rate = curve.rate(utilization)?;
+    let fee = asset
+        .amount
+        .checked_mul_ceil(rate)
+        .map_err(|_| ContractError::FeeOverflow {
+            asset: asset.clone(),
+        })?;
+
+    Ok((coin(fee.u128(), asset.denom.as_str()), rate, utilization))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::Uint256;
+    use proptest::prelude::*;
+
+    fn fee_curve() -> FeeCurve {
+        FeeCurve {
+            base_rate: Decimal::percent(1),
+            kink: Decimal::percent(50),
+            slope_below_kink: Decimal::percent(4),
+            slope_above_kink: Decimal::percent(50),
+        }
+    }
+
+    #[test]
+    fn rate_follows_utilization() {
+        let curve = fee_curve();
+
+        assert_eq!(curve.rate(Decimal::zero()).unwrap(), Decimal::percent(1));
+        assert_eq!(
+            curve.rate(Decimal::percent(25)).unwrap(),
+            Decimal::percent(3)
+        );
+        assert_eq!(
+            curve.rate(Decimal::percent(50)).unwrap(),
+            Decimal::percent(5)
+        );
+        assert_eq!(
+            curve.rate(Decimal::percent(75)).unwrap(),
+            Decimal::percent(30)
+        );
+        assert_eq!(curve.rate(Decimal::one()).unwrap(), Decimal::percent(55));
+    }
+
+    #[test]
+    fn quotes_loans_against_available_liquidity() {
+        let curve = fee_curve();
+
+        assert_eq!(
+            quote(&curve, &coin(50_000, "uluna"), Uint128::new(200_000)).unwrap(),
+            (
+                coin(1_500, "uluna"),
+                Decimal::percent(3),
+                Decimal::percent(25)
+            )
+        );
+
+        assert!(matches!(
+            quote(&curve, &coin(200_001, "uluna"), Uint128::new(200_000)),
+            Err(ContractError::InsufficientLiquidity { .. })
+        ));
+        assert!(matches!(
+            quote(&curve, &coin(0, "uluna"), Uint128::zero()),
+            Err(ContractError::InsufficientLiquidity { .. })
+        ));
+    }
+
+    /// Mostly sensible rates, now and then absurd ones
+    fn decimal() -> impl Strategy<Value = Decimal> {
+        prop_oneof![0..=10 * Decimal::one().atomics().u128(), any::<u128>()].prop_map(Decimal::raw)
+    }
+
+    proptest! {
+        #[test]
+        fn fee_never_rounds_in_the_borrowers_favour(
+            amount in any::<u128>(),
+            liquid in any::<u128>(),
+            base_rate in decimal(),
+            kink in 1..=Decimal::one().atomics().u128(),
+            slope_below_kink in decimal(),
+            slope_above_kink in decimal(),
+        ) {
+            let curve = FeeCurve {
+                base_rate,
+                kink: Decimal::raw(kink),
+                slope_below_kink,
+                slope_above_kink,
+            };
+
+            // overflowing fees are refused instead of panicking or wrapping around
+            if let Ok((fee, rate, _)) = quote(&curve, &coin(amount, "uluna"), Uint128::new(liquid)) {
+                let owed = Uint256::from(amount) * Uint256::from(rate.atomics());
+                let charged = Uint256::from(fee.amount) * Uint256::from(Decimal::one().atomics());
+
+                prop_assert!(charged >= owed);
+                prop_assert!(charged < owed + Uint256::from(Decimal::one().atomics()));
+            }
+        }
+    }
+}
@@ -2,10 +2,13 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use cosmwasm_std::{
-    to_binary, Addr, Coin, CosmosMsg, StdResult, WasmMsg,
+    to_json_binary, Addr, Coin, CosmosMsg, QuerierWrapper, StdResult, WasmMsg,
 };
 
-use crate::msg::{ExecuteMsg};
+use crate::msg::{
+    ExecuteMsg, FeeQuoteResponse, QueryMsg, RemoteVaultsResponse, TotalAssetsResponse,
+};
+use crate::state::Config;
 
 /// CwBridgeContract is a wrapper around Addr that provides a lot of helpers
 /// for working with this.
@@ -18,7 +21,7 @@ impl Contract {
     }
 
     pub fn call<T: Into<ExecuteMsg>>(&self, msg: T, funds: Option<Vec<Coin>>) -> StdResult<CosmosMsg> {
-        let msg = to_binary(&msg.into())?;
+        let msg = to_json_binary(&msg.into())?;
         Ok(WasmMsg::Execute {
             contract_addr: self.addr().into(),
             msg,
@@ -26,4 +29,23 @@ impl Contract {
         }
         .into())
     }
+
+    /// Fee the vault charges for lending `asset` right now
+    pub fn fee_quote(&self, querier: &QuerierWrapper, asset: Coin) -> StdResult<FeeQuoteResponse> {
+        querier.query_wasm_smart(self.addr(), &QueryMsg::FeeQuote { asset })
+    }
+
+    pub fn config(&self, querier: &QuerierWrapper) -> StdResult<Config> {
+        querier.query_wasm_smart(self.addr(), &QueryMsg::Config {})
+    }
+
+    /// Everything the vault's shares are backed by, and what it borrowed remotely
+    pub fn total_assets(&self, querier: &QuerierWrapper) -> StdResult<TotalAssetsResponse> {
+        querier.query_wasm_smart(self.addr(), &QueryMsg::TotalAssets {})
+    }
+
+    /// Vaults on other chains the vault can move liquidity from, and what they owe each other
+    pub fn remote_vaults(&self, querier: &QuerierWrapper) -> StdResult<RemoteVaultsResponse> {
+        querier.query_wasm_smart(self.addr(), &QueryMsg::RemoteVaults {})
+    }
 }
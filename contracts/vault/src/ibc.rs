@@ -0,0 +1,1197 @@
+//! Lending idle liquidity between vaults on different chains.
+//!
+//! Vaults get connected over an unordered channel between their `wasm` ports, once their owners
+//! allowed each other's. Either of them
+//! can then request liquidity from the other, which sends it over an ICS-20 transfer channel
+//! when it can spare it, and acknowledges what it sent. Flash loans settle within a single
+//! transaction, so they are only ever served from the local vault's balance: liquidity gets
+//! moved ahead of time to the chain it is needed on.
+//!
+//! Each vault keeps a ledger of what it lent to and borrowed from every remote vault.
+//! Transfers stay in flight, backing neither vault's shares, until the chain's IBC hooks tell
+//! the lender whether they landed or timed out and got refunded. The lender books them then,
+//! and tells the borrower to book them too, sending that packet again if it times out.
+//! Deposits and withdrawals wait for transfers in flight to settle, shares would be mispriced
+//! until then. Lent liquidity still backs the lender's shares, borrowed liquidity doesn't back
+//! the borrower's. Loans are repaid by lending the liquidity back.
+//!
+//! Both vaults must lend the same asset across the transfer channel,
+//! the native denom on one chain and its ICS-20 voucher on the other.
+
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
+use cosmwasm_std::{
+    coin, from_json, to_json_binary, Binary, Deps, DepsMut, Empty, Env, IbcBasicResponse,
+    IbcChannel, IbcChannelCloseMsg, IbcChannelConnectMsg, IbcChannelOpenMsg, IbcMsg, IbcOrder,
+    IbcPacketAckMsg, IbcPacketReceiveMsg, IbcPacketTimeoutMsg, IbcReceiveResponse, IbcTimeout,
+    Order, Response, StdError, StdResult, SubMsg, SubMsgResult, Timestamp, Uint128,
+};
+use cw_storage_plus::Map;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::contract::{ensure_no_active_loan, query_liquid};
+use crate::error::ContractError;
+use crate::ics20;
+use crate::msg::IbcLifecycleComplete;
+use crate::state::{
+    RemoteVault, Transfer, ALLOWED_REMOTE_VAULTS, CONFIG, EARLY_SETTLEMENTS, LENDING_TRANSFER,
+    REMOTE_VAULTS, TRANSFERS_IN, TRANSFERS_OUT, TRANSFER_PACKETS,
+};
+
+/// Version of the channels between vaults
+pub const IBC_VERSION: &str = "cw-flash-loan-vault-1";
+
+/// Seconds packets and transfers have to get relayed
+const PACKET_LIFETIME: u64 = 60 * 60;
+
+pub(crate) const REPLY_ON_TRANSFER_SENT: u64 = 3;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum VaultPacket {
+    /// Asks the remote vault to lend `amount` of its idle liquidity
+    RequestLiquidity { amount: Uint128 },
+    /// Tells the remote vault whether the transfer it borrowed landed, for it to book the loan
+    TransferSettled {
+        transfer_id: u64,
+        amount: Uint128,
+        landed: bool,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum VaultAck {
+    /// Liquidity the remote vault sent over the transfer channel, which lands by `timeout` if ever
+    Lent {
+        amount: Uint128,
+        transfer_id: u64,
+        timeout: Timestamp,
+    },
+    /// The remote vault booked the settled transfer
+    Settled {},
+    Error(String),
+}
+
+pub fn request_liquidity(
+    deps: DepsMut,
+    env: Env,
+    channel_id: String,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    load_remote_vault(deps.as_ref(), &channel_id)?;
+
+    if amount.is_zero() {
+        return Err(StdError::generic_err("Cannot request zero liquidity").into());
+    }
+
+    println!(
+        "[Vault: request_liquidity] channel = {} | amount = {}",
+        &channel_id, amount
+    );
+
+    let msg = IbcMsg::SendPacket {
+        channel_id: channel_id.clone(),
+        data: to_json_binary(&VaultPacket::RequestLiquidity { amount })?,
+        timeout: IbcTimeout::with_timestamp(env.block.time.plus_seconds(PACKET_LIFETIME)),
+    };
+
+    Ok(Response::new().add_message(msg).add_attributes(vec![
+        ("module", "vault"),
+        ("action", "request_liquidity"),
+        ("channel_id", channel_id.as_str()),
+        ("amount", &amount.to_string()),
+    ]))
+}
+
+/// Books the transfer the IBC hooks report the `outcome` of as lent if it landed,
+/// refunded transfers are back in the vault's balance, and tells the borrower
+pub fn settle_transfer(
+    deps: DepsMut,
+    env: Env,
+    outcome: IbcLifecycleComplete,
+) -> Result<Response, ContractError> {
+    let (transfer_channel, sequence, landed) = match outcome {
+        IbcLifecycleComplete::IbcAck {
+            channel,
+            sequence,
+            success,
+            ..
+        } => (channel, sequence, success),
+        IbcLifecycleComplete::IbcTimeout { channel, sequence } => (channel, sequence, false),
+    };
+
+    let (channel_id, transfer_id) = TRANSFER_PACKETS
+        .may_load(deps.storage, (&transfer_channel, sequence))?
+        .ok_or_else(|| ContractError::UnknownTransferPacket {
+            channel: transfer_channel.clone(),
+            sequence,
+        })?;
+    let mut remote = load_remote_vault(deps.as_ref(), &channel_id)?;
+    let transfer = load_transfer(deps.as_ref(), &TRANSFERS_OUT, &channel_id, transfer_id)?;
+
+    TRANSFER_PACKETS.remove(deps.storage, (&transfer_channel, sequence));
+    TRANSFERS_OUT.remove(deps.storage, (&channel_id, transfer_id));
+    remote.sending -= transfer.amount;
+    if landed {
+        remote.sent += transfer.amount;
+    }
+    REMOTE_VAULTS.save(deps.storage, &channel_id, &remote)?;
+
+    println!(
+        "[Vault: settle_transfer] channel = {} | transfer = {} | landed = {}",
+        &channel_id, transfer_id, landed
+    );
+
+    let msg = IbcMsg::SendPacket {
+        channel_id: channel_id.clone(),
+        data: to_json_binary(&VaultPacket::TransferSettled {
+            transfer_id,
+            amount: transfer.amount,
+            landed,
+        })?,
+        timeout: IbcTimeout::with_timestamp(env.block.time.plus_seconds(PACKET_LIFETIME)),
+    };
+
+    Ok(Response::new().add_message(msg).add_attributes(vec![
+        ("module", "vault"),
+        ("action", "settle_transfer"),
+        ("channel_id", channel_id.as_str()),
+        ("transfer_id", &transfer_id.to_string()),
+        ("landed", &landed.to_string()),
+    ]))
+}
+
+/// Keeps the sequence of the transfer being lent, the IBC hooks report its outcome by it
+pub(crate) fn reply_on_transfer_sent(
+    deps: DepsMut,
+    result: SubMsgResult,
+) -> Result<Response, ContractError> {
+    let data = result
+        .into_result()
+        .map_err(StdError::generic_err)?
+        .data
+        .ok_or_else(|| StdError::generic_err("No sequence for the transfer sent"))?;
+    let sequence = ics20::parse_sequence(&data)?;
+
+    let (channel_id, transfer_id) = LENDING_TRANSFER.load(deps.storage)?;
+    LENDING_TRANSFER.remove(deps.storage);
+
+    let remote = load_remote_vault(deps.as_ref(), &channel_id)?;
+    let transfer_channel = remote.transfer_channel.unwrap_or_default();
+    TRANSFER_PACKETS.save(
+        deps.storage,
+        (&transfer_channel, sequence),
+        &(channel_id.clone(), transfer_id),
+    )?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("module", "vault"),
+        ("action", "reply_on_transfer_sent"),
+        ("channel_id", channel_id.as_str()),
+        ("transfer_id", &transfer_id.to_string()),
+        ("sequence", &sequence.to_string()),
+    ]))
+}
+
+pub fn update_allowed_remote_vaults(
+    deps: DepsMut,
+    add: Vec<String>,
+    remove: Vec<String>,
+) -> Result<Response, ContractError> {
+    for port_id in &add {
+        ALLOWED_REMOTE_VAULTS.save(deps.storage, port_id, &Empty {})?;
+    }
+
+    // channels already open stay so, the owner gets them closed
+    for port_id in &remove {
+        ALLOWED_REMOTE_VAULTS.remove(deps.storage, port_id);
+    }
+
+    Ok(Response::new().add_attributes(vec![
+        ("module", "vault"),
+        ("action", "update_allowed_remote_vaults"),
+    ]))
+}
+
+pub fn update_remote_vault(
+    deps: DepsMut,
+    channel_id: String,
+    transfer_channel: String,
+    min_liquidity: Uint128,
+) -> Result<Response, ContractError> {
+    let mut remote = load_remote_vault(deps.as_ref(), &channel_id)?;
+
+    remote.transfer_channel = Some(transfer_channel);
+    remote.min_liquidity = min_liquidity;
+    REMOTE_VAULTS.save(deps.storage, &channel_id, &remote)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("module", "vault"),
+        ("action", "update_remote_vault"),
+        ("channel_id", channel_id.as_str()),
+    ]))
+}
+
+/// The total leaves out transfers in flight, shares would be mispriced until they settle
+pub(crate) fn ensure_no_transfer_in_flight(deps: Deps) -> Result<(), ContractError> {
+    for remote in REMOTE_VAULTS.range(deps.storage, None, None, Order::Ascending) {
+        let (channel_id, remote) = remote?;
+
+        if !remote.sending.is_zero() || !remote.receiving.is_zero() {
+            return Err(ContractError::TransferInFlight { channel_id });
+        }
+    }
+
+    Ok(())
+}
+
+fn load_remote_vault(deps: Deps, channel_id: &str) -> Result<RemoteVault, ContractError> {
+    REMOTE_VAULTS
+        .may_load(deps.storage, channel_id)?
+        .ok_or_else(|| ContractError::UnknownRemoteVault {
+            channel_id: channel_id.to_string(),
+        })
+}
+
+fn load_transfer(
+    deps: Deps,
+    transfers: &Map<(&str, u64), Transfer>,
+    channel_id: &str,
+    transfer_id: u64,
+) -> Result<Transfer, ContractError> {
+    transfers
+        .may_load(deps.storage, (channel_id, transfer_id))?
+        .ok_or_else(|| ContractError::UnknownTransfer {
+            channel_id: channel_id.to_string(),
+            transfer_id,
+        })
+}
+
+/// Checks `channel` leads to another vault the owner allowed, returning its address
+fn remote_vault_addr(
+    deps: Deps,
+    channel: &IbcChannel,
+    counterparty_version: Option<&str>,
+) -> Result<String, ContractError> {
+    let invalid = || ContractError::InvalidIbcChannel {
+        version: IBC_VERSION.to_string(),
+    };
+
+    if channel.order != IbcOrder::Unordered
+        || channel.version != IBC_VERSION
+        || matches!(counterparty_version, Some(version) if version != IBC_VERSION)
+    {
+        return Err(invalid());
+    }
+
+    let port_id = &channel.counterparty_endpoint.port_id;
+    let addr = port_id.strip_prefix("wasm.").ok_or_else(invalid)?;
+
+    if !ALLOWED_REMOTE_VAULTS.has(deps.storage, port_id) {
+        return Err(ContractError::RemoteVaultNotAllowed {
+            port_id: port_id.clone(),
+        });
+    }
+
+    Ok(addr.to_string())
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn ibc_channel_open(
+    deps: DepsMut,
+    _env: Env,
+    msg: IbcChannelOpenMsg,
+) -> Result<(), ContractError> {
+    remote_vault_addr(deps.as_ref(), msg.channel(), msg.counterparty_version())?;
+
+    Ok(())
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn ibc_channel_connect(
+    deps: DepsMut,
+    _env: Env,
+    msg: IbcChannelConnectMsg,
+) -> Result<IbcBasicResponse, ContractError> {
+    let channel = msg.channel();
+    let addr = remote_vault_addr(deps.as_ref(), channel, msg.counterparty_version())?;
+    let channel_id = &channel.endpoint.channel_id;
+
+    println!(
+        "[Vault: ibc_channel_connect] channel = {} | remote vault = {}",
+        channel_id, &addr
+    );
+
+    // nothing gets lent until the owner sets a transfer channel
+    REMOTE_VAULTS.save(
+        deps.storage,
+        channel_id,
+        &RemoteVault {
+            channel_id: channel_id.clone(),
+            addr: addr.clone(),
+            transfer_channel: None,
+            min_liquidity: Uint128::zero(),
+            sent: Uint128::zero(),
+            received: Uint128::zero(),
+            sending: Uint128::zero(),
+            receiving: Uint128::zero(),
+            next_transfer_id: 1,
+        },
+    )?;
+
+    Ok(IbcBasicResponse::new().add_attributes(vec![
+        ("module", "vault"),
+        ("action", "ibc_channel_connect"),
+        ("channel_id", channel_id.as_str()),
+        ("remote_vault", addr.as_str()),
+    ]))
+}
+
+/// The remote vault's ledger is kept, what the vaults owe each other is still owed
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn ibc_channel_close(
+    _deps: DepsMut,
+    _env: Env,
+    msg: IbcChannelCloseMsg,
+) -> Result<IbcBasicResponse, ContractError> {
+    Ok(IbcBasicResponse::new().add_attributes(vec![
+        ("module", "vault"),
+        ("action", "ibc_channel_close"),
+        ("channel_id", msg.channel().endpoint.channel_id.as_str()),
+    ]))
+}
+
+/// Failures are acknowledged as errors, leaving the vault untouched
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn ibc_packet_receive(
+    deps: DepsMut,
+    env: Env,
+    msg: IbcPacketReceiveMsg,
+) -> StdResult<IbcReceiveResponse> {
+    let channel_id = msg.packet.dest.channel_id;
+
+    match receive_packet(deps, env, &channel_id, &msg.packet.data) {
+        Ok(response) => Ok(response),
+        Err(err) => {
+            println!("[Vault: ibc_packet_receive] error = {}", err);
+
+            Ok(IbcReceiveResponse::new()
+                .set_ack(to_json_binary(&VaultAck::Error(err.to_string()))?)
+                .add_attributes(vec![
+                    ("module", "vault"),
+                    ("action", "ibc_packet_receive"),
+                    ("channel_id", channel_id.as_str()),
+                    ("error", &err.to_string()),
+                ]))
+        }
+    }
+}
+
+fn receive_packet(
+    deps: DepsMut,
+    env: Env,
+    channel_id: &str,
+    data: &Binary,
+) -> Result<IbcReceiveResponse, ContractError> {
+    match from_json(data)? {
+        VaultPacket::RequestLiquidity { amount } => lend_liquidity(deps, env, channel_id, amount),
+        VaultPacket::TransferSettled {
+            transfer_id,
+            amount,
+            landed,
+        } => book_transfer_in(deps, channel_id, transfer_id, amount, landed),
+    }
+}
+
+/// Sends `amount` to the remote vault on `channel_id`, as long as it leaves the vault
+/// with the minimum liquidity it keeps for that remote vault
+fn lend_liquidity(
+    deps: DepsMut,
+    env: Env,
+    channel_id: &str,
+    amount: Uint128,
+) -> Result<IbcReceiveResponse, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let mut remote = load_remote_vault(deps.as_ref(), channel_id)?;
+
+    let transfer_channel =
+        remote
+            .transfer_channel
+            .clone()
+            .ok_or_else(|| ContractError::NoTransferChannel {
+                channel_id: channel_id.to_string(),
+            })?;
+    ensure_no_active_loan(deps.as_ref())?;
+
+    if amount.is_zero() {
+        return Err(StdError::generic_err("Cannot lend zero liquidity").into());
+    }
+
+    let liquid = query_liquid(deps.as_ref(), &env, &config)?;
+    let available = liquid.saturating_sub(remote.min_liquidity);
+
+    if amount > available {
+        return Err(ContractError::InsufficientLiquidity {
+            available: coin(available.u128(), config.denom.as_str()),
+        });
+    }
+
+    // only booked as lent once the IBC hooks tell whether it landed
+    let transfer_id = remote.next_transfer_id;
+    let timeout = env.block.time.plus_seconds(PACKET_LIFETIME);
+    TRANSFERS_OUT.save(
+        deps.storage,
+        (channel_id, transfer_id),
+        &Transfer { amount, timeout },
+    )?;
+
+    remote.next_transfer_id += 1;
+    remote.sending += amount;
+    REMOTE_VAULTS.save(deps.storage, channel_id, &remote)?;
+    LENDING_TRANSFER.save(deps.storage, &(channel_id.to_string(), transfer_id))?;
+
+    println!(
+        "[Vault: lend_liquidity] channel = {} | amount = {} | remote vault = {}",
+        channel_id, amount, &remote.addr
+    );
+
+    let msg = ics20::transfer_msg(
+        &env.contract.address,
+        &transfer_channel,
+        coin(amount.u128(), config.denom.as_str()),
+        &remote.addr,
+        timeout,
+    );
+    let ack = VaultAck::Lent {
+        amount,
+        transfer_id,
+        timeout,
+    };
+
+    Ok(IbcReceiveResponse::new()
+        .set_ack(to_json_binary(&ack)?)
+        .add_submessage(SubMsg::reply_on_success(msg, REPLY_ON_TRANSFER_SENT))
+        .add_attributes(vec![
+            ("module", "vault"),
+            ("action", "lend_liquidity"),
+            ("channel_id", channel_id),
+            ("transfer_id", &transfer_id.to_string()),
+            ("amount", &amount.to_string()),
+        ]))
+}
+
+/// Books the transfer the remote vault lent as `transfer_id` as it settled
+fn book_transfer_in(
+    deps: DepsMut,
+    channel_id: &str,
+    transfer_id: u64,
+    amount: Uint128,
+    landed: bool,
+) -> Result<IbcReceiveResponse, ContractError> {
+    let mut remote = load_remote_vault(deps.as_ref(), channel_id)?;
+
+    match TRANSFERS_IN.may_load(deps.storage, (channel_id, transfer_id))? {
+        Some(transfer) => {
+            TRANSFERS_IN.remove(deps.storage, (channel_id, transfer_id));
+            remote.receiving -= transfer.amount;
+        }
+        // relayed ahead of the acknowledgement that it was lent, which then books nothing
+        None => EARLY_SETTLEMENTS.save(deps.storage, (channel_id, transfer_id), &Empty {})?,
+    }
+    if landed {
+        remote.received += amount;
+    }
+    REMOTE_VAULTS.save(deps.storage, channel_id, &remote)?;
+
+    println!(
+        "[Vault: book_transfer_in] channel = {} | transfer = {} | landed = {}",
+        channel_id, transfer_id, landed
+    );
+
+    Ok(IbcReceiveResponse::new()
+        .set_ack(to_json_binary(&VaultAck::Settled {})?)
+        .add_attributes(vec![
+            ("module", "vault"),
+            ("action", "book_transfer_in"),
+            ("channel_id", channel_id),
+            ("transfer_id", &transfer_id.to_string()),
+            ("landed", &landed.to_string()),
+        ]))
+}
+
+/// Keeps track of the transfers the remote vault lent, until it tells they settled
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn ibc_packet_ack(
+    deps: DepsMut,
+    _env: Env,
+    msg: IbcPacketAckMsg,
+) -> Result<IbcBasicResponse, ContractError> {
+    let channel_id = msg.original_packet.src.channel_id;
+    let response = IbcBasicResponse::new().add_attributes(vec![
+        ("module", "vault"),
+        ("action", "ibc_packet_ack"),
+        ("channel_id", channel_id.as_str()),
+    ]);
+
+    let packet = from_json(&msg.original_packet.data)?;
+    let ack = from_json(&msg.acknowledgement.data)?;
+
+    let mut remote = load_remote_vault(deps.as_ref(), &channel_id)?;
+
+    match (packet, ack) {
+        (
+            VaultPacket::RequestLiquidity { .. },
+            VaultAck::Lent {
+                amount,
+                transfer_id,
+                timeout,
+            },
+        ) => {
+            if EARLY_SETTLEMENTS.has(deps.storage, (&channel_id, transfer_id)) {
+                EARLY_SETTLEMENTS.remove(deps.storage, (&channel_id, transfer_id));
+
+                return Ok(response.add_attribute("transfer_id", transfer_id.to_string()));
+            }
+
+            TRANSFERS_IN.save(
+                deps.storage,
+                (&channel_id, transfer_id),
+                &Transfer { amount, timeout },
+            )?;
+            remote.receiving += amount;
+            REMOTE_VAULTS.save(deps.storage, &channel_id, &remote)?;
+
+            println!(
+                "[Vault: ibc_packet_ack] channel = {} | transfer = {} | receiving = {}",
+                &channel_id, transfer_id, amount
+            );
+
+            Ok(response.add_attributes(vec![
+                ("transfer_id", transfer_id.to_string()),
+                ("receiving", amount.to_string()),
+            ]))
+        }
+        (VaultPacket::TransferSettled { transfer_id, .. }, VaultAck::Settled {}) => {
+            Ok(response.add_attribute("transfer_id", transfer_id.to_string()))
+        }
+        (_, VaultAck::Error(err)) => Ok(response.add_attribute("error", err)),
+        _ => Err(StdError::generic_err("Unexpected acknowledgement").into()),
+    }
+}
+
+/// Nothing was booked for a packet which timed out, settlements get sent again
+/// for the borrower to book them
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn ibc_packet_timeout(
+    _deps: DepsMut,
+    env: Env,
+    msg: IbcPacketTimeoutMsg,
+) -> Result<IbcBasicResponse, ContractError> {
+    let channel_id = msg.packet.src.channel_id;
+    let response = IbcBasicResponse::new().add_attributes(vec![
+        ("module", "vault"),
+        ("action", "ibc_packet_timeout"),
+        ("channel_id", channel_id.as_str()),
+    ]);
+
+    match from_json(&msg.packet.data)? {
+        VaultPacket::TransferSettled { transfer_id, .. } => {
+            let msg = IbcMsg::SendPacket {
+                channel_id,
+                data: msg.packet.data,
+                timeout: IbcTimeout::with_timestamp(env.block.time.plus_seconds(PACKET_LIFETIME)),
+            };
+
+            Ok(response
+                .add_message(msg)
+                .add_attribute("transfer_id", transfer_id.to_string()))
+        }
+        VaultPacket::RequestLiquidity { .. } => Ok(response),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::testing::{
+        mock_dependencies, mock_env, mock_ibc_packet_ack, mock_ibc_packet_recv,
+        mock_ibc_packet_timeout, mock_info, MockApi, MockQuerier, MockStorage,
+    };
+    use cosmwasm_std::{
+        Addr, CosmosMsg, IbcAcknowledgement, IbcEndpoint, OwnedDeps, Reply, SubMsgResponse, Uint128,
+    };
+    use prost::Message;
+
+    use crate::contract::{execute, instantiate, query, reply, sudo};
+    use crate::ics20::{MsgTransfer, MsgTransferResponse};
+    use crate::msg::{
+        ExecuteMsg, InstantiateMsg, QueryMsg, RemoteVaultsResponse, SudoMsg, TotalAssetsResponse,
+    };
+
+    const DENOM: &str = "uluna";
+    const OWNER: &str = "owner";
+
+    /// A vault on its own chain, whose transfers get relayed by the tests
+    struct Chain {
+        deps: OwnedDeps<MockStorage, MockApi, MockQuerier>,
+        env: Env,
+        /// Of the last transfer the vault sent
+        sequence: u64,
+    }
+
+    impl Chain {
+        fn new(vault: &str, liquidity: u128) -> Self {
+            let mut env = mock_env();
+            env.contract.address = Addr::unchecked(vault);
+
+            let mut deps = mock_dependencies();
+            deps.querier
+                .update_balance(vault, vec![coin(liquidity, DENOM)]);
+
+            let msg = InstantiateMsg {
+                denom: DENOM.to_string(),
+                gateway: None,
+                staking: None,
+                withdrawal_cooldown: 0,
+                share_token: None,
+                fee_curve: None,
+            };
+            let info = mock_info(OWNER, &[coin(liquidity, DENOM)]);
+            instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+            Chain {
+                deps,
+                env,
+                sequence: 0,
+            }
+        }
+
+        fn vault(&self) -> &str {
+            self.env.contract.address.as_str()
+        }
+
+        fn balance(&self) -> Uint128 {
+            self.deps
+                .as_ref()
+                .querier
+                .query_balance(self.vault(), DENOM)
+                .unwrap()
+                .amount
+        }
+
+        /// Moves `amount` in or out of the vault, as ICS-20 transfers do
+        fn transfer(&mut self, amount: i128) {
+            let balance = self.balance().u128() as i128 + amount;
+            let vault = self.vault().to_string();
+
+            self.deps
+                .querier
+                .update_balance(vault, vec![coin(balance as u128, DENOM)]);
+        }
+
+        fn execute(&mut self, msg: ExecuteMsg) -> Result<Response, ContractError> {
+            execute(
+                self.deps.as_mut(),
+                self.env.clone(),
+                mock_info(OWNER, &[]),
+                msg,
+            )
+        }
+
+        fn withdraw(&mut self, shares: u128) -> Result<Response, ContractError> {
+            self.execute(ExecuteMsg::Withdraw {
+                shares: Uint128::new(shares),
+            })
+        }
+
+        fn total_assets(&self) -> TotalAssetsResponse {
+            let msg = QueryMsg::TotalAssets {};
+            from_json(query(self.deps.as_ref(), self.env.clone(), msg).unwrap()).unwrap()
+        }
+
+        fn remote_vaults(&self) -> Vec<RemoteVault> {
+            let msg = QueryMsg::RemoteVaults {};
+            let response: RemoteVaultsResponse =
+                from_json(query(self.deps.as_ref(), self.env.clone(), msg).unwrap()).unwrap();
+
+            response.vaults
+        }
+    }
+
+    fn channel(
+        chain: &Chain,
+        channel_id: &str,
+        counterparty: &Chain,
+        order: IbcOrder,
+    ) -> IbcChannel {
+        IbcChannel::new(
+            IbcEndpoint {
+                port_id: format!("wasm.{}", chain.vault()),
+                channel_id: channel_id.to_string(),
+            },
+            IbcEndpoint {
+                port_id: format!("wasm.{}", counterparty.vault()),
+                channel_id: if channel_id == "channel-a" {
+                    "channel-b".to_string()
+                } else {
+                    "channel-a".to_string()
+                },
+            },
+            order,
+            IBC_VERSION,
+            "connection-0",
+        )
+    }
+
+    /// Has the owner of `chain` allow the vault on `remote` to connect
+    fn allow(chain: &mut Chain, remote: &Chain) {
+        chain
+            .execute(ExecuteMsg::UpdateAllowedRemoteVaults {
+                add: vec![format!("wasm.{}", remote.vault())],
+                remove: vec![],
+            })
+            .unwrap();
+    }
+
+    /// Runs the channel handshake between the vaults on `a` and `b`, on `channel-a` and
+    /// `channel-b` respectively, and has them lend over `transfer-a` and `transfer-b`
+    fn connect(a: &mut Chain, b: &mut Chain, min_liquidity: u128) {
+        allow(a, b);
+        allow(b, a);
+
+        let channel_a = channel(a, "channel-a", b, IbcOrder::Unordered);
+        let channel_b = channel(b, "channel-b", a, IbcOrder::Unordered);
+
+        let msg = IbcChannelOpenMsg::new_init(channel_a.clone());
+        ibc_channel_open(a.deps.as_mut(), a.env.clone(), msg).unwrap();
+        let msg = IbcChannelOpenMsg::new_try(channel_b.clone(), IBC_VERSION);
+        ibc_channel_open(b.deps.as_mut(), b.env.clone(), msg).unwrap();
+        let msg = IbcChannelConnectMsg::new_ack(channel_a, IBC_VERSION);
+        ibc_channel_connect(a.deps.as_mut(), a.env.clone(), msg).unwrap();
+        let msg = IbcChannelConnectMsg::new_confirm(channel_b);
+        ibc_channel_connect(b.deps.as_mut(), b.env.clone(), msg).unwrap();
+
+        for (chain, channel_id, transfer_channel) in [
+            (a, "channel-a", "transfer-a"),
+            (b, "channel-b", "transfer-b"),
+        ] {
+            chain
+                .execute(ExecuteMsg::UpdateRemoteVault {
+                    channel_id: channel_id.to_string(),
+                    transfer_channel: transfer_channel.to_string(),
+                    min_liquidity: Uint128::new(min_liquidity),
+                })
+                .unwrap();
+        }
+    }
+
+    /// Channel the vault on `chain` is connected over, and the one on the counterparty's side
+    fn channels(chain: &Chain) -> (&'static str, &'static str) {
+        if chain.vault() == "vault_a" {
+            ("channel-a", "channel-b")
+        } else {
+            ("channel-b", "channel-a")
+        }
+    }
+
+    /// Channel the vault on `chain` lends over
+    fn transfer_channel(chain: &Chain) -> String {
+        channels(chain).0.replace("channel", "transfer")
+    }
+
+    /// Delivers the packet `src` sent in `msg` to `dest`
+    fn deliver(src: &Chain, dest: &mut Chain, msg: &CosmosMsg) -> (Binary, IbcReceiveResponse) {
+        let (src_channel, dest_channel) = channels(src);
+
+        let data = match msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket {
+                channel_id, data, ..
+            }) => {
+                assert_eq!(channel_id, src_channel);
+                data.clone()
+            }
+            msg => panic!("unexpected message: {:?}", msg),
+        };
+
+        let packet = from_json::<VaultPacket>(&data).unwrap();
+        let mut msg = mock_ibc_packet_recv(dest_channel, &packet).unwrap();
+        msg.packet.src.channel_id = src_channel.to_string();
+        let received = ibc_packet_receive(dest.deps.as_mut(), dest.env.clone(), msg).unwrap();
+
+        (data, received)
+    }
+
+    /// Relays the acknowledgement `received` back to `src`, which sent the packet with `data`
+    fn acknowledge(src: &mut Chain, data: &Binary, received: &IbcReceiveResponse) {
+        let packet = from_json::<VaultPacket>(data).unwrap();
+        let ack = IbcAcknowledgement::new(received.acknowledgement.clone());
+        let msg = mock_ibc_packet_ack(channels(src).0, &packet, ack).unwrap();
+        ibc_packet_ack(src.deps.as_mut(), src.env.clone(), msg).unwrap();
+    }
+
+    /// Relays the packet `src` sent in `msg` to `dest`, and the acknowledgement back
+    fn relay(src: &mut Chain, dest: &mut Chain, msg: &CosmosMsg) -> IbcReceiveResponse {
+        let (data, received) = deliver(src, dest, msg);
+        acknowledge(src, &data, &received);
+
+        received
+    }
+
+    /// Sends the ICS-20 transfers the lender lent in `received`, replying with their sequence.
+    /// They leave the lender, but are up to the tests to land.
+    fn send_transfers(lender: &mut Chain, borrower: &Chain, received: &IbcReceiveResponse) {
+        for sub_msg in &received.messages {
+            let msg = match &sub_msg.msg {
+                CosmosMsg::Stargate { type_url, value } => {
+                    assert_eq!(type_url, "/ibc.applications.transfer.v1.MsgTransfer");
+                    MsgTransfer::decode(value.as_slice()).unwrap()
+                }
+                msg => panic!("unexpected message: {:?}", msg),
+            };
+            assert_eq!(msg.source_channel, transfer_channel(lender));
+            assert_eq!(msg.receiver, borrower.vault());
+            assert_eq!(
+                msg.memo,
+                format!(r#"{{"ibc_callback":"{}"}}"#, lender.vault())
+            );
+
+            let amount: u128 = msg.token.unwrap().amount.parse().unwrap();
+            lender.transfer(-(amount as i128));
+            lender.sequence += 1;
+
+            let data = MsgTransferResponse {
+                sequence: lender.sequence,
+            }
+            .encode_to_vec();
+            let msg = Reply {
+                id: sub_msg.id,
+                result: SubMsgResult::Ok(SubMsgResponse {
+                    events: vec![],
+                    data: Some(data.into()),
+                }),
+            };
+            reply(lender.deps.as_mut(), lender.env.clone(), msg).unwrap();
+        }
+    }
+
+    /// Has `borrower` request `amount` from `lender`, relaying the packet and the acknowledgement,
+    /// and sending the ICS-20 transfer it led to
+    fn request_liquidity(borrower: &mut Chain, lender: &mut Chain, amount: u128) -> VaultAck {
+        let response = borrower
+            .execute(ExecuteMsg::RequestLiquidity {
+                channel_id: channels(borrower).0.to_string(),
+                amount: Uint128::new(amount),
+            })
+            .unwrap();
+        let received = relay(borrower, lender, &response.messages[0].msg);
+        send_transfers(lender, borrower, &received);
+
+        from_json(&received.acknowledgement).unwrap()
+    }
+
+    /// What the IBC hooks report of the transfer sent as `sequence` on `channel`
+    fn transfer_acked(channel: &str, sequence: u64, success: bool) -> IbcLifecycleComplete {
+        IbcLifecycleComplete::IbcAck {
+            channel: channel.to_string(),
+            sequence,
+            ack: String::new(),
+            success,
+        }
+    }
+
+    fn transfer_timed_out(channel: &str, sequence: u64) -> IbcLifecycleComplete {
+        IbcLifecycleComplete::IbcTimeout {
+            channel: channel.to_string(),
+            sequence,
+        }
+    }
+
+    /// Reports the `outcome` of a transfer to `lender`, without relaying the settlement
+    fn complete_transfer(
+        lender: &mut Chain,
+        outcome: IbcLifecycleComplete,
+    ) -> Result<Response, ContractError> {
+        let msg = SudoMsg::IbcLifecycleComplete(outcome);
+        sudo(lender.deps.as_mut(), lender.env.clone(), msg)
+    }
+
+    /// Reports the `outcome` of a transfer to `lender`, relaying the settlement to `borrower`
+    fn settle_transfer(
+        lender: &mut Chain,
+        borrower: &mut Chain,
+        outcome: IbcLifecycleComplete,
+    ) -> Result<VaultAck, ContractError> {
+        let response = complete_transfer(lender, outcome)?;
+        let received = relay(lender, borrower, &response.messages[0].msg);
+
+        Ok(from_json(&received.acknowledgement).unwrap())
+    }
+
+    /// Lends `amount` from `lender` to `borrower` and lands it, settling the loan
+    fn move_liquidity(borrower: &mut Chain, lender: &mut Chain, amount: u128) {
+        let ack = request_liquidity(borrower, lender, amount);
+        assert!(matches!(ack, VaultAck::Lent { .. }));
+
+        borrower.transfer(amount as i128);
+        let outcome = transfer_acked(&transfer_channel(lender), lender.sequence, true);
+        let ack = settle_transfer(lender, borrower, outcome).unwrap();
+        assert_eq!(ack, VaultAck::Settled {});
+    }
+
+    #[test]
+    fn only_vaults_connect() {
+        let a = Chain::new("vault_a", 1_000);
+        let mut b = Chain::new("vault_b", 1_000);
+
+        // any contract could use the version, only allowed vaults connect
+        let unlisted = channel(&b, "channel-b", &a, IbcOrder::Unordered);
+        let msg = IbcChannelOpenMsg::new_try(unlisted, IBC_VERSION);
+        let err = ibc_channel_open(b.deps.as_mut(), b.env.clone(), msg).unwrap_err();
+        assert!(
+            matches!(err, ContractError::RemoteVaultNotAllowed { port_id } if port_id == "wasm.vault_a")
+        );
+
+        allow(&mut b, &a);
+        let allowed = channel(&b, "channel-b", &a, IbcOrder::Unordered);
+        let msg = IbcChannelOpenMsg::new_try(allowed, IBC_VERSION);
+        ibc_channel_open(b.deps.as_mut(), b.env.clone(), msg).unwrap();
+
+        let ordered = channel(&b, "channel-b", &a, IbcOrder::Ordered);
+        let msg = IbcChannelOpenMsg::new_try(ordered, IBC_VERSION);
+        let err = ibc_channel_open(b.deps.as_mut(), b.env.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidIbcChannel { .. }));
+
+        let unordered = channel(&b, "channel-b", &a, IbcOrder::Unordered);
+        let msg = IbcChannelOpenMsg::new_try(unordered.clone(), "ics20-1");
+        let err = ibc_channel_open(b.deps.as_mut(), b.env.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidIbcChannel { .. }));
+
+        let mut transfer = unordered;
+        transfer.counterparty_endpoint.port_id = "transfer".to_string();
+        let msg = IbcChannelOpenMsg::new_try(transfer, IBC_VERSION);
+        let err = ibc_channel_open(b.deps.as_mut(), b.env.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidIbcChannel { .. }));
+
+        assert_eq!(b.remote_vaults(), vec![]);
+    }
+
+    #[test]
+    fn liquidity_moves_between_chains() {
+        let mut a = Chain::new("vault_a", 1_000_000);
+        let mut b = Chain::new("vault_b", 100_000);
+        connect(&mut a, &mut b, 50_000);
+
+        let ack = request_liquidity(&mut b, &mut a, 300_000);
+        assert_eq!(
+            ack,
+            VaultAck::Lent {
+                amount: Uint128::new(300_000),
+                transfer_id: 1,
+                timeout: a.env.block.time.plus_seconds(PACKET_LIFETIME),
+            }
+        );
+
+        // in flight, the liquidity backs neither vault's shares, nor can shares be minted or burnt
+        assert_eq!(a.total_assets().total, Uint128::new(700_000));
+        b.transfer(300_000);
+        let assets = b.total_assets();
+        assert_eq!(assets.receiving_remotely, Uint128::new(300_000));
+        assert_eq!(assets.total, Uint128::new(100_000));
+
+        for chain in [&mut a, &mut b] {
+            let info = mock_info(OWNER, &[coin(1_000, DENOM)]);
+            let msg = ExecuteMsg::Deposit {};
+            let err = execute(chain.deps.as_mut(), chain.env.clone(), info, msg).unwrap_err();
+            assert!(matches!(err, ContractError::TransferInFlight { .. }));
+
+            let err = chain.withdraw(1_000).unwrap_err();
+            assert!(matches!(err, ContractError::TransferInFlight { .. }));
+        }
+
+        // the IBC hooks tell the lender it landed, which tells the borrower
+        let ack = settle_transfer(&mut a, &mut b, transfer_acked("transfer-a", 1, true)).unwrap();
+        assert_eq!(ack, VaultAck::Settled {});
+
+        // lent liquidity still backs the lender's shares, and not the borrower's
+        let assets = a.total_assets();
+        assert_eq!(assets.liquid, Uint128::new(700_000));
+        assert_eq!(assets.lent_remotely, Uint128::new(300_000));
+        assert_eq!(assets.total, Uint128::new(1_000_000));
+
+        let assets = b.total_assets();
+        assert_eq!(assets.liquid, Uint128::new(400_000));
+        assert_eq!(assets.borrowed_remotely, Uint128::new(300_000));
+        assert_eq!(assets.receiving_remotely, Uint128::zero());
+        assert_eq!(assets.total, Uint128::new(100_000));
+
+        // lending it back settles the ledgers
+        move_liquidity(&mut a, &mut b, 300_000);
+
+        for (chain, liquid) in [(&a, 1_000_000), (&b, 100_000)] {
+            let assets = chain.total_assets();
+            assert_eq!(assets.liquid, Uint128::new(liquid));
+            assert_eq!(assets.lent_remotely, Uint128::zero());
+            assert_eq!(assets.borrowed_remotely, Uint128::zero());
+            assert_eq!(assets.total, Uint128::new(liquid));
+
+            let remote = &chain.remote_vaults()[0];
+            assert_eq!(remote.sent, Uint128::new(300_000));
+            assert_eq!(remote.received, Uint128::new(300_000));
+            assert_eq!(remote.sending, Uint128::zero());
+            assert_eq!(remote.receiving, Uint128::zero());
+        }
+    }
+
+    #[test]
+    fn refunded_transfers_are_not_booked() {
+        let mut a = Chain::new("vault_a", 1_000_000);
+        let mut b = Chain::new("vault_b", 100_000);
+        connect(&mut a, &mut b, 0);
+
+        // one times out, the other fails to land
+        request_liquidity(&mut b, &mut a, 300_000);
+        request_liquidity(&mut b, &mut a, 200_000);
+        a.transfer(500_000);
+
+        let ack = settle_transfer(&mut a, &mut b, transfer_timed_out("transfer-a", 1)).unwrap();
+        assert_eq!(ack, VaultAck::Settled {});
+        let ack = settle_transfer(&mut a, &mut b, transfer_acked("transfer-a", 2, false)).unwrap();
+        assert_eq!(ack, VaultAck::Settled {});
+
+        for (chain, liquid) in [(&a, 1_000_000), (&b, 100_000)] {
+            let assets = chain.total_assets();
+            assert_eq!(assets.liquid, Uint128::new(liquid));
+            assert_eq!(assets.lent_remotely, Uint128::zero());
+            assert_eq!(assets.borrowed_remotely, Uint128::zero());
+            assert_eq!(assets.receiving_remotely, Uint128::zero());
+            assert_eq!(assets.total, Uint128::new(liquid));
+
+            let remote = &chain.remote_vaults()[0];
+            assert_eq!(remote.sent, Uint128::zero());
+            assert_eq!(remote.received, Uint128::zero());
+            assert_eq!(remote.sending, Uint128::zero());
+            assert_eq!(remote.receiving, Uint128::zero());
+        }
+
+        // the transfer is settled once and for all
+        let err = complete_transfer(&mut a, transfer_acked("transfer-a", 1, true)).unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::UnknownTransferPacket { sequence: 1, .. }
+        ));
+    }
+
+    #[test]
+    fn settlements_outrunning_the_loan_are_booked_once() {
+        let mut a = Chain::new("vault_a", 1_000_000);
+        let mut b = Chain::new("vault_b", 100_000);
+        connect(&mut a, &mut b, 0);
+
+        // the transfer lands and settles before the borrower hears it was lent
+        let msg = ExecuteMsg::RequestLiquidity {
+            channel_id: "channel-b".to_string(),
+            amount: Uint128::new(300_000),
+        };
+        let response = b.execute(msg).unwrap();
+        let (request, lent) = deliver(&b, &mut a, &response.messages[0].msg);
+        send_transfers(&mut a, &b, &lent);
+        b.transfer(300_000);
+
+        let ack = settle_transfer(&mut a, &mut b, transfer_acked("transfer-a", 1, true)).unwrap();
+        assert_eq!(ack, VaultAck::Settled {});
+        acknowledge(&mut b, &request, &lent);
+
+        let remote = &b.remote_vaults()[0];
+        assert_eq!(remote.received, Uint128::new(300_000));
+        assert_eq!(remote.receiving, Uint128::zero());
+        assert_eq!(b.total_assets().total, Uint128::new(100_000));
+        b.withdraw(1_000).unwrap();
+    }
+
+    #[test]
+    fn settlements_are_sent_again_until_booked() {
+        let mut a = Chain::new("vault_a", 1_000_000);
+        let mut b = Chain::new("vault_b", 100_000);
+        connect(&mut a, &mut b, 0);
+
+        request_liquidity(&mut b, &mut a, 300_000);
+        b.transfer(300_000);
+
+        let response = complete_transfer(&mut a, transfer_acked("transfer-a", 1, true)).unwrap();
+        let settlement: VaultPacket = match &response.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => from_json(data).unwrap(),
+            msg => panic!("unexpected message: {:?}", msg),
+        };
+        assert_eq!(
+            settlement,
+            VaultPacket::TransferSettled {
+                transfer_id: 1,
+                amount: Uint128::new(300_000),
+                landed: true,
+            }
+        );
+
+        // the lender booked it, the borrower still waits for it
+        assert_eq!(a.remote_vaults()[0].sent, Uint128::new(300_000));
+        assert_eq!(b.remote_vaults()[0].receiving, Uint128::new(300_000));
+
+        let msg = mock_ibc_packet_timeout("channel-a", &settlement).unwrap();
+        let timed_out = ibc_packet_timeout(a.deps.as_mut(), a.env.clone(), msg).unwrap();
+        relay(&mut a, &mut b, &timed_out.messages[0].msg);
+
+        let remote = &b.remote_vaults()[0];
+        assert_eq!(remote.received, Uint128::new(300_000));
+        assert_eq!(remote.receiving, Uint128::zero());
+    }
+
+    #[test]
+    fn lenders_keep_their_minimum_liquidity() {
+        let mut a = Chain::new("vault_a", 1_000_000);
+        let mut b = Chain::new("vault_b", 100_000);
+        connect(&mut a, &mut b, 500_000);
+
+        let ack = request_liquidity(&mut b, &mut a, 500_001);
+        let expected = ContractError::InsufficientLiquidity {
+            available: coin(500_000, DENOM),
+        };
+        assert_eq!(ack, VaultAck::Error(expected.to_string()));
+
+        assert_eq!(a.balance(), Uint128::new(1_000_000));
+        assert_eq!(b.balance(), Uint128::new(100_000));
+        assert_eq!(a.remote_vaults()[0].sent, Uint128::zero());
+        assert_eq!(b.remote_vaults()[0].received, Uint128::zero());
+
+        let ack = request_liquidity(&mut b, &mut a, 500_000);
+        assert!(matches!(ack, VaultAck::Lent { amount, .. } if amount == Uint128::new(500_000)));
+    }
+
+    #[test]
+    fn only_owner_moves_liquidity() {
+        let mut a = Chain::new("vault_a", 1_000_000);
+        let mut b = Chain::new("vault_b", 100_000);
+
+        let msg = ExecuteMsg::RequestLiquidity {
+            channel_id: "channel-b".to_string(),
+            amount: Uint128::new(1_000),
+        };
+        let err = b.execute(msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::UnknownRemoteVault { .. }));
+
+        connect(&mut a, &mut b, 0);
+
+        let info = mock_info("anyone", &[]);
+        let err = execute(b.deps.as_mut(), b.env.clone(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        let msg = ExecuteMsg::UpdateAllowedRemoteVaults {
+            add: vec!["wasm.anyone".to_string()],
+            remove: vec![],
+        };
+        let info = mock_info("anyone", &[]);
+        let err = execute(b.deps.as_mut(), b.env.clone(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+    }
+}
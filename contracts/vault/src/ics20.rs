@@ -0,0 +1,77 @@
+//! ICS-20 transfers the vault gets told the outcome of.
+//!
+//! Transfers are sent as `MsgTransfer` with an `ibc_callback` memo naming the vault, for the
+//! chain's IBC hooks to call its `sudo` entry point once the transfer got acknowledged or timed
+//! out. Only the messages the vault needs are defined, following the
+//! `ibc.applications.transfer.v1` protobuf definitions.
+
+use cosmwasm_std::{Addr, Binary, Coin, CosmosMsg, StdError, StdResult, Timestamp};
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct ProtoCoin {
+    #[prost(string, tag = "1")]
+    pub denom: String,
+    #[prost(string, tag = "2")]
+    pub amount: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct MsgTransfer {
+    #[prost(string, tag = "1")]
+    pub source_port: String,
+    #[prost(string, tag = "2")]
+    pub source_channel: String,
+    #[prost(message, optional, tag = "3")]
+    pub token: Option<ProtoCoin>,
+    #[prost(string, tag = "4")]
+    pub sender: String,
+    #[prost(string, tag = "5")]
+    pub receiver: String,
+    /// Nanoseconds, the transfers never time out by height
+    #[prost(uint64, tag = "7")]
+    pub timeout_timestamp: u64,
+    #[prost(string, tag = "8")]
+    pub memo: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct MsgTransferResponse {
+    #[prost(uint64, tag = "1")]
+    pub sequence: u64,
+}
+
+/// Sends `amount` from `vault` to `receiver` over `channel_id`, calling the vault back
+/// with the outcome. Its response carries the packet's sequence the callback refers to.
+pub fn transfer_msg(
+    vault: &Addr,
+    channel_id: &str,
+    amount: Coin,
+    receiver: &str,
+    timeout: Timestamp,
+) -> CosmosMsg {
+    let msg = MsgTransfer {
+        source_port: "transfer".to_string(),
+        source_channel: channel_id.to_string(),
+        token: Some(ProtoCoin {
+            denom: amount.denom,
+            amount: amount.amount.to_string(),
+        }),
+        sender: vault.to_string(),
+        receiver: receiver.to_string(),
+        timeout_timestamp: timeout.nanos(),
+        memo: format!(r#"{{"ibc_callback":"{}"}}"#, vault),
+    };
+
+    CosmosMsg::Stargate {
+        type_url: "/ibc.applications.transfer.v1.MsgTransfer".to_string(),
+        value: msg.encode_to_vec().into(),
+    }
+}
+
+/// Sequence of the packet sent by a `MsgTransfer`, from the data of its response
+pub fn parse_sequence(data: &Binary) -> StdResult<u64> {
+    MsgTransferResponse::decode(data.as_slice())
+        .map(|response| response.sequence)
+        .map_err(|err| StdError::parse_err("MsgTransferResponse", err))
+}
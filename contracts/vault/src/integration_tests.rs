@@ -0,0 +1,576 @@
+#[cfg(test)]
+mod tests {
+    use crate::helpers::Contract as CwVaultContract;
+    use crate::msg::{
+        ExecuteMsg, InstantiateMsg, PendingWithdrawalsResponse, QueryMsg, ReceiveMsg,
+        ShareTokenMsg, SharesResponse, SudoMsg, TotalAssetsResponse,
+    };
+    use crate::state::{Config, FeeCurve, PendingWithdrawal, ShareToken, StakingConfig};
+    use crate::ContractError;
+    use cosmwasm_std::testing::mock_env;
+    use cosmwasm_std::{coin, coins, to_json_binary, Addr, Decimal, Empty, Uint128, Validator};
+    use cw20::{Cw20ExecuteMsg, Cw20QueryMsg, Cw20ReceiveMsg, TokenInfoResponse};
+    use cw_multi_test::{
+        next_block, App, AppBuilder, Contract, ContractWrapper, Executor, StakingInfo,
+    };
+
+    pub fn cw_vault_contract() -> Box<dyn Contract<Empty>> {
+        let contract = ContractWrapper::new(
+            crate::contract::execute,
+            crate::contract::instantiate,
+            crate::contract::query,
+        )
+        .with_reply(crate::contract::reply)
+        .with_sudo(crate::contract::sudo);
+
+        Box::new(contract)
+    }
+
+    pub fn cw20_base_contract() -> Box<dyn Contract<Empty>> {
+        let contract = ContractWrapper::new(
+            cw20_base::contract::execute,
+            cw20_base::contract::instantiate,
+            cw20_base::contract::query,
+        );
+
+        Box::new(contract)
+    }
+
+    const ADMIN: &str = "admin";
+    const GATEWAY: &str = "gateway";
+    const PROVIDER: &str = "provider";
+    const VALIDATOR: &str = "validator";
+    const YEAR: u64 = 365 * 24 * 60 * 60;
+
+    fn mock_app() -> App {
+        AppBuilder::new().build(|router, api, storage| {
+            router
+                .bank
+                .init_balance(
+                    storage,
+                    &Addr::unchecked(ADMIN),
+                    vec![coin(1_000_000_000, "uluna"), coin(1_000_000_000, "uusd")],
+                )
+                .unwrap();
+
+            router
+                .bank
+                .init_balance(
+                    storage,
+                    &Addr::unchecked(PROVIDER),
+                    coins(1_000_000, "uluna"),
+                )
+                .unwrap();
+
+            router
+                .staking
+                .setup(
+                    storage,
+                    StakingInfo {
+                        bonded_denom: "uluna".to_string(),
+                        unbonding_time: 60,
+                        apr: Decimal::percent(10),
+                    },
+                )
+                .unwrap();
+
+            router
+                .staking
+                .add_validator(
+                    api,
+                    storage,
+                    &mock_env().block,
+                    Validator {
+                        address: VALIDATOR.to_string(),
+                        commission: Decimal::zero(),
+                        max_commission: Decimal::one(),
+                        max_change_rate: Decimal::one(),
+                    },
+                )
+                .unwrap();
+        })
+    }
+
+    fn staking_config() -> StakingConfig {
+        StakingConfig {
+            validators: vec![VALIDATOR.to_string()],
+            staked_fraction: Decimal::percent(50),
+            max_loan: Uint128::new(600_000),
+            unbonding_period: 60,
+        }
+    }
+
+    /// Vault holding 1_000_000 uluna deposited by the admin
+    fn instantiate_vault(
+        app: &mut App,
+        staking: Option<StakingConfig>,
+        withdrawal_cooldown: u64,
+    ) -> CwVaultContract {
+        let cw_vault_id = app.store_code(cw_vault_contract());
+        let cw_vault_contract_addr = app
+            .instantiate_contract(
+                cw_vault_id,
+                Addr::unchecked(ADMIN),
+                &InstantiateMsg {
+                    denom: "uluna".to_string(),
+                    gateway: Some(GATEWAY.to_string()),
+                    staking,
+                    withdrawal_cooldown,
+                    share_token: None,
+                    fee_curve: None,
+                },
+                &coins(1_000_000, "uluna"),
+                "vault",
+                None,
+            )
+            .unwrap();
+
+        CwVaultContract(cw_vault_contract_addr)
+    }
+
+    /// Vault staking half of its assets as long as 600_000 uluna stay available for loans
+    fn proper_instantiate() -> (App, CwVaultContract) {
+        let mut app = mock_app();
+        let vault = instantiate_vault(&mut app, Some(staking_config()), 0);
+
+        (app, vault)
+    }
+
+    fn total_assets(app: &App, vault: &CwVaultContract) -> TotalAssetsResponse {
+        app.wrap()
+            .query_wasm_smart(vault.addr(), &QueryMsg::TotalAssets {})
+            .unwrap()
+    }
+
+    fn shares(app: &App, vault: &CwVaultContract, provider: &str) -> SharesResponse {
+        app.wrap()
+            .query_wasm_smart(
+                vault.addr(),
+                &QueryMsg::Shares {
+                    provider: provider.to_string(),
+                },
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn idle_liquidity_earns_staking_rewards() {
+        let (mut app, vault) = proper_instantiate();
+
+        // Half of the assets would be 500_000, but 600_000 must stay liquid
+        assert_eq!(
+            total_assets(&app, &vault),
+            TotalAssetsResponse {
+                liquid: Uint128::new(600_000),
+                pending_withdrawals: Uint128::zero(),
+                staked: Uint128::new(400_000),
+                rewards: Uint128::zero(),
+                unbonding: Uint128::zero(),
+                lent_remotely: Uint128::zero(),
+                borrowed_remotely: Uint128::zero(),
+                receiving_remotely: Uint128::zero(),
+                total: Uint128::new(1_000_000),
+                total_shares: Uint128::new(1_000_000),
+            }
+        );
+
+        app.update_block(|block| block.time = block.time.plus_seconds(YEAR));
+
+        // 10% APR on the staked part
+        let assets = total_assets(&app, &vault);
+        assert_eq!(assets.rewards, Uint128::new(40_000));
+        // Share values round down, in favour of the vault
+        assert_eq!(shares(&app, &vault, ADMIN).value, coin(1_039_999, "uluna"));
+
+        // Newcomers pay for the rewards accrued so far
+        app.execute_contract(
+            Addr::unchecked(PROVIDER),
+            vault.addr(),
+            &ExecuteMsg::Deposit {},
+            &coins(104_000, "uluna"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            shares(&app, &vault, PROVIDER),
+            SharesResponse {
+                shares: Uint128::new(100_000),
+                value: coin(103_999, "uluna"),
+            }
+        );
+
+        // Rewards got claimed and the surplus above the max loan staked
+        assert_eq!(
+            total_assets(&app, &vault),
+            TotalAssetsResponse {
+                liquid: Uint128::new(600_000),
+                pending_withdrawals: Uint128::zero(),
+                staked: Uint128::new(544_000),
+                rewards: Uint128::zero(),
+                unbonding: Uint128::zero(),
+                lent_remotely: Uint128::zero(),
+                borrowed_remotely: Uint128::zero(),
+                receiving_remotely: Uint128::zero(),
+                total: Uint128::new(1_144_000),
+                total_shares: Uint128::new(1_100_000),
+            }
+        );
+    }
+
+    #[test]
+    fn withdrawals_unstake_to_restore_liquidity() {
+        let (mut app, vault) = proper_instantiate();
+
+        let err = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                vault.addr(),
+                &ExecuteMsg::Withdraw {
+                    shares: Uint128::new(700_000),
+                },
+                &[],
+            )
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<ContractError>(),
+            Some(ContractError::InsufficientLiquidity { .. })
+        ));
+
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            vault.addr(),
+            &ExecuteMsg::Withdraw {
+                shares: Uint128::new(500_000),
+            },
+            &[],
+        )
+        .unwrap();
+
+        // Only 100_000 are left in the vault, so everything staked is on its way back
+        assert_eq!(
+            total_assets(&app, &vault),
+            TotalAssetsResponse {
+                liquid: Uint128::new(100_000),
+                pending_withdrawals: Uint128::zero(),
+                staked: Uint128::zero(),
+                rewards: Uint128::zero(),
+                unbonding: Uint128::new(400_000),
+                lent_remotely: Uint128::zero(),
+                borrowed_remotely: Uint128::zero(),
+                receiving_remotely: Uint128::zero(),
+                total: Uint128::new(500_000),
+                total_shares: Uint128::new(500_000),
+            }
+        );
+
+        // Matured unbondings get paid out when the following block starts
+        app.update_block(|block| block.time = block.time.plus_seconds(61));
+        app.update_block(next_block);
+
+        let assets = total_assets(&app, &vault);
+        assert_eq!(assets.liquid, Uint128::new(500_000));
+        assert_eq!(assets.unbonding, Uint128::zero());
+        assert_eq!(assets.total, Uint128::new(500_000));
+    }
+
+    #[test]
+    fn only_bonded_denom_is_staked() {
+        let mut app = mock_app();
+
+        let cw_vault_id = app.store_code(cw_vault_contract());
+        let err = app
+            .instantiate_contract(
+                cw_vault_id,
+                Addr::unchecked(ADMIN),
+                &InstantiateMsg {
+                    denom: "uusd".to_string(),
+                    gateway: None,
+                    staking: Some(staking_config()),
+                    withdrawal_cooldown: 0,
+                    share_token: None,
+                    fee_curve: None,
+                },
+                &coins(1_000_000, "uusd"),
+                "vault",
+                None,
+            )
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<ContractError>(),
+            Some(ContractError::NotStakeDenom { .. })
+        ));
+    }
+
+    #[test]
+    fn withdrawals_wait_for_the_cooldown() {
+        let mut app = mock_app();
+        let vault = instantiate_vault(&mut app, None, 3_600);
+
+        let withdrawn_at = app.block_info().time;
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            vault.addr(),
+            &ExecuteMsg::Withdraw {
+                shares: Uint128::new(400_000),
+            },
+            &[],
+        )
+        .unwrap();
+
+        // The funds are still in the vault, but no longer backing shares nor loans
+        let assets = total_assets(&app, &vault);
+        assert_eq!(assets.liquid, Uint128::new(600_000));
+        assert_eq!(assets.pending_withdrawals, Uint128::new(400_000));
+        assert_eq!(assets.total, Uint128::new(600_000));
+
+        let err = app
+            .execute_contract(
+                Addr::unchecked(GATEWAY),
+                vault.addr(),
+                &ExecuteMsg::ProvideAsset {
+                    asset: coin(700_000, "uluna"),
+                    borrower_addr: PROVIDER.to_string(),
+                },
+                &[],
+            )
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<ContractError>(),
+            Some(ContractError::AssetUnavailable {})
+        ));
+
+        let claim = |app: &mut App| {
+            app.execute_contract(
+                Addr::unchecked(ADMIN),
+                vault.addr(),
+                &ExecuteMsg::Claim {},
+                &[],
+            )
+        };
+
+        let err = claim(&mut app).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ContractError>(),
+            Some(ContractError::NothingToClaim {})
+        ));
+
+        app.update_block(|block| block.time = block.time.plus_seconds(3_600));
+
+        let query_pending = |app: &App| -> PendingWithdrawalsResponse {
+            app.wrap()
+                .query_wasm_smart(
+                    vault.addr(),
+                    &QueryMsg::PendingWithdrawals {
+                        provider: ADMIN.to_string(),
+                    },
+                )
+                .unwrap()
+        };
+
+        assert_eq!(
+            query_pending(&app),
+            PendingWithdrawalsResponse {
+                withdrawals: vec![PendingWithdrawal {
+                    amount: Uint128::new(400_000),
+                    claimable_at: withdrawn_at.plus_seconds(3_600),
+                }],
+                claimable: coin(400_000, "uluna"),
+            }
+        );
+
+        let balance_before = app.wrap().query_balance(ADMIN, "uluna").unwrap();
+        claim(&mut app).unwrap();
+        let balance_after = app.wrap().query_balance(ADMIN, "uluna").unwrap();
+
+        assert_eq!(
+            balance_after.amount - balance_before.amount,
+            Uint128::new(400_000)
+        );
+        assert!(query_pending(&app).withdrawals.is_empty());
+        assert_eq!(
+            total_assets(&app, &vault).pending_withdrawals,
+            Uint128::zero()
+        );
+    }
+
+    #[test]
+    fn cw20_shares_are_transferable() {
+        let mut app = mock_app();
+
+        let cw20_base_id = app.store_code(cw20_base_contract());
+        let cw_vault_id = app.store_code(cw_vault_contract());
+        let cw_vault_contract_addr = app
+            .instantiate_contract(
+                cw_vault_id,
+                Addr::unchecked(ADMIN),
+                &InstantiateMsg {
+                    denom: "uluna".to_string(),
+                    gateway: None,
+                    staking: None,
+                    withdrawal_cooldown: 0,
+                    share_token: Some(ShareTokenMsg::Cw20 {
+                        code_id: cw20_base_id,
+                        name: "Vault shares".to_string(),
+                        symbol: "vLUNA".to_string(),
+                    }),
+                    fee_curve: None,
+                },
+                &coins(1_000_000, "uluna"),
+                "vault",
+                None,
+            )
+            .unwrap();
+        let vault = CwVaultContract(cw_vault_contract_addr);
+
+        let config: Config = app
+            .wrap()
+            .query_wasm_smart(vault.addr(), &QueryMsg::Config {})
+            .unwrap();
+        let share_token = match config.share_token {
+            Some(ShareToken::Cw20 { addr }) => addr,
+            other => panic!("Unexpected share token {:?}", other),
+        };
+
+        // The initial deposit came with the token
+        assert_eq!(shares(&app, &vault, ADMIN).shares, Uint128::new(1_000_000));
+
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            share_token.clone(),
+            &Cw20ExecuteMsg::Transfer {
+                recipient: PROVIDER.to_string(),
+                amount: Uint128::new(400_000),
+            },
+            &[],
+        )
+        .unwrap();
+
+        app.execute_contract(
+            Addr::unchecked(PROVIDER),
+            vault.addr(),
+            &ExecuteMsg::Deposit {},
+            &coins(100_000, "uluna"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            shares(&app, &vault, PROVIDER),
+            SharesResponse {
+                shares: Uint128::new(500_000),
+                value: coin(500_000, "uluna"),
+            }
+        );
+
+        // Shares can't be withdrawn without being sent to the vault
+        let err = app
+            .execute_contract(
+                Addr::unchecked(PROVIDER),
+                vault.addr(),
+                &ExecuteMsg::Withdraw {
+                    shares: Uint128::new(500_000),
+                },
+                &[],
+            )
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ContractError>(),
+            Some(ContractError::SharesAreCw20 {})
+        ));
+
+        let withdraw_msg = to_json_binary(&ReceiveMsg::Withdraw {}).unwrap();
+
+        // ...by the share token itself
+        let err = app
+            .execute_contract(
+                Addr::unchecked(PROVIDER),
+                vault.addr(),
+                &ExecuteMsg::Receive(Cw20ReceiveMsg {
+                    sender: PROVIDER.to_string(),
+                    amount: Uint128::new(500_000),
+                    msg: withdraw_msg.clone(),
+                }),
+                &[],
+            )
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ContractError>(),
+            Some(ContractError::Unauthorized {})
+        ));
+
+        let balance_before = app.wrap().query_balance(PROVIDER, "uluna").unwrap();
+        app.execute_contract(
+            Addr::unchecked(PROVIDER),
+            share_token.clone(),
+            &Cw20ExecuteMsg::Send {
+                contract: vault.addr().to_string(),
+                amount: Uint128::new(500_000),
+                msg: withdraw_msg,
+            },
+            &[],
+        )
+        .unwrap();
+        let balance_after = app.wrap().query_balance(PROVIDER, "uluna").unwrap();
+
+        assert_eq!(
+            balance_after.amount - balance_before.amount,
+            Uint128::new(500_000)
+        );
+
+        // The shares sent to the vault got burnt
+        let token_info: TokenInfoResponse = app
+            .wrap()
+            .query_wasm_smart(&share_token, &Cw20QueryMsg::TokenInfo {})
+            .unwrap();
+        assert_eq!(token_info.total_supply, Uint128::new(600_000));
+        assert_eq!(
+            total_assets(&app, &vault).total_shares,
+            Uint128::new(600_000)
+        );
+    }
+
+    #[test]
+    fn governance_configures_the_vault() {
+        let mut app = mock_app();
+        let vault = instantiate_vault(&mut app, None, 0);
+        let fee_curve = FeeCurve {
+            base_rate: Decimal::percent(1),
+            kink: Decimal::percent(50),
+            slope_below_kink: Decimal::percent(4),
+            slope_above_kink: Decimal::percent(50),
+        };
+
+        app.wasm_sudo(
+            vault.addr(),
+            &SudoMsg::UpdateStaking {
+                staking: staking_config(),
+            },
+        )
+        .unwrap();
+        app.wasm_sudo(
+            vault.addr(),
+            &SudoMsg::UpdateFeeCurve {
+                fee_curve: fee_curve.clone(),
+            },
+        )
+        .unwrap();
+        app.wasm_sudo(
+            vault.addr(),
+            &SudoMsg::UpdateGateway {
+                gateway: "new_gateway".to_string(),
+            },
+        )
+        .unwrap();
+
+        let config: Config = app
+            .wrap()
+            .query_wasm_smart(vault.addr(), &QueryMsg::Config {})
+            .unwrap();
+        assert_eq!(config.staking, Some(staking_config()));
+        assert_eq!(config.fee_curve, fee_curve);
+        assert_eq!(config.gateway, Some(Addr::unchecked("new_gateway")));
+        assert_eq!(total_assets(&app, &vault).staked, Uint128::new(400_000));
+    }
+}
@@ -1,7 +1,19 @@
+#[cfg(feature = "client")]
+pub mod client;
 pub mod contract;
 mod error;
+pub mod fee;
 pub mod helpers;
+#[cfg(feature = "ibc")]
+pub mod ibc;
+#[cfg(feature = "ibc")]
+mod ics20;
+pub mod integration_tests;
 pub mod msg;
+pub mod share_token;
+pub mod staking;
 pub mod state;
+#[cfg(feature = "tokenfactory")]
+pub mod tokenfactory;
 
 pub use crate::error::ContractError;
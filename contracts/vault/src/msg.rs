@@ -1,9 +1,46 @@
-use cosmwasm_std::Coin;
+use cosmwasm_schema::QueryResponses;
+use cosmwasm_std::{Coin, Decimal, Uint128};
+use cw20::Cw20ReceiveMsg;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::state::{FeeCurve, PendingWithdrawal, RemoteVault, StakingConfig};
+
+/// Funds sent along are deposited on behalf of the sender
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InstantiateMsg {
+    pub denom: String,
+    /// Gateway loans are requested through, can be set later with `UpdateGateway`
+    #[serde(default)]
+    pub gateway: Option<String>,
+    #[serde(default)]
+    pub staking: Option<StakingConfig>,
+    /// Seconds between a withdrawal and its claim, zero pays withdrawals out right away
+    #[serde(default)]
+    pub withdrawal_cooldown: u64,
+    /// Token to issue LP shares as, shares are only tracked by the vault otherwise
+    #[serde(default)]
+    pub share_token: Option<ShareTokenMsg>,
+    /// Defaults to a flat 2% fee
+    #[serde(default)]
+    pub fee_curve: Option<FeeCurve>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateMsg {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ShareTokenMsg {
+    /// CW20 instantiated from `code_id`, with the vault as its minter
+    Cw20 {
+        code_id: u64,
+        name: String,
+        symbol: String,
+    },
+    /// `factory/{vault}/{subdenom}` denom created by the vault
+    #[cfg(feature = "tokenfactory")]
+    TokenFactory { subdenom: String },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -13,11 +50,160 @@ pub enum ExecuteMsg {
         asset: Coin,
         borrower_addr: String,
     },
+    /// Adds the attached funds to the vault in exchange for LP shares
+    Deposit {},
+    /// Burns LP shares in exchange for their value in liquid funds,
+    /// claimable once the withdrawal cooldown has passed.
+    /// TokenFactory shares must be sent along, CW20 ones go through `Receive`.
+    Withdraw { shares: Uint128 },
+    /// CW20 shares sent to the vault
+    Receive(Cw20ReceiveMsg),
+    /// Pays out the sender's withdrawals whose cooldown has passed
+    Claim {},
+    /// Stakes idle liquidity or unstakes some to restore it, and claims staking rewards
+    Rebalance {},
+    /// Owner only, a zero `staked_fraction` unstakes everything
+    UpdateStaking { staking: StakingConfig },
+    /// Owner only
+    UpdateFeeCurve { fee_curve: FeeCurve },
+    /// Owner only, no loan can be in progress
+    UpdateGateway { gateway: String },
+    /// Owner only, asks the remote vault on `channel_id` to lend `amount` of its idle liquidity
+    #[cfg(feature = "ibc")]
+    RequestLiquidity { channel_id: String, amount: Uint128 },
+    /// Owner only, sets which remote vaults, by their `wasm.{address}` port, can open a channel
+    #[cfg(feature = "ibc")]
+    UpdateAllowedRemoteVaults {
+        add: Vec<String>,
+        remove: Vec<String>,
+    },
+    /// Owner only, sets how liquidity gets lent to the remote vault on `channel_id`
+    #[cfg(feature = "ibc")]
+    UpdateRemoteVault {
+        channel_id: String,
+        transfer_channel: String,
+        min_liquidity: Uint128,
+    },
 }
 
+/// Governance messages mirroring the owner's, for chains where the vault is governance-owned
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
+pub enum SudoMsg {
+    UpdateStaking { staking: StakingConfig },
+    UpdateFeeCurve { fee_curve: FeeCurve },
+    UpdateGateway { gateway: String },
+    #[cfg(feature = "ibc")]
+    RequestLiquidity { channel_id: String, amount: Uint128 },
+    #[cfg(feature = "ibc")]
+    UpdateAllowedRemoteVaults {
+        add: Vec<String>,
+        remove: Vec<String>,
+    },
+    #[cfg(feature = "ibc")]
+    UpdateRemoteVault {
+        channel_id: String,
+        transfer_channel: String,
+        min_liquidity: Uint128,
+    },
+    /// Outcome of a transfer lent to a remote vault, reported by the chain's IBC hooks
+    #[cfg(feature = "ibc")]
+    IbcLifecycleComplete(IbcLifecycleComplete),
+}
+
+/// What became of an ICS-20 transfer sent with an `ibc_callback` memo
+#[cfg(feature = "ibc")]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum IbcLifecycleComplete {
+    /// The transfer landed if it was a `success`, and got refunded otherwise
+    IbcAck {
+        channel: String,
+        sequence: u64,
+        ack: String,
+        success: bool,
+    },
+    /// The transfer got refunded
+    IbcTimeout { channel: String, sequence: u64 },
+}
+
+/// Messages attached to CW20 shares sent to the vault
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiveMsg {
+    /// Burns the shares, as `ExecuteMsg::Withdraw` does
+    Withdraw {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, QueryResponses)]
+#[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
+    #[returns(crate::state::Config)]
+    Config {},
+    #[returns(TotalAssetsResponse)]
+    TotalAssets {},
+    #[returns(SharesResponse)]
+    Shares { provider: String },
+    #[returns(PendingWithdrawalsResponse)]
+    PendingWithdrawals { provider: String },
+    /// Fee the vault charges for lending `asset` right now
+    #[returns(FeeQuoteResponse)]
+    FeeQuote { asset: Coin },
+    /// Vaults on other chains connected to this one, along with what they lent each other
+    #[returns(RemoteVaultsResponse)]
+    RemoteVaults {},
+}
+
+/// Everything LP shares are backed by
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TotalAssetsResponse {
+    /// Funds held by the vault, available for loans
+    pub liquid: Uint128,
+    /// Funds held by the vault for withdrawals which haven't been claimed yet,
+    /// not part of the total
+    pub pending_withdrawals: Uint128,
+    pub staked: Uint128,
+    /// Staking rewards not claimed yet
+    pub rewards: Uint128,
+    pub unbonding: Uint128,
+    /// Lent to vaults on other chains
+    pub lent_remotely: Uint128,
+    /// Lent by vaults on other chains, part of the liquid funds but not of the total
+    pub borrowed_remotely: Uint128,
+    /// Lent by vaults on other chains through transfers which weren't settled yet,
+    /// whether or not they landed, not part of the total
+    pub receiving_remotely: Uint128,
+    pub total: Uint128,
+    pub total_shares: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SharesResponse {
+    pub shares: Uint128,
+    /// What the shares are currently worth
+    pub value: Coin,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FeeQuoteResponse {
+    pub fee: Coin,
+    pub rate: Decimal,
+    /// Share of the vault's available liquidity the loan would take
+    pub utilization: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RemoteVaultsResponse {
+    pub vaults: Vec<RemoteVault>,
+    /// Ports of the remote vaults allowed to open a channel
+    pub allowed_ports: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingWithdrawalsResponse {
+    pub withdrawals: Vec<PendingWithdrawal>,
+    /// Sum of the withdrawals which can be claimed right now
+    pub claimable: Coin,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
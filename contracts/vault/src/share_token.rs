@@ -0,0 +1,101 @@
+//! LP shares issued as a token, making them transferable and usable outside the vault.
+//!
+//! The vault is the only minter of its share token. Shares sent back to the vault
+//! for a withdrawal get burnt.
+
+use cosmwasm_std::{
+    to_json_binary, wasm_execute, Addr, CosmosMsg, QuerierWrapper, StdResult, Uint128, WasmMsg,
+};
+use cw20::{BalanceResponse, Cw20Coin, Cw20ExecuteMsg, Cw20QueryMsg, MinterResponse};
+
+use crate::state::ShareToken;
+#[cfg(feature = "tokenfactory")]
+use crate::tokenfactory;
+
+/// Instantiates the vault's CW20 share token, minting `initial_shares` to `provider`
+pub fn instantiate_cw20_msg(
+    vault: &Addr,
+    provider: &Addr,
+    code_id: u64,
+    name: String,
+    symbol: String,
+    initial_shares: Uint128,
+) -> StdResult<WasmMsg> {
+    let initial_balances = if initial_shares.is_zero() {
+        vec![]
+    } else {
+        vec![Cw20Coin {
+            address: provider.to_string(),
+            amount: initial_shares,
+        }]
+    };
+
+    Ok(WasmMsg::Instantiate {
+        admin: None,
+        code_id,
+        msg: to_json_binary(&cw20_base::msg::InstantiateMsg {
+            name,
+            symbol: symbol.clone(),
+            decimals: 6,
+            initial_balances,
+            mint: Some(MinterResponse {
+                minter: vault.to_string(),
+                cap: None,
+            }),
+            marketing: None,
+        })?,
+        funds: vec![],
+        label: format!("{} vault shares", symbol),
+    })
+}
+
+pub fn mint_msg(
+    token: &ShareToken,
+    _vault: &Addr,
+    recipient: &Addr,
+    shares: Uint128,
+) -> StdResult<CosmosMsg> {
+    match token {
+        ShareToken::Cw20 { addr } => Ok(wasm_execute(
+            addr,
+            &Cw20ExecuteMsg::Mint {
+                recipient: recipient.to_string(),
+                amount: shares,
+            },
+            vec![],
+        )?
+        .into()),
+        #[cfg(feature = "tokenfactory")]
+        ShareToken::TokenFactory { denom } => {
+            Ok(tokenfactory::mint_msg(_vault, denom, shares, recipient))
+        }
+    }
+}
+
+/// Burns `shares` held by the vault
+pub fn burn_msg(token: &ShareToken, _vault: &Addr, shares: Uint128) -> StdResult<CosmosMsg> {
+    match token {
+        ShareToken::Cw20 { addr } => {
+            Ok(wasm_execute(addr, &Cw20ExecuteMsg::Burn { amount: shares }, vec![])?.into())
+        }
+        #[cfg(feature = "tokenfactory")]
+        ShareToken::TokenFactory { denom } => Ok(tokenfactory::burn_msg(_vault, denom, shares)),
+    }
+}
+
+/// Shares held by `owner`
+pub fn balance(querier: &QuerierWrapper, token: &ShareToken, owner: &Addr) -> StdResult<Uint128> {
+    match token {
+        ShareToken::Cw20 { addr } => {
+            let response: BalanceResponse = querier.query_wasm_smart(
+                addr,
+                &Cw20QueryMsg::Balance {
+                    address: owner.to_string(),
+                },
+            )?;
+            Ok(response.balance)
+        }
+        #[cfg(feature = "tokenfactory")]
+        ShareToken::TokenFactory { denom } => Ok(querier.query_balance(owner, denom)?.amount),
+    }
+}
@@ -0,0 +1,263 @@
+//! Staking strategy putting the vault's idle liquidity to work.
+//!
+//! A configurable fraction of the vault's assets gets delegated to validators, always
+//! leaving enough liquidity in the vault for the largest loan it should be able to serve.
+//! Delegations, unclaimed rewards and funds still unbonding all back the LP shares.
+
+use cosmwasm_std::{
+    coin, Addr, CosmosMsg, DistributionMsg, QuerierWrapper, StakingMsg, StdResult, Timestamp,
+    Uint128,
+};
+
+use crate::state::{StakingConfig, Unbonding};
+
+/// A single delegation of the vault
+pub struct Delegation {
+    pub validator: String,
+    pub amount: Uint128,
+    /// Rewards accumulated since they were last claimed
+    pub rewards: Uint128,
+}
+
+/// What the vault holds at validators
+pub struct StakedAssets {
+    pub delegations: Vec<Delegation>,
+    pub unbonding: Uint128,
+}
+
+impl StakedAssets {
+    pub fn staked(&self) -> Uint128 {
+        self.delegations.iter().map(|d| d.amount).sum()
+    }
+
+    pub fn rewards(&self) -> Uint128 {
+        self.delegations.iter().map(|d| d.rewards).sum()
+    }
+}
+
+/// Collects the vault's delegations, with rewards in `denom`,
+/// and its funds which are still unbonding at `now`.
+pub fn staked_assets(
+    querier: &QuerierWrapper,
+    vault: &Addr,
+    denom: &str,
+    unbondings: &[Unbonding],
+    now: Timestamp,
+) -> StdResult<StakedAssets> {
+    let mut delegations = vec![];
+
+    for delegation in querier.query_all_delegations(vault)? {
+        let rewards = querier
+            .query_delegation(vault, delegation.validator.as_str())?
+            .map(|full| {
+                full.accumulated_rewards
+                    .iter()
+                    .filter(|reward| reward.denom == denom)
+                    .map(|reward| reward.amount)
+                    .sum()
+            })
+            .unwrap_or_default();
+
+        delegations.push(Delegation {
+            validator: delegation.validator,
+            amount: delegation.amount.amount,
+            rewards,
+        });
+    }
+
+    Ok(StakedAssets {
+        delegations,
+        unbonding: pending_unbonding(unbondings, now),
+    })
+}
+
+/// Sums up the undelegated funds which haven't made it back to the vault at `now`
+pub fn pending_unbonding(unbondings: &[Unbonding], now: Timestamp) -> Uint128 {
+    unbondings
+        .iter()
+        .filter(|unbonding| unbonding.completes_at > now)
+        .map(|unbonding| unbonding.amount)
+        .sum()
+}
+
+/// Messages claiming staking rewards and moving stake towards its target,
+/// along with the amount getting undelegated.
+pub fn rebalance_msgs(
+    staking: &StakingConfig,
+    denom: &str,
+    liquid: Uint128,
+    assets: &StakedAssets,
+) -> (Vec<CosmosMsg>, Uint128) {
+    let mut msgs: Vec<CosmosMsg> = assets
+        .delegations
+        .iter()
+        .filter(|delegation| !delegation.rewards.is_zero())
+        .map(|delegation| {
+            DistributionMsg::WithdrawDelegatorReward {
+                validator: delegation.validator.clone(),
+            }
+            .into()
+        })
+        .collect();
+
+    // claimed rewards are liquid by the time the stake gets moved
+    let liquid = liquid + assets.rewards();
+    let committed = assets.staked() + assets.unbonding;
+    let total = liquid + committed;
+
+    let target = (total * staking.staked_fraction).min(total.saturating_sub(staking.max_loan));
+
+    if target > committed {
+        let to_stake = (target - committed).min(liquid.saturating_sub(staking.max_loan));
+
+        msgs.extend(delegate_msgs(&staking.validators, denom, to_stake));
+
+        (msgs, Uint128::zero())
+    } else {
+        let to_unstake = (committed - target).min(assets.staked());
+
+        msgs.extend(undelegate_msgs(&assets.delegations, denom, to_unstake));
+
+        (msgs, to_unstake)
+    }
+}
+
+/// Spreads `amount` evenly across `validators`
+fn delegate_msgs(validators: &[String], denom: &str, amount: Uint128) -> Vec<CosmosMsg> {
+    if validators.is_empty() || amount.is_zero() {
+        return vec![];
+    }
+
+    let count = Uint128::from(validators.len() as u128);
+    let share = amount / count;
+    let remainder = amount - share * count;
+
+    validators
+        .iter()
+        .enumerate()
+        .map(|(index, validator)| {
+            let amount = if index == 0 { share + remainder } else { share };
+            (validator, amount)
+        })
+        .filter(|(_, amount)| !amount.is_zero())
+        .map(|(validator, amount)| {
+            StakingMsg::Delegate {
+                validator: validator.clone(),
+                amount: coin(amount.u128(), denom),
+            }
+            .into()
+        })
+        .collect()
+}
+
+/// Takes `amount` out of the delegations, in order
+fn undelegate_msgs(delegations: &[Delegation], denom: &str, amount: Uint128) -> Vec<CosmosMsg> {
+    let mut remaining = amount;
+    let mut msgs = vec![];
+
+    for delegation in delegations {
+        if remaining.is_zero() {
+            break;
+        }
+
+        let amount = delegation.amount.min(remaining);
+        if amount.is_zero() {
+            continue;
+        }
+        remaining -= amount;
+
+        msgs.push(
+            StakingMsg::Undelegate {
+                validator: delegation.validator.clone(),
+                amount: coin(amount.u128(), denom),
+            }
+            .into(),
+        );
+    }
+
+    msgs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::Decimal;
+
+    fn staking_config() -> StakingConfig {
+        StakingConfig {
+            validators: vec!["validator1".to_string(), "validator2".to_string()],
+            staked_fraction: Decimal::percent(50),
+            max_loan: Uint128::new(700),
+            unbonding_period: 60,
+        }
+    }
+
+    #[test]
+    fn stakes_idle_liquidity_above_max_loan() {
+        let assets = StakedAssets {
+            delegations: vec![],
+            unbonding: Uint128::zero(),
+        };
+
+        // Half of the assets would be 500, but 700 must stay liquid
+        let (msgs, undelegated) =
+            rebalance_msgs(&staking_config(), "uluna", Uint128::new(1_000), &assets);
+
+        assert!(undelegated.is_zero());
+        assert_eq!(
+            msgs,
+            vec![
+                CosmosMsg::from(StakingMsg::Delegate {
+                    validator: "validator1".to_string(),
+                    amount: coin(150, "uluna"),
+                }),
+                CosmosMsg::from(StakingMsg::Delegate {
+                    validator: "validator2".to_string(),
+                    amount: coin(150, "uluna"),
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn unstakes_to_restore_liquidity() {
+        let assets = StakedAssets {
+            delegations: vec![
+                Delegation {
+                    validator: "validator1".to_string(),
+                    amount: Uint128::new(150),
+                    rewards: Uint128::new(5),
+                },
+                Delegation {
+                    validator: "validator2".to_string(),
+                    amount: Uint128::new(150),
+                    rewards: Uint128::zero(),
+                },
+            ],
+            unbonding: Uint128::zero(),
+        };
+
+        // 205 liquid and 300 staked, while only 505 - 700 = 0 may be staked
+        let (msgs, undelegated) =
+            rebalance_msgs(&staking_config(), "uluna", Uint128::new(200), &assets);
+
+        assert_eq!(undelegated, Uint128::new(300));
+        assert_eq!(
+            msgs,
+            vec![
+                CosmosMsg::from(DistributionMsg::WithdrawDelegatorReward {
+                    validator: "validator1".to_string(),
+                }),
+                CosmosMsg::from(StakingMsg::Undelegate {
+                    validator: "validator1".to_string(),
+                    amount: coin(150, "uluna"),
+                }),
+                CosmosMsg::from(StakingMsg::Undelegate {
+                    validator: "validator2".to_string(),
+                    amount: coin(150, "uluna"),
+                }),
+            ]
+        );
+    }
+}
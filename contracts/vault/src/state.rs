@@ -1,8 +1,8 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::Coin;
-use cw_storage_plus::Item;
+use cosmwasm_std::{Addr, Coin, Decimal, Empty, Timestamp, Uint128};
+use cw_storage_plus::{Item, Map};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct State {
@@ -11,3 +11,163 @@ pub struct State {
 
 pub const STATE: Item<State> = Item::new("state");
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Config {
+    pub owner: Addr,
+    /// The only one allowed to request loans, none can be taken until it's set
+    #[serde(default)]
+    pub gateway: Option<Addr>,
+    /// The only denom the vault lends and takes deposits in
+    pub denom: String,
+    /// Where idle liquidity is put to work, if anywhere
+    pub staking: Option<StakingConfig>,
+    /// Seconds withdrawn funds stay in the vault before they can be claimed
+    pub withdrawal_cooldown: u64,
+    /// Token LP shares are issued as, if they aren't only tracked by the vault
+    pub share_token: Option<ShareToken>,
+    pub fee_curve: FeeCurve,
+}
+
+/// Vault fee rate as a function of the utilization of its available liquidity by a loan
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FeeCurve {
+    /// Rate of a loan taking none of the liquidity
+    pub base_rate: Decimal,
+    /// Utilization past which the rate grows along `slope_above_kink`
+    pub kink: Decimal,
+    /// Rate added from no utilization up to the kink
+    pub slope_below_kink: Decimal,
+    /// Rate added from the kink up to full utilization
+    pub slope_above_kink: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ShareToken {
+    Cw20 {
+        addr: Addr,
+    },
+    #[cfg(feature = "tokenfactory")]
+    TokenFactory {
+        denom: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StakingConfig {
+    /// Validators the stake is spread across
+    pub validators: Vec<String>,
+    /// Share of the vault's assets to keep delegated
+    pub staked_fraction: Decimal,
+    /// Liquidity always kept in the vault, whatever the staked fraction
+    pub max_loan: Uint128,
+    /// Chain's unbonding time in seconds, undelegated funds are accounted for until then
+    pub unbonding_period: u64,
+}
+
+/// Undelegated funds on their way back to the vault
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Unbonding {
+    pub amount: Uint128,
+    pub completes_at: Timestamp,
+}
+
+/// Withdrawn funds waiting for the cooldown to pass
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingWithdrawal {
+    pub amount: Uint128,
+    pub claimable_at: Timestamp,
+}
+
+pub const CONFIG: Item<Config> = Item::new("config");
+
+/// LP shares issued so far
+pub const TOTAL_SHARES: Item<Uint128> = Item::new("total_shares");
+
+/// LP shares held by each provider, unless shares are issued as a token
+pub const SHARES: Map<&Addr, Uint128> = Map::new("shares");
+
+pub const UNBONDINGS: Item<Vec<Unbonding>> = Item::new("unbondings");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ActiveLoan {
+    pub asset: Coin,
+    pub fee: Coin,
+    /// Vault balance once the loan is repaid along with its fee
+    pub expected_balance: Uint128,
+}
+
+/// Loan lent out by the vault until it gets repaid
+pub const ACTIVE_LOAN: Item<ActiveLoan> = Item::new("active_loan");
+
+/// Withdrawals requested by each provider, in order
+pub const PENDING_WITHDRAWALS: Map<&Addr, Vec<PendingWithdrawal>> =
+    Map::new("pending_withdrawals");
+
+/// Funds set aside for all pending withdrawals, no longer lent nor staked
+pub const TOTAL_PENDING: Item<Uint128> = Item::new("total_pending");
+
+/// Vault on another chain, connected over an IBC channel between the two vaults
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RemoteVault {
+    /// Channel between the two vaults
+    pub channel_id: String,
+    /// Counterparty vault's address on its chain
+    pub addr: String,
+    /// ICS-20 channel liquidity is sent to the counterparty through,
+    /// nothing gets lent to it until one is set
+    pub transfer_channel: Option<String>,
+    /// Liquidity the vault keeps whatever the counterparty requests
+    pub min_liquidity: Uint128,
+    /// Lent to the counterparty so far, once settled
+    pub sent: Uint128,
+    /// Lent by the counterparty so far, once settled
+    pub received: Uint128,
+    /// Sent to the counterparty by transfers which weren't settled yet
+    pub sending: Uint128,
+    /// Sent by the counterparty through transfers which weren't settled yet
+    pub receiving: Uint128,
+    /// Id of the next transfer lent to the counterparty
+    pub next_transfer_id: u64,
+}
+
+impl RemoteVault {
+    /// What the counterparty owes the vault, net of what it lent back
+    pub fn lent(&self) -> Uint128 {
+        self.sent.saturating_sub(self.received)
+    }
+
+    /// What the vault owes the counterparty, net of what it lent back
+    pub fn borrowed(&self) -> Uint128 {
+        self.received.saturating_sub(self.sent)
+    }
+}
+
+/// Remote vaults by the channel they are connected over
+pub const REMOTE_VAULTS: Map<&str, RemoteVault> = Map::new("remote_vaults");
+
+/// Ports of the remote vaults allowed to open a channel to the vault
+pub const ALLOWED_REMOTE_VAULTS: Map<&str, Empty> = Map::new("allowed_remote_vaults");
+
+/// ICS-20 transfer of liquidity between vaults, in flight until it lands or gets refunded
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Transfer {
+    pub amount: Uint128,
+    /// Past it, a transfer which didn't land never will
+    pub timeout: Timestamp,
+}
+
+/// Transfers lent to remote vaults, by channel and transfer id
+pub const TRANSFERS_OUT: Map<(&str, u64), Transfer> = Map::new("transfers_out");
+
+/// Transfers lent by remote vaults, by channel and the id the lender gave them
+pub const TRANSFERS_IN: Map<(&str, u64), Transfer> = Map::new("transfers_in");
+
+/// Channel and id of the transfer being lent, until the reply gives its packet's sequence
+pub const LENDING_TRANSFER: Item<(String, u64)> = Item::new("lending_transfer");
+
+/// Channel and id of the transfers lent, by the transfer channel and sequence of their packet
+pub const TRANSFER_PACKETS: Map<(&str, u64), (String, u64)> = Map::new("transfer_packets");
+
+/// Transfers lent by remote vaults which settled before the vault heard they were lent
+pub const EARLY_SETTLEMENTS: Map<(&str, u64), Empty> = Map::new("early_settlements");
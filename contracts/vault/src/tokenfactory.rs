@@ -0,0 +1,95 @@
+//! TokenFactory messages, for chains where vault shares are issued as a native denom.
+//!
+//! Only the messages the vault needs are defined, following
+//! the `osmosis.tokenfactory.v1beta1` protobuf definitions.
+
+use cosmwasm_std::{Addr, CosmosMsg, Uint128};
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+struct ProtoCoin {
+    #[prost(string, tag = "1")]
+    denom: String,
+    #[prost(string, tag = "2")]
+    amount: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct MsgCreateDenom {
+    #[prost(string, tag = "1")]
+    sender: String,
+    #[prost(string, tag = "2")]
+    subdenom: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct MsgMint {
+    #[prost(string, tag = "1")]
+    sender: String,
+    #[prost(message, optional, tag = "2")]
+    amount: Option<ProtoCoin>,
+    #[prost(string, tag = "3")]
+    mint_to_address: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct MsgBurn {
+    #[prost(string, tag = "1")]
+    sender: String,
+    #[prost(message, optional, tag = "2")]
+    amount: Option<ProtoCoin>,
+    #[prost(string, tag = "3")]
+    burn_from_address: String,
+}
+
+/// Full denom created by `vault` for `subdenom`
+pub fn denom(vault: &Addr, subdenom: &str) -> String {
+    format!("factory/{}/{}", vault, subdenom)
+}
+
+pub fn create_denom_msg(vault: &Addr, subdenom: &str) -> CosmosMsg {
+    stargate_msg(
+        "/osmosis.tokenfactory.v1beta1.MsgCreateDenom",
+        MsgCreateDenom {
+            sender: vault.to_string(),
+            subdenom: subdenom.to_string(),
+        },
+    )
+}
+
+pub fn mint_msg(vault: &Addr, denom: &str, amount: Uint128, recipient: &Addr) -> CosmosMsg {
+    stargate_msg(
+        "/osmosis.tokenfactory.v1beta1.MsgMint",
+        MsgMint {
+            sender: vault.to_string(),
+            amount: Some(proto_coin(denom, amount)),
+            mint_to_address: recipient.to_string(),
+        },
+    )
+}
+
+/// Burns `amount` held by the vault
+pub fn burn_msg(vault: &Addr, denom: &str, amount: Uint128) -> CosmosMsg {
+    stargate_msg(
+        "/osmosis.tokenfactory.v1beta1.MsgBurn",
+        MsgBurn {
+            sender: vault.to_string(),
+            amount: Some(proto_coin(denom, amount)),
+            burn_from_address: vault.to_string(),
+        },
+    )
+}
+
+fn proto_coin(denom: &str, amount: Uint128) -> ProtoCoin {
+    ProtoCoin {
+        denom: denom.to_string(),
+        amount: amount.to_string(),
+    }
+}
+
+fn stargate_msg(type_url: &str, msg: impl Message) -> CosmosMsg {
+    CosmosMsg::Stargate {
+        type_url: type_url.to_string(),
+        value: msg.encode_to_vec().into(),
+    }
+}
@@ -0,0 +1,57 @@
+use cosmwasm_std::{Addr, Coin, QuerierWrapper};
+use cw_flash_loan_gateway::helpers::Contract as CwGatewayContract;
+use cw_multi_test::AppResponse;
+
+/// Takes a querier rather than the app, so apps with custom modules work too
+pub fn assert_balance(querier: &QuerierWrapper, addr: impl Into<String>, expected: Coin) {
+    let addr = addr.into();
+    let balance = querier.query_balance(&addr, &expected.denom).unwrap();
+
+    assert_eq!(balance, expected, "balance of {}", addr);
+}
+
+/// Checks the gateway's fee ledger accounts for exactly what it holds
+pub fn assert_treasury_balanced(querier: &QuerierWrapper, gateway: &CwGatewayContract) {
+    let fees = gateway.protocol_fees(querier).unwrap();
+
+    let mut ledger: Vec<Coin> = vec![];
+    for fee in fees.accrued.into_iter().chain(fees.referral_fees_owed) {
+        match ledger.iter_mut().find(|coin| coin.denom == fee.denom) {
+            Some(coin) => coin.amount += fee.amount,
+            None => ledger.push(fee),
+        }
+    }
+    ledger.sort_by(|a, b| a.denom.cmp(&b.denom));
+
+    let balances = querier.query_all_balances(gateway.addr()).unwrap();
+
+    assert_eq!(ledger, balances, "gateway fee ledger and balance");
+}
+
+/// Checks `response` has an event of type `ty` with all of `attributes`
+pub fn assert_event(response: &AppResponse, ty: &str, attributes: &[(&str, &str)]) {
+    let found = response.events.iter().any(|event| {
+        event.ty == ty
+            && attributes.iter().all(|(key, value)| {
+                event
+                    .attributes
+                    .iter()
+                    .any(|attr| attr.key == *key && attr.value == *value)
+            })
+    });
+
+    assert!(
+        found,
+        "no {} event with {:?} in {:?}",
+        ty, attributes, response.events
+    );
+}
+
+/// Checks `contract` reported `action` in `response`
+pub fn assert_wasm_action(response: &AppResponse, contract: &Addr, action: &str) {
+    assert_event(
+        response,
+        "wasm",
+        &[("_contract_address", contract.as_str()), ("action", action)],
+    );
+}
@@ -0,0 +1,310 @@
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{coin, wasm_execute, Addr, BankMsg, Coin, CosmosMsg, Empty};
+    use cw_flash_loan_gateway::helpers::RequestFlashLoanProps;
+    use cw_flash_loan_gateway::msg::ExecuteMsg as GatewayExecuteMsg;
+    use cw_flash_loan_gateway::ContractError as GatewayError;
+    use cw_flash_loan_vault::msg::ExecuteMsg as VaultExecuteMsg;
+    use cw_flash_loan_vault::ContractError as VaultError;
+    use cw_multi_test::Executor;
+
+    use crate::test_borrower::{self, Hook, Repay};
+    use crate::{Deployment, DeploymentBuilder, ADMIN};
+
+    const LIQUIDITY: u128 = 1_000_000;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Attack {
+        /// Borrows again from within the hook
+        Reenter,
+        /// Tells the gateway a loan was provided, without the vault lending anything
+        FakeLoanProvided,
+        /// Repays the loan in another denom
+        WrongDenom,
+        /// Sends the repayment to the gateway before borrowing, then keeps the loan
+        Donate,
+        /// Has the vault send the repayment to the gateway out of its own funds
+        VaultRepays,
+        /// Borrows nothing, only to burn loan ids and gas
+        ZeroAmount,
+        /// Borrows straight from the vault, skipping the gateway's fee and bookkeeping
+        BypassGateway,
+        /// Borrows the smallest amount there is, paying next to no fee
+        Dust,
+    }
+
+    impl Attack {
+        /// Message having `attacker` carry out the attack with a loan of `asset`
+        fn msg(
+            self,
+            deployment: &Deployment,
+            attacker: &Addr,
+            asset: Coin,
+        ) -> test_borrower::ExecuteMsg {
+            let gateway = deployment.gateway.addr();
+            let borrow = |asset: Coin, hook: Hook| test_borrower::ExecuteMsg::Borrow {
+                gateway: gateway.to_string(),
+                asset,
+                referrer: None,
+                hook,
+            };
+            let execute = |msgs: Vec<CosmosMsg>| test_borrower::ExecuteMsg::Execute { msgs };
+
+            match self {
+                Attack::Reenter => borrow(
+                    asset.clone(),
+                    Hook {
+                        msgs: vec![deployment
+                            .gateway
+                            .request_flash_loan(RequestFlashLoanProps {
+                                asset,
+                                on_flash_loan_provided_hook: &Empty {},
+                                referrer: None,
+                            })
+                            .unwrap()],
+                        ..Hook::default()
+                    },
+                ),
+                Attack::FakeLoanProvided => execute(vec![deployment
+                    .gateway
+                    .call(GatewayExecuteMsg::FlashLoanProvided { asset }, None)
+                    .unwrap()]),
+                Attack::WrongDenom => borrow(
+                    asset,
+                    Hook {
+                        repay: Repay::Denom("uusd".to_string()),
+                        ..Hook::default()
+                    },
+                ),
+                Attack::Donate => execute(vec![
+                    BankMsg::Send {
+                        to_address: gateway.to_string(),
+                        amount: vec![coin(asset.amount.u128() * 2, &asset.denom)],
+                    }
+                    .into(),
+                    // keeps the loan, counting on what was sent beforehand
+                    wasm_execute(
+                        attacker,
+                        &borrow(
+                            asset,
+                            Hook {
+                                repay: Repay::Nothing,
+                                ..Hook::default()
+                            },
+                        ),
+                        vec![],
+                    )
+                    .unwrap()
+                    .into(),
+                ]),
+                Attack::VaultRepays => {
+                    let debt = deployment
+                        .gateway
+                        .simulate_flash_loan(
+                            &deployment.app.wrap(),
+                            vec![asset.clone()],
+                            attacker,
+                            None,
+                        )
+                        .unwrap()
+                        .total_repayment
+                        .remove(0);
+
+                    borrow(
+                        asset,
+                        Hook {
+                            repay: Repay::Nothing,
+                            msgs: vec![deployment
+                                .vault
+                                .call(
+                                    VaultExecuteMsg::ProvideAsset {
+                                        asset: debt,
+                                        borrower_addr: gateway.to_string(),
+                                    },
+                                    None,
+                                )
+                                .unwrap()],
+                            ..Hook::default()
+                        },
+                    )
+                }
+                Attack::ZeroAmount => borrow(coin(0, &asset.denom), Hook::default()),
+                Attack::BypassGateway => execute(vec![deployment
+                    .vault
+                    .call(
+                        VaultExecuteMsg::ProvideAsset {
+                            asset,
+                            borrower_addr: attacker.to_string(),
+                        },
+                        None,
+                    )
+                    .unwrap()]),
+                Attack::Dust => borrow(coin(1, &asset.denom), Hook::default()),
+            }
+        }
+    }
+
+    fn deploy() -> Deployment {
+        DeploymentBuilder::new()
+            .with_liquidity(LIQUIDITY)
+            .with_balance(ADMIN, vec![coin(1_000_000, "uusd")])
+            .build()
+    }
+
+    /// Runs `attack` with a loan of 1_000 uluna, checking it left the protocol untouched
+    fn attack(attack: Attack) -> anyhow::Error {
+        attack_deployment(deploy(), attack)
+    }
+
+    fn attack_deployment(mut deployment: Deployment, attack: Attack) -> anyhow::Error {
+        let attacker = deployment.register_borrower(
+            test_borrower::contract(),
+            &Empty {},
+            &[coin(10_000, "uluna"), coin(10_000, "uusd")],
+            "attacker",
+        );
+
+        let msg = attack.msg(&deployment, &attacker, coin(1_000, "uluna"));
+        let err = deployment
+            .app
+            .execute_contract(deployment.admin.clone(), attacker.clone(), &msg, &[])
+            .unwrap_err();
+
+        assert_untouched(&deployment, &attacker);
+
+        err
+    }
+
+    fn assert_untouched(deployment: &Deployment, attacker: &Addr) {
+        deployment.assert_balance(deployment.vault.addr(), coin(LIQUIDITY, "uluna"));
+        deployment.assert_balance(deployment.gateway.addr(), coin(0, "uluna"));
+        deployment.assert_balance(attacker, coin(10_000, "uluna"));
+        deployment.assert_treasury_balanced();
+    }
+
+    /// Checks `err` is the borrower's hook failing because of `cause`
+    fn assert_hook_failed(err: &anyhow::Error, cause: &str) {
+        match err.downcast_ref::<GatewayError>() {
+            Some(GatewayError::BorrowerHookFailed { reason, .. }) => {
+                assert!(reason.contains(cause), "unexpected reason: {}", reason)
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reentering_from_the_hook_is_rejected() {
+        let err = attack(Attack::Reenter);
+
+        assert_hook_failed(&err, &GatewayError::LoanInProgress {}.to_string());
+    }
+
+    #[test]
+    fn only_the_vault_reports_loans_provided() {
+        let err = attack(Attack::FakeLoanProvided);
+
+        assert!(matches!(
+            err.downcast_ref::<GatewayError>(),
+            Some(GatewayError::Unauthorized {})
+        ));
+    }
+
+    #[test]
+    fn repaying_in_another_denom_is_rejected() {
+        let err = attack(Attack::WrongDenom);
+
+        match err.downcast_ref::<GatewayError>() {
+            Some(GatewayError::RepaymentShortfall { available, .. }) => {
+                assert_eq!(available, &coin(0, "uluna"))
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn funds_sent_beforehand_do_not_repay_the_loan() {
+        let err = attack(Attack::Donate);
+
+        match err.downcast_ref::<GatewayError>() {
+            Some(GatewayError::RepaymentShortfall { available, .. }) => {
+                assert_eq!(available, &coin(0, "uluna"))
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn vault_cannot_be_made_to_repay_itself() {
+        let err = attack(Attack::VaultRepays);
+
+        assert_hook_failed(&err, &VaultError::Unauthorized {}.to_string());
+    }
+
+    #[test]
+    fn zero_amount_loans_are_rejected() {
+        let err = attack(Attack::ZeroAmount);
+
+        match err.downcast_ref::<GatewayError>() {
+            Some(GatewayError::LoanTooSmall { min }) => assert_eq!(min, &coin(1, "uluna")),
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn only_the_gateway_borrows_from_the_vault() {
+        let err = attack(Attack::BypassGateway);
+
+        assert!(matches!(
+            err.downcast_ref::<VaultError>(),
+            Some(VaultError::Unauthorized {})
+        ));
+    }
+
+    #[test]
+    fn dust_loans_are_rejected() {
+        let mut deployment = deploy();
+        deployment
+            .app
+            .execute_contract(
+                deployment.admin.clone(),
+                deployment.gateway.addr(),
+                &GatewayExecuteMsg::UpdateMinLoanSizes {
+                    min_loan_sizes: vec![coin(100, "uluna")],
+                },
+                &[],
+            )
+            .unwrap();
+
+        let err = attack_deployment(deployment, Attack::Dust);
+
+        match err.downcast_ref::<GatewayError>() {
+            Some(GatewayError::LoanTooSmall { min }) => assert_eq!(min, &coin(100, "uluna")),
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn vault_rejects_empty_loans_from_the_gateway() {
+        let mut deployment = deploy();
+
+        let err = deployment
+            .app
+            .execute_contract(
+                deployment.gateway.addr(),
+                deployment.vault.addr(),
+                &VaultExecuteMsg::ProvideAsset {
+                    asset: coin(0, "uluna"),
+                    borrower_addr: deployment.gateway.addr().to_string(),
+                },
+                &[],
+            )
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<VaultError>(),
+            Some(VaultError::EmptyLoan {})
+        ));
+        deployment.assert_balance(deployment.vault.addr(), coin(LIQUIDITY, "uluna"));
+        deployment.assert_treasury_balanced();
+    }
+}
@@ -0,0 +1,28 @@
+//! The protocol's contracts, wrapped for `cw-multi-test`
+
+use cosmwasm_std::Empty;
+use cw_multi_test::{Contract, ContractWrapper};
+
+pub fn vault_contract() -> Box<dyn Contract<Empty>> {
+    let contract = ContractWrapper::new(
+        cw_flash_loan_vault::contract::execute,
+        cw_flash_loan_vault::contract::instantiate,
+        cw_flash_loan_vault::contract::query,
+    )
+    .with_reply(cw_flash_loan_vault::contract::reply)
+    .with_sudo(cw_flash_loan_vault::contract::sudo);
+
+    Box::new(contract)
+}
+
+pub fn gateway_contract() -> Box<dyn Contract<Empty>> {
+    let contract = ContractWrapper::new(
+        cw_flash_loan_gateway::contract::execute,
+        cw_flash_loan_gateway::contract::instantiate,
+        cw_flash_loan_gateway::contract::query,
+    )
+    .with_reply(cw_flash_loan_gateway::contract::reply)
+    .with_sudo(cw_flash_loan_gateway::contract::sudo);
+
+    Box::new(contract)
+}
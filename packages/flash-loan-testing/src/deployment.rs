@@ -0,0 +1,294 @@
+use cosmwasm_std::testing::{MockApi, MockStorage};
+use cosmwasm_std::{coin, Addr, Coin, Empty, Uint128};
+use cw_flash_loan_gateway::helpers::Contract as CwGatewayContract;
+use cw_flash_loan_gateway::state::FeeConfig;
+use cw_flash_loan_vault::helpers::Contract as CwVaultContract;
+use cw_flash_loan_vault::state::FeeCurve;
+use cw_multi_test::{
+    App, AppBuilder, BankKeeper, Contract, DistributionKeeper, Executor, FailingModule,
+    GovFailingModule, IbcFailingModule, StakeKeeper, Stargate, StargateFailingModule, WasmKeeper,
+};
+use serde::Serialize;
+
+use crate::assertions;
+use crate::contracts::{gateway_contract, vault_contract};
+
+/// Instantiates the protocol's contracts and owns the vault's initial shares
+pub const ADMIN: &str = "admin";
+
+/// Funds the admin gets on top of the vault's liquidity, in the lent denom
+const ADMIN_FUNDS: u128 = 1_000_000_000_000_000_000;
+
+/// App deployments run in, whose stargate module handles the messages and queries
+/// of chain modules, like a DEX's, borrowers use
+pub type DeploymentApp<St = StargateFailingModule> = App<
+    BankKeeper,
+    MockApi,
+    MockStorage,
+    FailingModule<Empty, Empty, Empty>,
+    WasmKeeper<Empty, Empty>,
+    StakeKeeper,
+    DistributionKeeper,
+    IbcFailingModule,
+    GovFailingModule,
+    St,
+>;
+
+/// Sets up a vault lending `denom` and a gateway in front of it
+pub struct DeploymentBuilder<St = StargateFailingModule> {
+    denom: String,
+    liquidity: Uint128,
+    vault_fee_curve: Option<FeeCurve>,
+    gateway_fees: Option<FeeConfig>,
+    balances: Vec<(String, Vec<Coin>)>,
+    stargate: St,
+}
+
+impl Default for DeploymentBuilder {
+    /// A vault with 1_000_000 uluna and default fees, in an app failing stargate messages
+    fn default() -> Self {
+        DeploymentBuilder {
+            denom: "uluna".to_string(),
+            liquidity: Uint128::new(1_000_000),
+            vault_fee_curve: None,
+            gateway_fees: None,
+            balances: vec![],
+            stargate: StargateFailingModule::new(),
+        }
+    }
+}
+
+impl DeploymentBuilder {
+    pub fn new() -> Self {
+        DeploymentBuilder::default()
+    }
+}
+
+impl<St: Stargate> DeploymentBuilder<St> {
+    /// Module handling the stargate messages and queries of the borrowers under test
+    pub fn with_stargate<S: Stargate>(self, stargate: S) -> DeploymentBuilder<S> {
+        DeploymentBuilder {
+            denom: self.denom,
+            liquidity: self.liquidity,
+            vault_fee_curve: self.vault_fee_curve,
+            gateway_fees: self.gateway_fees,
+            balances: self.balances,
+            stargate,
+        }
+    }
+
+    /// Denom the vault lends
+    pub fn with_denom(mut self, denom: &str) -> Self {
+        self.denom = denom.to_string();
+        self
+    }
+
+    /// Funds deposited into the vault by the admin
+    pub fn with_liquidity(mut self, liquidity: u128) -> Self {
+        self.liquidity = Uint128::new(liquidity);
+        self
+    }
+
+    pub fn with_vault_fee_curve(mut self, fee_curve: FeeCurve) -> Self {
+        self.vault_fee_curve = Some(fee_curve);
+        self
+    }
+
+    pub fn with_gateway_fees(mut self, fees: FeeConfig) -> Self {
+        self.gateway_fees = Some(fees);
+        self
+    }
+
+    /// Initial balance of `addr`, on top of the admin's funds when it is the admin
+    pub fn with_balance(mut self, addr: &str, coins: Vec<Coin>) -> Self {
+        self.balances.push((addr.to_string(), coins));
+        self
+    }
+
+    pub fn build(self) -> Deployment<St> {
+        let admin = Addr::unchecked(ADMIN);
+
+        let mut balances = vec![(ADMIN.to_string(), vec![coin(ADMIN_FUNDS, &self.denom)])];
+        for (addr, coins) in self.balances {
+            match balances.iter_mut().find(|(existing, _)| *existing == addr) {
+                Some((_, existing)) => add_coins(existing, coins),
+                None => balances.push((addr, coins)),
+            }
+        }
+
+        let mut app = AppBuilder::new()
+            .with_stargate(self.stargate)
+            .build(|router, _, storage| {
+                for (addr, coins) in balances {
+                    router
+                        .bank
+                        .init_balance(storage, &Addr::unchecked(addr), coins)
+                        .unwrap();
+                }
+            });
+
+        let vault_id = app.store_code(vault_contract());
+        let vault = app
+            .instantiate_contract(
+                vault_id,
+                admin.clone(),
+                &cw_flash_loan_vault::msg::InstantiateMsg {
+                    denom: self.denom.clone(),
+                    gateway: None,
+                    staking: None,
+                    withdrawal_cooldown: 0,
+                    share_token: None,
+                    fee_curve: self.vault_fee_curve,
+                },
+                &[coin(self.liquidity.u128(), &self.denom)],
+                "vault",
+                None,
+            )
+            .unwrap();
+
+        let gateway_id = app.store_code(gateway_contract());
+        let gateway = app
+            .instantiate_contract(
+                gateway_id,
+                admin.clone(),
+                &cw_flash_loan_gateway::msg::InstantiateMsg {
+                    cw_vault_contract_addr: vault.to_string(),
+                },
+                &[],
+                "gateway",
+                None,
+            )
+            .unwrap();
+
+        // the vault only lends through the gateway, which couldn't be deployed before it
+        app.execute_contract(
+            admin.clone(),
+            vault.clone(),
+            &cw_flash_loan_vault::msg::ExecuteMsg::UpdateGateway {
+                gateway: gateway.to_string(),
+            },
+            &[],
+        )
+        .unwrap();
+
+        if let Some(fees) = self.gateway_fees {
+            app.execute_contract(
+                admin.clone(),
+                gateway.clone(),
+                &cw_flash_loan_gateway::msg::ExecuteMsg::UpdateFees { fees },
+                &[],
+            )
+            .unwrap();
+        }
+
+        Deployment {
+            app,
+            admin,
+            denom: self.denom,
+            vault: CwVaultContract(vault),
+            gateway: CwGatewayContract(gateway),
+        }
+    }
+}
+
+fn add_coins(balance: &mut Vec<Coin>, coins: Vec<Coin>) {
+    for added in coins {
+        match balance.iter_mut().find(|coin| coin.denom == added.denom) {
+            Some(coin) => coin.amount += added.amount,
+            None => balance.push(added),
+        }
+    }
+}
+
+/// Vault and gateway deployed in a `cw-multi-test` app
+pub struct Deployment<St = StargateFailingModule> {
+    pub app: DeploymentApp<St>,
+    pub admin: Addr,
+    /// Denom the vault lends
+    pub denom: String,
+    pub vault: CwVaultContract,
+    pub gateway: CwGatewayContract,
+}
+
+impl<St: Stargate> Deployment<St> {
+    /// Stores and instantiates a borrower contract, funded with `funds` from the admin
+    pub fn register_borrower<M: Serialize>(
+        &mut self,
+        contract: Box<dyn Contract<Empty>>,
+        msg: &M,
+        funds: &[Coin],
+        label: &str,
+    ) -> Addr {
+        let code_id = self.app.store_code(contract);
+
+        self.app
+            .instantiate_contract(code_id, self.admin.clone(), msg, funds, label, None)
+            .unwrap()
+    }
+
+    /// Balance of `addr` in the lent denom
+    pub fn balance(&self, addr: impl Into<String>) -> Coin {
+        self.app.wrap().query_balance(addr, &self.denom).unwrap()
+    }
+
+    pub fn assert_balance(&self, addr: impl Into<String>, expected: Coin) {
+        assertions::assert_balance(&self.app.wrap(), addr, expected)
+    }
+
+    pub fn assert_treasury_balanced(&self) {
+        assertions::assert_treasury_balanced(&self.app.wrap(), &self.gateway)
+    }
+
+    /// Prints the balances of the vault, the gateway and `others`
+    pub fn print_balances(&self, label: &str, others: &[(&str, &Addr)]) {
+        let contracts = [("Vault", &self.vault.0), ("Gateway", &self.gateway.0)];
+
+        for (name, addr) in contracts.iter().chain(others) {
+            let coins = self.app.wrap().query_all_balances(*addr).unwrap();
+
+            println!("[{}]: {} = {:?}", label, name, &coins);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::assertions::assert_wasm_action;
+    use crate::test_borrower;
+
+    #[test]
+    fn registered_borrower_takes_flash_loans() {
+        let mut deployment = DeploymentBuilder::new()
+            .with_denom("uusd")
+            .with_liquidity(100_000)
+            .build();
+        let borrower = deployment.register_borrower(
+            test_borrower::contract(),
+            &Empty {},
+            &[coin(100, "uusd")],
+            "borrower",
+        );
+
+        let response = deployment
+            .app
+            .execute_contract(
+                deployment.admin.clone(),
+                borrower.clone(),
+                &test_borrower::ExecuteMsg::borrow(&deployment.gateway.addr(), coin(1_000, "uusd")),
+                &[],
+            )
+            .unwrap();
+
+        assert_wasm_action(
+            &response,
+            &deployment.gateway.addr(),
+            "reply_on_external_handler_completed",
+        );
+        deployment.assert_balance(&borrower, coin(70, "uusd"));
+        deployment.assert_balance(deployment.vault.addr(), coin(100_020, "uusd"));
+        deployment.assert_balance(deployment.gateway.addr(), coin(10, "uusd"));
+        deployment.assert_treasury_balanced();
+    }
+}
@@ -0,0 +1,76 @@
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{coin, Binary, Empty};
+    use cw_multi_test::Executor;
+
+    use crate::test_borrower::{self, Hook};
+    use crate::{assert_event, DeploymentBuilder};
+
+    /// Borrows 1_000 uusd twice, with a hook responding with `outcome` the second time
+    fn borrow_twice(outcome: Option<&str>) -> cw_multi_test::AppResponse {
+        let mut deployment = DeploymentBuilder::new()
+            .with_denom("uusd")
+            .with_liquidity(100_000)
+            .build();
+        let borrower = deployment.register_borrower(
+            test_borrower::contract(),
+            &Empty {},
+            &[coin(100, "uusd")],
+            "borrower",
+        );
+
+        let mut borrow = |outcome: Option<&str>| {
+            deployment
+                .app
+                .execute_contract(
+                    deployment.admin.clone(),
+                    borrower.clone(),
+                    &test_borrower::ExecuteMsg::Borrow {
+                        gateway: deployment.gateway.addr().to_string(),
+                        asset: coin(1_000, "uusd"),
+                        referrer: None,
+                        hook: Hook {
+                            data: outcome.map(|outcome| Binary::from(outcome.as_bytes())),
+                            ..Hook::default()
+                        },
+                    },
+                    &[],
+                )
+                .unwrap()
+        };
+
+        borrow(None);
+        let response = borrow(outcome);
+
+        deployment.assert_treasury_balanced();
+
+        response
+    }
+
+    #[test]
+    fn borrower_reads_what_its_hook_responded() {
+        let response = borrow_twice(Some("profit: 42uusd"));
+
+        assert_event(
+            &response,
+            "wasm",
+            &[
+                ("loan_id", "2"),
+                ("vault_fee", "20uusd"),
+                ("gateway_fee", "10uusd"),
+                ("hook_data", "profit: 42uusd"),
+            ],
+        );
+    }
+
+    #[test]
+    fn hooks_do_not_have_to_respond_with_data() {
+        let response = borrow_twice(None);
+
+        assert_event(
+            &response,
+            "wasm",
+            &[("loan_id", "2"), ("hook_data", "none")],
+        );
+    }
+}
@@ -0,0 +1,322 @@
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{coin, Addr, Coin, Decimal, Empty, Uint128};
+    use cw_flash_loan_gateway::msg::{ExecuteMsg as GatewayExecuteMsg, ProtocolFeesResponse};
+    use cw_flash_loan_gateway::state::{FeeConfig, FeeTier};
+    use cw_flash_loan_vault::msg::{
+        ExecuteMsg as VaultExecuteMsg, QueryMsg as VaultQueryMsg, TotalAssetsResponse,
+    };
+    use cw_flash_loan_vault::state::FeeCurve;
+    use cw_multi_test::Executor;
+    use proptest::prelude::*;
+
+    use crate::test_borrower::{self, Hook, Repay};
+    use crate::{Deployment, DeploymentBuilder};
+
+    const DENOM: &str = "uusd";
+    const LIQUIDITY: u128 = 1_000_000;
+    const REFERRER: &str = "referrer";
+
+    /// How a borrower lets its loan fail
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Failure {
+        /// Errors out of the hook
+        Revert,
+        /// Repays one less than it owes
+        Shortchange,
+    }
+
+    /// Hook of a borrower failing its loan the `failure` way
+    fn hook(failure: Option<Failure>) -> Hook {
+        match failure {
+            Some(Failure::Revert) => Hook {
+                revert: true,
+                ..Hook::default()
+            },
+            Some(Failure::Shortchange) => Hook {
+                repay: Repay::Short(Uint128::one()),
+                ..Hook::default()
+            },
+            None => Hook::default(),
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    enum Op {
+        Deposit(u128),
+        Borrow {
+            amount: u128,
+            referred: bool,
+            failure: Option<Failure>,
+        },
+        UpdateVaultFees {
+            base_rate: Decimal,
+            slope_above_kink: Decimal,
+        },
+        UpdateGatewayFees {
+            rate: Decimal,
+            discount: Decimal,
+            referral_share: Decimal,
+        },
+    }
+
+    fn permille(max: u64) -> impl Strategy<Value = Decimal> {
+        (0..=max).prop_map(Decimal::permille)
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        let failure = prop_oneof![
+            Just(None),
+            Just(Some(Failure::Revert)),
+            Just(Some(Failure::Shortchange)),
+        ];
+
+        prop_oneof![
+            1 => (1..LIQUIDITY).prop_map(Op::Deposit),
+            4 => (0..2 * LIQUIDITY, any::<bool>(), failure).prop_map(|(amount, referred, failure)| {
+                Op::Borrow {
+                    amount,
+                    referred,
+                    failure,
+                }
+            }),
+            1 => (permille(100), permille(2_000)).prop_map(|(base_rate, slope_above_kink)| {
+                Op::UpdateVaultFees {
+                    base_rate,
+                    slope_above_kink,
+                }
+            }),
+            1 => (permille(1_000), permille(1_000), permille(1_000)).prop_map(
+                |(rate, discount, referral_share)| Op::UpdateGatewayFees {
+                    rate,
+                    discount,
+                    referral_share,
+                }
+            ),
+        ]
+    }
+
+    /// Everything a failed loan must leave untouched
+    #[derive(Debug, PartialEq)]
+    struct Snapshot {
+        vault: Coin,
+        gateway: Coin,
+        borrower: Coin,
+        total_assets: TotalAssetsResponse,
+        fees: ProtocolFeesResponse,
+    }
+
+    fn snapshot(deployment: &Deployment, borrower: &Addr) -> Snapshot {
+        let querier = deployment.app.wrap();
+
+        Snapshot {
+            vault: deployment.balance(deployment.vault.addr()),
+            gateway: deployment.balance(deployment.gateway.addr()),
+            borrower: deployment.balance(borrower),
+            total_assets: querier
+                .query_wasm_smart(deployment.vault.addr(), &VaultQueryMsg::TotalAssets {})
+                .unwrap(),
+            fees: deployment.gateway.protocol_fees(&querier).unwrap(),
+        }
+    }
+
+    fn borrow(
+        deployment: &mut Deployment,
+        borrower: &Addr,
+        amount: u128,
+        referred: bool,
+        failure: Option<Failure>,
+    ) {
+        let asset = coin(amount, DENOM);
+        let before = snapshot(deployment, borrower);
+        let vault_quote = deployment
+            .vault
+            .fee_quote(&deployment.app.wrap(), asset.clone());
+        let gateway_rate = deployment
+            .gateway
+            .borrower_tier(&deployment.app.wrap(), borrower, DENOM)
+            .unwrap()
+            .fee_rate;
+
+        let result = deployment.app.execute_contract(
+            deployment.admin.clone(),
+            borrower.clone(),
+            &test_borrower::ExecuteMsg::Borrow {
+                gateway: deployment.gateway.addr().to_string(),
+                asset,
+                referrer: if referred {
+                    Some(REFERRER.to_string())
+                } else {
+                    None
+                },
+                hook: hook(failure),
+            },
+            &[],
+        );
+        let after = snapshot(deployment, borrower);
+
+        if result.is_err() {
+            assert_eq!(before, after, "failed loan left traces");
+            return;
+        }
+
+        assert_eq!(failure, None, "failing borrower got a loan through");
+        assert!(
+            after.vault.amount >= before.vault.amount,
+            "vault lost funds"
+        );
+
+        let vault_fee = after.vault.amount - before.vault.amount;
+        let gateway_fee = after.gateway.amount - before.gateway.amount;
+        let quote = vault_quote.unwrap();
+
+        assert_eq!(vault_fee, quote.fee.amount);
+        assert_eq!(
+            before.borrower.amount - after.borrower.amount,
+            vault_fee + gateway_fee
+        );
+
+        // rounding fees down would lend below the advertised rates
+        if amount > 0 {
+            assert!(Decimal::from_ratio(vault_fee, amount) >= quote.rate);
+            assert!(Decimal::from_ratio(gateway_fee, amount) >= gateway_rate);
+        }
+    }
+
+    /// Pays out all of the gateway's fees, which must leave it empty
+    fn drain_gateway(deployment: &mut Deployment) {
+        let fees = deployment
+            .gateway
+            .protocol_fees(&deployment.app.wrap())
+            .unwrap();
+
+        for fee in fees.accrued {
+            deployment
+                .app
+                .execute_contract(
+                    deployment.admin.clone(),
+                    deployment.gateway.addr(),
+                    &GatewayExecuteMsg::WithdrawFees {
+                        denom: fee.denom,
+                        amount: fee.amount,
+                        recipient: deployment.admin.to_string(),
+                    },
+                    &[],
+                )
+                .unwrap();
+        }
+
+        if !fees.referral_fees_owed.is_empty() {
+            deployment
+                .app
+                .execute_contract(
+                    Addr::unchecked(REFERRER),
+                    deployment.gateway.addr(),
+                    &GatewayExecuteMsg::ClaimReferralFees {},
+                    &[],
+                )
+                .unwrap();
+        }
+
+        let balances = deployment
+            .app
+            .wrap()
+            .query_all_balances(deployment.gateway.addr())
+            .unwrap();
+
+        assert_eq!(balances, vec![], "gateway kept residual funds");
+    }
+
+    fn run(ops: Vec<Op>) {
+        let mut deployment = DeploymentBuilder::new()
+            .with_denom(DENOM)
+            .with_liquidity(LIQUIDITY)
+            .build();
+        let borrower = deployment.register_borrower(
+            test_borrower::contract(),
+            &Empty {},
+            &[coin(100 * LIQUIDITY, DENOM)],
+            "borrower",
+        );
+
+        for op in ops {
+            match op {
+                Op::Deposit(amount) => {
+                    deployment
+                        .app
+                        .execute_contract(
+                            deployment.admin.clone(),
+                            deployment.vault.addr(),
+                            &VaultExecuteMsg::Deposit {},
+                            &[coin(amount, DENOM)],
+                        )
+                        .unwrap();
+                }
+                Op::Borrow {
+                    amount,
+                    referred,
+                    failure,
+                } => borrow(&mut deployment, &borrower, amount, referred, failure),
+                Op::UpdateVaultFees {
+                    base_rate,
+                    slope_above_kink,
+                } => {
+                    let fee_curve = FeeCurve {
+                        base_rate,
+                        slope_above_kink,
+                        ..FeeCurve::default()
+                    };
+
+                    deployment
+                        .app
+                        .execute_contract(
+                            deployment.admin.clone(),
+                            deployment.vault.addr(),
+                            &VaultExecuteMsg::UpdateFeeCurve { fee_curve },
+                            &[],
+                        )
+                        .unwrap();
+                }
+                Op::UpdateGatewayFees {
+                    rate,
+                    discount,
+                    referral_share,
+                } => {
+                    let fees = FeeConfig {
+                        rate,
+                        tiers: vec![FeeTier {
+                            denom: DENOM.to_string(),
+                            min_volume: Uint128::new(LIQUIDITY),
+                            discount,
+                        }],
+                        referral_share,
+                        ..FeeConfig::default()
+                    };
+
+                    deployment
+                        .app
+                        .execute_contract(
+                            deployment.admin.clone(),
+                            deployment.gateway.addr(),
+                            &GatewayExecuteMsg::UpdateFees { fees },
+                            &[],
+                        )
+                        .unwrap();
+                }
+            }
+
+            deployment.assert_treasury_balanced();
+        }
+
+        drain_gateway(&mut deployment);
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn funds_are_conserved(ops in prop::collection::vec(op(), 1..16)) {
+            run(ops);
+        }
+    }
+}
@@ -0,0 +1,27 @@
+//! `cw-multi-test` harness for testing flash loan borrowers against the vault and gateway.
+//!
+//! [`DeploymentBuilder`] sets up an app with a funded vault and a gateway lending from it.
+//! Borrower contracts then get registered on the [`Deployment`], and the assertion helpers
+//! check balances, events and the gateway's fee ledger once loans went through.
+//! Tests of the protocol itself borrow through the [`test_borrower`], whose hook is set per loan.
+//!
+//! ```ignore
+//! let mut deployment = DeploymentBuilder::new()
+//!     .with_denom("uusd")
+//!     .with_liquidity(1_000_000)
+//!     .build();
+//! let borrower = deployment.register_borrower(my_borrower(), &InstantiateMsg { .. }, &[], "borrower");
+//! ```
+
+pub mod assertions;
+mod attack_tests;
+pub mod contracts;
+pub mod deployment;
+mod hook_data_tests;
+mod invariant_tests;
+pub mod test_borrower;
+
+pub use crate::assertions::{
+    assert_balance, assert_event, assert_treasury_balanced, assert_wasm_action,
+};
+pub use crate::deployment::{Deployment, DeploymentBuilder, ADMIN};
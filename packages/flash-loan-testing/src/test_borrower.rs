@@ -0,0 +1,170 @@
+//! Borrower whose behaviour is set per loan, for testing the gateway and vault rather than
+//! a strategy. Its hook can pay back more or less than owed, run extra messages, respond with
+//! data or error out. Loans are requested as a submessage, whose reply reports the loan.
+
+use cosmwasm_std::{
+    coin, to_json_binary, Addr, Binary, Coin, CosmosMsg, Deps, DepsMut, Empty, Env, MessageInfo,
+    Reply, Response, StdError, StdResult, SubMsg, Uint128,
+};
+use cw_flash_loan_gateway::helpers::{
+    parse_flash_loan_reply, Contract as CwGatewayContract, RequestFlashLoanProps,
+};
+use cw_multi_test::{Contract, ContractWrapper};
+use serde::{Deserialize, Serialize};
+
+const REPLY_ON_FLASH_LOAN: u64 = 1;
+
+/// How much of its loan the borrower pays back
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Repay {
+    /// Everything owed, fees included
+    Debt,
+    /// What it borrowed, without the fees
+    Principal,
+    /// What it owes, less `short`
+    Short(Uint128),
+    /// What it owes, in another denom
+    Denom(String),
+    Nothing,
+}
+
+/// What the borrower does with a loan once provided
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Hook {
+    pub repay: Repay,
+    /// Messages run before paying back
+    pub msgs: Vec<CosmosMsg>,
+    /// Data the hook responds with, the gateway passes it back with the loan
+    pub data: Option<Binary>,
+    /// Errors out of the hook
+    pub revert: bool,
+}
+
+impl Default for Hook {
+    /// Pays back the debt and nothing else
+    fn default() -> Self {
+        Hook {
+            repay: Repay::Debt,
+            msgs: vec![],
+            data: None,
+            revert: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    Borrow {
+        gateway: String,
+        asset: Coin,
+        referrer: Option<String>,
+        hook: Hook,
+    },
+    /// Runs `msgs` as the borrower, for anything else than a loan
+    Execute { msgs: Vec<CosmosMsg> },
+    OnFlashLoanProvided {
+        gateway: String,
+        asset: Coin,
+        hook: Hook,
+    },
+}
+
+impl ExecuteMsg {
+    /// Loan of `asset` from `gateway` paid back in full
+    pub fn borrow(gateway: &Addr, asset: Coin) -> Self {
+        ExecuteMsg::Borrow {
+            gateway: gateway.to_string(),
+            asset,
+            referrer: None,
+            hook: Hook::default(),
+        }
+    }
+}
+
+pub fn contract() -> Box<dyn Contract<Empty>> {
+    Box::new(ContractWrapper::new(execute, instantiate, query).with_reply(reply))
+}
+
+fn instantiate(_: DepsMut, _: Env, _: MessageInfo, _: Empty) -> StdResult<Response> {
+    Ok(Response::new())
+}
+
+fn execute(deps: DepsMut, env: Env, _: MessageInfo, msg: ExecuteMsg) -> StdResult<Response> {
+    match msg {
+        ExecuteMsg::Borrow {
+            gateway,
+            asset,
+            referrer,
+            hook,
+        } => {
+            let msg = CwGatewayContract(Addr::unchecked(&gateway)).request_flash_loan(
+                RequestFlashLoanProps {
+                    asset: asset.clone(),
+                    on_flash_loan_provided_hook: &ExecuteMsg::OnFlashLoanProvided {
+                        gateway,
+                        asset,
+                        hook,
+                    },
+                    referrer,
+                },
+            )?;
+
+            Ok(Response::new().add_submessage(SubMsg::reply_on_success(msg, REPLY_ON_FLASH_LOAN)))
+        }
+        ExecuteMsg::Execute { msgs } => Ok(Response::new().add_messages(msgs)),
+        ExecuteMsg::OnFlashLoanProvided {
+            gateway,
+            asset,
+            hook,
+        } => {
+            if hook.revert {
+                return Err(StdError::generic_err("borrower gave up"));
+            }
+
+            let gateway = CwGatewayContract(Addr::unchecked(gateway));
+            let debt = gateway.get_debt_remaining(&deps.querier, env.contract.address)?;
+            let repayment = match hook.repay {
+                Repay::Debt => Some(debt),
+                Repay::Principal => Some(asset),
+                Repay::Short(short) => Some(coin(
+                    debt.amount.saturating_sub(short).u128(),
+                    debt.denom.as_str(),
+                )),
+                Repay::Denom(denom) => Some(coin(debt.amount.u128(), denom.as_str())),
+                Repay::Nothing => None,
+            };
+
+            let mut response = Response::new().add_messages(hook.msgs);
+            if let Some(repayment) = repayment {
+                response = response.add_message(gateway.repay_flash_loan(repayment)?);
+            }
+            if let Some(data) = hook.data {
+                response = response.set_data(data);
+            }
+
+            Ok(response)
+        }
+    }
+}
+
+fn query(_: Deps, _: Env, _: Empty) -> StdResult<Binary> {
+    to_json_binary(&Empty {})
+}
+
+/// Reports the loan, with the data the hook responded with as text
+fn reply(_: DepsMut, _: Env, msg: Reply) -> StdResult<Response> {
+    let loan = parse_flash_loan_reply(msg)?;
+    let hook_data = match loan.hook_data {
+        Some(data) => String::from_utf8_lossy(data.as_slice()).into_owned(),
+        None => "none".to_string(),
+    };
+
+    Ok(Response::new().add_attributes(vec![
+        ("loan_id", loan.loan_id.to_string()),
+        ("vault_fee", loan.vault_fee.to_string()),
+        ("gateway_fee", loan.gateway_fee.to_string()),
+        ("hook_data", hook_data),
+    ]))
+}